@@ -0,0 +1,17 @@
+use std::sync::LazyLock;
+
+use tokio::sync::watch;
+
+use crate::frontend::audio::{AudioCommand, AudioState};
+
+/// There is no PipeWire outside of linux, so there are never any sinks.
+static STATE: LazyLock<watch::Sender<AudioState>> =
+    LazyLock::new(|| watch::channel(AudioState::default()).0);
+
+pub fn state() -> watch::Receiver<AudioState> {
+    STATE.subscribe()
+}
+
+pub fn control(_command: AudioCommand) -> anyhow::Result<()> {
+    anyhow::bail!("There is no audio output to control")
+}
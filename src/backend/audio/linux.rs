@@ -0,0 +1,521 @@
+//! Follows the audio sinks of PipeWire, and changes their volume and which one is the default.
+//!
+//! PipeWire stores volumes linearly, but we show them on a cubic scale like the desktop does,
+//! so the middle of the slider sounds about half as loud.
+
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    rc::{Rc, Weak},
+    sync::{Arc, LazyLock, Mutex},
+    thread,
+    time::Duration,
+};
+
+use anyhow::Context as _;
+use dioxus::prelude::*;
+use pipewire as pw;
+use pw::{
+    metadata::{Metadata, MetadataListener},
+    node::{Node, NodeListener},
+    registry::{GlobalObject, RegistryRc},
+    spa,
+    types::ObjectType,
+};
+use spa::{
+    param::ParamType,
+    pod::{
+        Object, Pod, Property, PropertyFlags, Value, ValueArray, deserialize::PodDeserializer,
+        serialize::PodSerializer,
+    },
+    sys::{SPA_PROP_channelVolumes, SPA_PROP_mute},
+    utils::{SpaTypes, dict::DictRef},
+};
+use tokio::sync::watch;
+
+use crate::frontend::audio::{AudioCommand, AudioState, Sink};
+
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// The metadata key PipeWire stores the default sink under.
+const DEFAULT_SINK_KEY: &str = "default.audio.sink";
+/// The metadata key to set to change the default sink, which is remembered across restarts.
+const CONFIGURED_SINK_KEY: &str = "default.configured.audio.sink";
+
+struct Context {
+    state_receiver: watch::Receiver<AudioState>,
+    /// Sends commands to the audio thread while it is connected to PipeWire
+    commands: Arc<Mutex<Option<pw::channel::Sender<AudioCommand>>>>,
+}
+
+impl Context {
+    fn new() -> Self {
+        let (state_tx, state_rx) = watch::channel(AudioState::default());
+        let commands = Arc::new(Mutex::new(None));
+        thread::spawn({
+            let commands = commands.clone();
+            move || audio_thread(state_tx, commands)
+        });
+
+        Self {
+            state_receiver: state_rx,
+            commands,
+        }
+    }
+}
+
+static CONTEXT: LazyLock<Context> = LazyLock::new(Context::new);
+
+pub fn state() -> watch::Receiver<AudioState> {
+    CONTEXT.state_receiver.clone()
+}
+
+pub fn control(command: AudioCommand) -> anyhow::Result<()> {
+    let commands = CONTEXT.commands.lock().unwrap();
+    let Some(commands) = commands.as_ref() else {
+        anyhow::bail!("Not connected to PipeWire");
+    };
+    commands
+        .send(command)
+        .map_err(|_| anyhow::anyhow!("The audio thread stopped"))
+}
+
+/// Follow the sinks forever, connecting to PipeWire again if the connection breaks.
+fn audio_thread(
+    state_tx: watch::Sender<AudioState>,
+    commands: Arc<Mutex<Option<pw::channel::Sender<AudioCommand>>>>,
+) {
+    pw::init();
+    loop {
+        let (command_tx, command_rx) = pw::channel::channel();
+        *commands.lock().unwrap() = Some(command_tx);
+
+        if let Err(err) = follow_sinks(&state_tx, command_rx) {
+            warn!("Failed to follow audio outputs: {:#}", err);
+        }
+
+        *commands.lock().unwrap() = None;
+        state_tx.send_replace(AudioState::default());
+        thread::sleep(RETRY_DELAY);
+    }
+}
+
+/// A sink we follow until PipeWire removes it.
+struct BoundSink {
+    node: Node,
+    _listener: NodeListener,
+    /// The `node.name`, which the default sink is stored as
+    name: String,
+    description: String,
+    /// Linear volume of each channel
+    channel_volumes: Vec<f32>,
+    muted: bool,
+}
+
+/// The metadata object holding the default sink.
+struct DefaultMetadata {
+    id: u32,
+    metadata: Metadata,
+    _listener: MetadataListener,
+}
+
+struct Sinks {
+    state_tx: watch::Sender<AudioState>,
+    sinks: BTreeMap<u32, BoundSink>,
+    metadata: Option<DefaultMetadata>,
+    /// The `node.name` of the default sink
+    default_sink: Option<String>,
+}
+
+impl Sinks {
+    /// Tell the websockets about the current state, if it changed.
+    fn publish(&self) {
+        let state = AudioState {
+            sinks: self
+                .sinks
+                .iter()
+                .map(|(&id, sink)| Sink {
+                    id,
+                    name: sink.description.clone(),
+                    volume: cubic_volume(&sink.channel_volumes),
+                    muted: sink.muted,
+                })
+                .collect(),
+            default_sink: self
+                .sinks
+                .iter()
+                .find(|(_, sink)| self.default_sink.as_ref() == Some(&sink.name))
+                .map(|(&id, _)| id),
+        };
+
+        self.state_tx.send_if_modified(|current| {
+            let modified = *current != state;
+            *current = state;
+            modified
+        });
+    }
+
+    fn sink(&self, id: u32) -> anyhow::Result<&BoundSink> {
+        self.sinks
+            .get(&id)
+            .with_context(|| format!("There is no sink with id {id}"))
+    }
+
+    fn run(&self, command: AudioCommand) -> anyhow::Result<()> {
+        match command {
+            AudioCommand::SetVolume { sink, volume } => {
+                let sink = self.sink(sink)?;
+                let volume = volume.clamp(0.0, 1.0).powi(3);
+                let channels = sink.channel_volumes.len().max(1);
+                set_prop(
+                    &sink.node,
+                    SPA_PROP_channelVolumes,
+                    Value::ValueArray(ValueArray::Float(vec![volume; channels])),
+                )
+            }
+            AudioCommand::SetMuted { sink, muted } => {
+                set_prop(&self.sink(sink)?.node, SPA_PROP_mute, Value::Bool(muted))
+            }
+            AudioCommand::SetDefaultSink(sink) => {
+                let sink = self.sink(sink)?;
+                let Some(metadata) = &self.metadata else {
+                    anyhow::bail!(
+                        "PipeWire has no default metadata, is a session manager running?"
+                    );
+                };
+                let value = serde_json::json!({ "name": sink.name }).to_string();
+                metadata.metadata.set_property(
+                    0,
+                    CONFIGURED_SINK_KEY,
+                    Some("Spa:String:JSON"),
+                    Some(&value),
+                );
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Get the volume shown to the user from the linear volumes of the channels.
+fn cubic_volume(channel_volumes: &[f32]) -> f32 {
+    if channel_volumes.is_empty() {
+        return 0.0;
+    }
+    let average = channel_volumes.iter().sum::<f32>() / channel_volumes.len() as f32;
+    average.cbrt()
+}
+
+/// Set a single property of the `Props` param of a node.
+fn set_prop(node: &Node, key: u32, value: Value) -> anyhow::Result<()> {
+    let props = Value::Object(Object {
+        type_: SpaTypes::ObjectParamProps.as_raw(),
+        id: ParamType::Props.as_raw(),
+        properties: vec![Property {
+            key,
+            flags: PropertyFlags::empty(),
+            value,
+        }],
+    });
+    let bytes = PodSerializer::serialize(std::io::Cursor::new(Vec::new()), &props)
+        .map_err(|err| anyhow::anyhow!("Failed to serialize props: {:?}", err))?
+        .0
+        .into_inner();
+    let pod = Pod::from_bytes(&bytes).context("Failed to create props pod")?;
+
+    node.set_param(ParamType::Props, 0, pod);
+    Ok(())
+}
+
+/// Get the channel volumes and mute state out of a `Props` param, as far as it has them.
+fn parse_props(param: &Pod) -> (Option<Vec<f32>>, Option<bool>) {
+    let Ok((_, Value::Object(object))) = PodDeserializer::deserialize_any_from(param.as_bytes())
+    else {
+        return (None, None);
+    };
+
+    let mut channel_volumes = None;
+    let mut muted = None;
+    for property in object.properties {
+        match (property.key, property.value) {
+            (SPA_PROP_channelVolumes, Value::ValueArray(ValueArray::Float(volumes))) => {
+                channel_volumes = Some(volumes);
+            }
+            (SPA_PROP_mute, Value::Bool(value)) => muted = Some(value),
+            _ => {}
+        }
+    }
+    (channel_volumes, muted)
+}
+
+/// Get the name out of a metadata value like `{ "name": "alsa_output.pci-0000_00_1f.3.hdmi-stereo" }`.
+fn json_name(value: &str) -> Option<String> {
+    #[derive(serde::Deserialize)]
+    struct NamedValue {
+        name: String,
+    }
+
+    serde_json::from_str::<NamedValue>(value)
+        .ok()
+        .map(|value| value.name)
+}
+
+fn follow_sinks(
+    state_tx: &watch::Sender<AudioState>,
+    commands: pw::channel::Receiver<AudioCommand>,
+) -> anyhow::Result<()> {
+    let mainloop = pw::main_loop::MainLoopRc::new(None).context("Failed to create main loop")?;
+    let context =
+        pw::context::ContextRc::new(&mainloop, None).context("Failed to create context")?;
+    let core = context
+        .connect_rc(None)
+        .context("Failed to connect to PipeWire")?;
+
+    // Errors on the core object itself mean the connection to PipeWire is gone
+    let failure = Rc::new(RefCell::new(Option::<String>::None));
+    let _core_listener = core
+        .add_listener_local()
+        .error({
+            let mainloop = mainloop.clone();
+            let failure = failure.clone();
+            move |id, _seq, res, message| {
+                if id == pw::core::PW_ID_CORE {
+                    failure
+                        .borrow_mut()
+                        .get_or_insert(format!("PipeWire connection failed ({res}): {message}"));
+                    mainloop.quit();
+                }
+            }
+        })
+        .register();
+
+    let registry = core
+        .get_registry_rc()
+        .context("Failed to get the registry")?;
+    let sinks = Rc::new(RefCell::new(Sinks {
+        state_tx: state_tx.clone(),
+        sinks: BTreeMap::new(),
+        metadata: None,
+        default_sink: None,
+    }));
+
+    let _registry_listener = registry
+        .add_listener_local()
+        .global({
+            let registry = registry.downgrade();
+            let sinks = Rc::downgrade(&sinks);
+            move |global| {
+                let (Some(registry), Some(sinks)) = (registry.upgrade(), sinks.upgrade()) else {
+                    return;
+                };
+                let Some(props) = global.props else {
+                    return;
+                };
+
+                match global.type_ {
+                    ObjectType::Node if props.get("media.class") == Some("Audio/Sink") => {
+                        match bind_sink(&registry, global, props, Rc::downgrade(&sinks)) {
+                            Ok(sink) => {
+                                sinks.borrow_mut().sinks.insert(global.id, sink);
+                                sinks.borrow().publish();
+                            }
+                            Err(err) => warn!("Failed to follow sink {}: {:#}", global.id, err),
+                        }
+                    }
+                    ObjectType::Metadata if props.get("metadata.name") == Some("default") => {
+                        match bind_metadata(&registry, global, Rc::downgrade(&sinks)) {
+                            Ok(metadata) => sinks.borrow_mut().metadata = Some(metadata),
+                            Err(err) => warn!("Failed to follow default metadata: {:#}", err),
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        })
+        .global_remove({
+            let sinks = Rc::downgrade(&sinks);
+            move |id| {
+                let Some(sinks) = sinks.upgrade() else {
+                    return;
+                };
+                let mut sinks = sinks.borrow_mut();
+                sinks.sinks.remove(&id);
+                if sinks
+                    .metadata
+                    .as_ref()
+                    .is_some_and(|metadata| metadata.id == id)
+                {
+                    sinks.metadata = None;
+                }
+                sinks.publish();
+            }
+        })
+        .register();
+
+    let _commands = commands.attach(mainloop.loop_(), {
+        let sinks = sinks.clone();
+        move |command| {
+            if let Err(err) = sinks.borrow().run(command) {
+                warn!("Failed to control audio: {:#}", err);
+            }
+        }
+    });
+
+    mainloop.run();
+
+    let failure = failure.borrow_mut().take();
+    Err(anyhow::anyhow!(
+        failure.unwrap_or_else(|| "The main loop stopped".to_owned())
+    ))
+}
+
+fn bind_sink(
+    registry: &RegistryRc,
+    global: &GlobalObject<&DictRef>,
+    props: &DictRef,
+    sinks: Weak<RefCell<Sinks>>,
+) -> anyhow::Result<BoundSink> {
+    let node: Node = registry.bind(global).context("Failed to bind node")?;
+    let id = global.id;
+
+    let listener = node
+        .add_listener_local()
+        .param(move |_seq, param_type, _index, _next, param| {
+            if param_type != ParamType::Props {
+                return;
+            }
+            let (Some(sinks), Some(param)) = (sinks.upgrade(), param) else {
+                return;
+            };
+
+            let (channel_volumes, muted) = parse_props(param);
+            let mut sinks = sinks.borrow_mut();
+            if let Some(sink) = sinks.sinks.get_mut(&id) {
+                if let Some(channel_volumes) = channel_volumes {
+                    sink.channel_volumes = channel_volumes;
+                }
+                if let Some(muted) = muted {
+                    sink.muted = muted;
+                }
+            }
+            sinks.publish();
+        })
+        .register();
+    node.subscribe_params(&[ParamType::Props]);
+
+    let name = props.get("node.name").unwrap_or_default().to_owned();
+    let description = props
+        .get("node.description")
+        .or_else(|| props.get("node.nick"))
+        .unwrap_or(name.as_str())
+        .to_owned();
+
+    Ok(BoundSink {
+        node,
+        _listener: listener,
+        name,
+        description,
+        channel_volumes: Vec::new(),
+        muted: false,
+    })
+}
+
+fn bind_metadata(
+    registry: &RegistryRc,
+    global: &GlobalObject<&DictRef>,
+    sinks: Weak<RefCell<Sinks>>,
+) -> anyhow::Result<DefaultMetadata> {
+    let metadata: Metadata = registry.bind(global).context("Failed to bind metadata")?;
+
+    let listener = metadata
+        .add_listener_local()
+        .property(move |subject, key, _type, value| {
+            // A missing key means everything was cleared
+            if subject == 0
+                && key.is_none_or(|key| key == DEFAULT_SINK_KEY)
+                && let Some(sinks) = sinks.upgrade()
+            {
+                let mut sinks = sinks.borrow_mut();
+                sinks.default_sink = value.and_then(json_name);
+                sinks.publish();
+            }
+            0
+        })
+        .register();
+
+    Ok(DefaultMetadata {
+        id: global.id,
+        metadata,
+        _listener: listener,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn serialize(value: &Value) -> Vec<u8> {
+        PodSerializer::serialize(std::io::Cursor::new(Vec::new()), value)
+            .unwrap()
+            .0
+            .into_inner()
+    }
+
+    #[test]
+    fn cubic_volume_averages_the_channels() {
+        assert_eq!(cubic_volume(&[]), 0.0);
+        assert_eq!(cubic_volume(&[0.125, 0.125]), 0.5);
+        assert_eq!(cubic_volume(&[1.0, 0.0]), 0.5f32.cbrt());
+    }
+
+    #[test]
+    fn json_name_reads_the_metadata_value() {
+        assert_eq!(
+            json_name(r#"{ "name": "alsa_output.pci-0000_00_1f.3.hdmi-stereo" }"#).as_deref(),
+            Some("alsa_output.pci-0000_00_1f.3.hdmi-stereo")
+        );
+        assert_eq!(
+            json_name(r#"{"name":"say \"hi\""}"#).as_deref(),
+            Some(r#"say "hi""#)
+        );
+        assert_eq!(json_name(r#"{ "other": "sink" }"#), None);
+        assert_eq!(json_name("not json"), None);
+
+        let value = serde_json::json!({ "name": r#"odd "sink" \ name"# }).to_string();
+        assert_eq!(json_name(&value).as_deref(), Some(r#"odd "sink" \ name"#));
+    }
+
+    #[test]
+    fn parse_props_finds_volumes_and_mute() {
+        let props = Value::Object(Object {
+            type_: SpaTypes::ObjectParamProps.as_raw(),
+            id: ParamType::Props.as_raw(),
+            properties: vec![
+                Property {
+                    key: SPA_PROP_channelVolumes,
+                    flags: PropertyFlags::empty(),
+                    value: Value::ValueArray(ValueArray::Float(vec![0.25, 0.5])),
+                },
+                Property {
+                    key: SPA_PROP_mute,
+                    flags: PropertyFlags::empty(),
+                    value: Value::Bool(true),
+                },
+            ],
+        });
+        let bytes = serialize(&props);
+        assert_eq!(
+            parse_props(Pod::from_bytes(&bytes).unwrap()),
+            (Some(vec![0.25, 0.5]), Some(true))
+        );
+
+        let bytes = serialize(&Value::Object(Object {
+            type_: SpaTypes::ObjectParamProps.as_raw(),
+            id: ParamType::Props.as_raw(),
+            properties: Vec::new(),
+        }));
+        assert_eq!(parse_props(Pod::from_bytes(&bytes).unwrap()), (None, None));
+
+        let bytes = serialize(&Value::Int(1));
+        assert_eq!(parse_props(Pod::from_bytes(&bytes).unwrap()), (None, None));
+    }
+}
@@ -0,0 +1,57 @@
+//! Controls the volume and audio output of the media PC.
+
+use dioxus::fullstack::{PostcardEncoding, WebSocketOptions, Websocket};
+use dioxus::prelude::*;
+
+#[cfg(all(target_os = "linux", feature = "server"))]
+mod linux;
+#[cfg(all(target_os = "linux", feature = "server"))]
+use linux as implementation;
+
+#[cfg(all(not(target_os = "linux"), feature = "server"))]
+mod dummy;
+#[cfg(all(not(target_os = "linux"), feature = "server"))]
+use dummy as implementation;
+
+use crate::frontend::audio::{AudioCommand, AudioState};
+#[cfg(feature = "server")]
+use crate::{backend::auth::require_role, frontend::devices::Role};
+
+/// Follow the audio outputs of the media PC, and change their volume.
+///
+/// The current state is sent right away, and again whenever it changes.
+#[get("/api/audio")]
+pub async fn audio(
+    options: WebSocketOptions,
+) -> Result<Websocket<AudioCommand, AudioState, PostcardEncoding>, HttpError> {
+    require_role(Role::Guest)?;
+
+    Ok(options.on_upgrade(|mut socket| async move {
+        let mut state = implementation::state();
+        state.mark_changed();
+
+        loop {
+            tokio::select! {
+                command = socket.recv() => {
+                    let command = match command {
+                        Ok(command) => command,
+                        Err(err) => {
+                            error!("socket.recv() returned an error: {err}");
+                            return;
+                        }
+                    };
+
+                    if let Err(err) = implementation::control(command) {
+                        warn!("Failed to control audio: {:#}", err);
+                    }
+                }
+                Ok(()) = state.changed() => {
+                    let new_state = state.borrow_and_update().clone();
+                    if let Err(err) = socket.send(new_state).await {
+                        warn!("Failed to send message: {}", err);
+                    }
+                }
+            }
+        }
+    }))
+}
@@ -0,0 +1,283 @@
+//! The devices paired with the media PC, stored in the state directory.
+
+use std::{
+    io,
+    net::SocketAddr,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Context as _;
+use axum::{
+    extract::{ConnectInfo, Request},
+    http::{
+        HeaderValue, StatusCode,
+        header::{ACCEPT, COOKIE, SET_COOKIE},
+    },
+    middleware::Next,
+    response::{IntoResponse, Redirect, Response},
+};
+use dioxus::prelude::*;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    fs,
+    sync::{Mutex, OnceCell},
+};
+
+use crate::{
+    backend::{config, tls},
+    frontend::devices::Role,
+};
+
+const COOKIE_NAME: &str = "empc_device";
+/// Paired devices stay paired until they are revoked, so the cookie should outlive the phone.
+const COOKIE_MAX_AGE: u64 = 10 * 365 * 24 * 60 * 60;
+
+/// What can be used without being paired, which is just what is needed to pair.
+///
+/// The pairing screen checks its own token before showing the PIN.
+const PUBLIC_PATHS: [&str; 7] = [
+    "/pair",
+    "/pair/screen",
+    "/api/auth/pin/request",
+    "/api/auth/pin/screen",
+    "/api/auth/pair",
+    "/api/discovery/qr",
+    "/api/tls/fingerprint",
+];
+
+/// The id of the media PC itself, which never has to pair.
+const MEDIA_PC_ID: &str = "media-pc";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Device {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub role: Role,
+    token: String,
+    /// Seconds since the unix epoch
+    pub paired_at: u64,
+    /// Seconds since the unix epoch, only written to disk when the list of devices changes
+    pub last_seen: u64,
+}
+
+impl Device {
+    /// The browser running on the media PC, which is trusted since it can be used to see the PIN anyway.
+    pub fn media_pc() -> Self {
+        let now = now();
+        Self {
+            id: MEDIA_PC_ID.to_owned(),
+            name: "This media PC".to_owned(),
+            role: Role::Admin,
+            token: String::new(),
+            paired_at: now,
+            last_seen: now,
+        }
+    }
+}
+
+static DEVICES: OnceCell<Mutex<Vec<Device>>> = OnceCell::const_new();
+
+async fn devices() -> &'static Mutex<Vec<Device>> {
+    DEVICES
+        .get_or_init(|| async {
+            let devices = read_devices().await.unwrap_or_else(|err| {
+                warn!("Failed to read paired devices: {:#}", err);
+                Vec::new()
+            });
+            Mutex::new(devices)
+        })
+        .await
+}
+
+fn devices_path() -> anyhow::Result<PathBuf> {
+    Ok(dirs::state_dir()
+        .or_else(dirs::data_local_dir)
+        .context("Failed to find the state directory")?
+        .join("empc")
+        .join("devices.json"))
+}
+
+async fn read_devices() -> anyhow::Result<Vec<Device>> {
+    let path = devices_path()?;
+    match fs::read(&path).await {
+        Ok(json) => serde_json::from_slice(&json)
+            .with_context(|| format!("Failed to parse paired devices {path:?}")),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(err).with_context(|| format!("Failed to read paired devices {path:?}")),
+    }
+}
+
+async fn write_devices(devices: &[Device]) -> anyhow::Result<()> {
+    let path = devices_path()?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .await
+            .with_context(|| format!("Failed to create state directory {dir:?}"))?;
+    }
+    let json = serde_json::to_vec_pretty(devices)?;
+
+    // Write to a temporary file first, so a crash can't unpair every device
+    let temporary = path.with_extension("json.tmp");
+    fs::write(&temporary, json)
+        .await
+        .with_context(|| format!("Failed to write paired devices {temporary:?}"))?;
+    #[cfg(unix)]
+    {
+        use std::{fs::Permissions, os::unix::fs::PermissionsExt};
+        fs::set_permissions(&temporary, Permissions::from_mode(0o600))
+            .await
+            .with_context(|| format!("Failed to restrict permissions of {temporary:?}"))?;
+    }
+    fs::rename(&temporary, &path)
+        .await
+        .with_context(|| format!("Failed to replace paired devices {path:?}"))
+}
+
+/// Pair a new device, returning the cookie it authenticates with from now on.
+pub async fn add(name: String) -> anyhow::Result<String> {
+    let token = random_hex(32)?;
+    let now = now();
+
+    let mut devices = devices().await.lock().await;
+    // Somebody has to be able to manage the others
+    let role = if devices.is_empty() {
+        Role::Admin
+    } else {
+        Role::default()
+    };
+    devices.push(Device {
+        id: random_hex(8)?,
+        name,
+        role,
+        token: token.clone(),
+        paired_at: now,
+        last_seen: now,
+    });
+    write_devices(&devices).await?;
+
+    Ok(cookie(&token))
+}
+
+/// The cookie a device authenticates with, only sent over HTTPS when serving HTTPS.
+fn cookie(token: &str) -> String {
+    let secure = if tls::is_serving() { "; Secure" } else { "" };
+    format!(
+        "{COOKIE_NAME}={token}; Path=/; Max-Age={COOKIE_MAX_AGE}; HttpOnly; SameSite=Lax{secure}"
+    )
+}
+
+/// Unpair a device, returning whether it was paired.
+pub async fn remove(id: &str) -> anyhow::Result<bool> {
+    let mut devices = devices().await.lock().await;
+    let count = devices.len();
+    devices.retain(|device| device.id != id);
+    if devices.len() == count {
+        return Ok(false);
+    }
+    write_devices(&devices).await?;
+
+    Ok(true)
+}
+
+/// Change what a device may do, returning whether it is paired.
+pub async fn set_role(id: &str, role: Role) -> anyhow::Result<bool> {
+    let mut devices = devices().await.lock().await;
+    let Some(device) = devices.iter_mut().find(|device| device.id == id) else {
+        return Ok(false);
+    };
+    device.role = role;
+    write_devices(&devices).await?;
+
+    Ok(true)
+}
+
+pub async fn list() -> Vec<Device> {
+    devices().await.lock().await.clone()
+}
+
+/// Let paired devices and the media PC itself through, and send everybody else to pair.
+///
+/// Connections over loopback are the media PC only if `auth.trust_loopback` is set.
+///
+/// The device is added to the request extensions, where server functions can find it.
+pub async fn require_device(mut request: Request, next: Next) -> Response {
+    let path = request.uri().path().to_owned();
+
+    match authenticate(&request).await {
+        Some(device) => {
+            // Devices paired before TLS was enabled have a cookie without Secure, so it is
+            // set again whenever they load a page
+            let reissue = (tls::is_serving() && !device.token.is_empty() && accepts_html(&request))
+                .then(|| cookie(&device.token));
+
+            request.extensions_mut().insert(device);
+            let mut response = next.run(request).await;
+            if let Some(value) = reissue.and_then(|cookie| HeaderValue::from_str(&cookie).ok()) {
+                response.headers_mut().append(SET_COOKIE, value);
+            }
+            response
+        }
+        None if PUBLIC_PATHS.contains(&path.as_str()) => next.run(request).await,
+        None if path.starts_with("/api/") => {
+            (StatusCode::UNAUTHORIZED, "This device is not paired").into_response()
+        }
+        None if accepts_html(&request) => Redirect::to("/pair").into_response(),
+        // The stylesheets and the wasm bundle are needed to pair
+        None => next.run(request).await,
+    }
+}
+
+async fn authenticate(request: &Request) -> Option<Device> {
+    // Anything on this machine can connect over loopback, so it is only trusted when asked to
+    let local = config::get().auth.trust_loopback
+        && request
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .is_some_and(|ConnectInfo(address)| address.ip().is_loopback());
+    if local {
+        return Some(Device::media_pc());
+    }
+
+    let token = request
+        .headers()
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .find_map(|cookie| cookie.trim().strip_prefix(COOKIE_NAME)?.strip_prefix('='))?;
+
+    let mut devices = devices().await.lock().await;
+    let device = devices
+        .iter_mut()
+        .find(|device| constant_time_eq(device.token.as_bytes(), token.as_bytes()))?;
+    device.last_seen = now();
+    Some(device.clone())
+}
+
+fn accepts_html(request: &Request) -> bool {
+    request
+        .headers()
+        .get(ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/html"))
+}
+
+/// Compare without returning early, so the time taken doesn't tell how much of a token was guessed right.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+pub fn random_hex(bytes: usize) -> anyhow::Result<String> {
+    let mut buffer = vec![0; bytes];
+    getrandom::fill(&mut buffer).context("Failed to generate random bytes")?;
+    Ok(buffer.iter().map(|byte| format!("{byte:02x}")).collect())
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
@@ -0,0 +1,168 @@
+//! Keeps everybody out except devices paired with the media PC.
+//!
+//! The TV shows a PIN, which is entered on the phone to get a device token.
+//! The token is kept in a cookie and checked by [`require_device`] on every request.
+
+#[cfg(feature = "server")]
+mod devices;
+#[cfg(feature = "server")]
+mod pin;
+
+#[cfg(feature = "server")]
+pub use devices::{Device, require_device};
+
+use dioxus::fullstack::{PostcardEncoding, SetCookie, SetHeader, WebSocketOptions, Websocket};
+use dioxus::prelude::*;
+
+use crate::frontend::devices::{PairedDevice, Role};
+
+/// The path of the pairing screen for the browser on the TV, which shows the PIN without pairing.
+#[cfg(feature = "server")]
+pub fn pairing_screen_path() -> Option<String> {
+    pin::screen_path()
+}
+
+/// The device making the current request, as found by [`require_device`].
+#[cfg(feature = "server")]
+pub fn current_device() -> Result<Device, HttpError> {
+    dioxus::fullstack::FullstackContext::current()
+        .and_then(|context| context.extension::<Device>())
+        .ok_or_else(|| HttpError::new(StatusCode::UNAUTHORIZED, "This device is not paired"))
+}
+
+/// The device making the current request, if it may do what the given role may.
+#[cfg(feature = "server")]
+pub fn require_role(role: Role) -> Result<Device, HttpError> {
+    let device = current_device()?;
+    if device.role < role {
+        return HttpError::forbidden(format!(
+            "{:?} is a {} device, but this needs a {} device",
+            device.name, device.role, role
+        ));
+    }
+    Ok(device)
+}
+
+/// What the current device may do.
+#[get("/api/auth/role")]
+pub async fn current_role() -> Result<Role, HttpError> {
+    Ok(current_device()?.role)
+}
+
+/// Show a new PIN on the TV to pair with, at most every 30 seconds.
+#[post("/api/auth/pin/request")]
+pub async fn request_pin() -> Result<(), HttpError> {
+    pin::show_new()
+}
+
+/// Follow the PIN to pair with, for the pairing screen on the TV.
+///
+/// Only the screen opened with the token printed at startup and admins may see the PIN,
+/// since anybody else could pair with it.
+#[get("/api/auth/pin/screen?token")]
+pub async fn pin_screen(
+    options: WebSocketOptions,
+    token: String,
+) -> Result<Websocket<(), Option<String>, PostcardEncoding>, HttpError> {
+    if !pin::is_screen_token(&token) && require_role(Role::Admin).is_err() {
+        return HttpError::forbidden(
+            "The PIN is only shown on the pairing screen opened with the URL EMPC printed at startup",
+        );
+    }
+
+    Ok(options.on_upgrade(|mut socket| async move {
+        let mut pin = pin::subscribe();
+        pin.mark_changed();
+
+        loop {
+            tokio::select! {
+                message = socket.recv() => {
+                    if let Err(err) = message {
+                        error!("socket.recv() returned an error: {err}");
+                        return;
+                    }
+                }
+                Ok(()) = pin.changed() => {
+                    let code = pin.borrow_and_update().as_ref().map(|pin| pin.code.clone());
+                    if let Err(err) = socket.send(code).await {
+                        warn!("Failed to send message: {}", err);
+                    }
+                }
+            }
+        }
+    }))
+}
+
+/// Pair this device using the PIN shown on the TV, setting the cookie it is known by from now on.
+#[post("/api/auth/pair")]
+pub async fn pair(code: String, name: String) -> Result<SetHeader<SetCookie>, HttpError> {
+    pin::check(&code)?;
+
+    let name = name.trim();
+    let name = if name.is_empty() {
+        "Unnamed device"
+    } else {
+        name
+    };
+    let cookie = devices::add(name.to_owned())
+        .await
+        .map_err(|err| HttpError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("{err:#}")))?;
+    println!("Paired {name:?}");
+
+    SetHeader::new(cookie)
+        .map_err(|err| HttpError::new(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))
+}
+
+/// List the paired devices, or only the current one if it isn't an admin.
+#[get("/api/auth/devices")]
+pub async fn paired_devices() -> Result<Vec<PairedDevice>, HttpError> {
+    let current = current_device()?;
+    let now = devices::now();
+
+    Ok(devices::list()
+        .await
+        .into_iter()
+        .filter(|device| current.role >= Role::Admin || device.id == current.id)
+        .map(|device| PairedDevice {
+            current: device.id == current.id,
+            paired_secs_ago: now.saturating_sub(device.paired_at),
+            last_seen_secs_ago: now.saturating_sub(device.last_seen),
+            id: device.id,
+            name: device.name,
+            role: device.role,
+        })
+        .collect())
+}
+
+/// Unpair a device, so it has to be paired again before it can be used.
+///
+/// Every device may unpair itself, but only admins may unpair others.
+#[post("/api/auth/devices/revoke")]
+pub async fn revoke_device(id: String) -> Result<(), HttpError> {
+    if current_device()?.id != id {
+        require_role(Role::Admin)?;
+    }
+
+    match devices::remove(&id).await {
+        Ok(true) => {
+            println!("Revoked device {id}");
+            Ok(())
+        }
+        Ok(false) => HttpError::not_found(format!("No device {id} is paired")),
+        Err(err) => HttpError::internal_server_error(format!("{err:#}")),
+    }
+}
+
+#[post("/api/auth/devices/role")]
+pub async fn set_device_role(id: String, role: Role) -> Result<(), HttpError> {
+    require_role(Role::Admin)?;
+
+    match devices::set_role(&id, role).await {
+        Ok(true) => {
+            println!("Device {id} is now a {role} device");
+            Ok(())
+        }
+        Ok(false) => HttpError::not_found(format!("No device {id} is paired")),
+        Err(err) => HttpError::internal_server_error(format!("{err:#}")),
+    }
+}
@@ -0,0 +1,291 @@
+//! The PIN shown on the TV while a phone is being paired.
+
+use std::{
+    sync::{LazyLock, Mutex},
+    time::{Duration, Instant},
+};
+
+use dioxus::prelude::*;
+use tokio::{sync::watch, time::sleep};
+
+use super::devices::{constant_time_eq, random_hex};
+
+const PIN_LIFETIME: Duration = Duration::from_secs(5 * 60);
+/// Wrong guesses allowed before a new PIN has to be shown.
+const MAX_ATTEMPTS: u32 = 5;
+/// How often a new PIN may be shown, so guessing can't be sped up by showing PINs all the time.
+const REQUEST_COOLDOWN: Duration = Duration::from_secs(30);
+/// Wrong guesses allowed across all PINs before pairing is locked.
+const MAX_FAILURES: u32 = 10;
+const LOCKOUT: Duration = Duration::from_secs(15 * 60);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pin {
+    pub code: String,
+    attempts: u32,
+}
+
+static PIN: LazyLock<watch::Sender<Option<Pin>>> = LazyLock::new(|| watch::channel(None).0);
+
+/// Limits on guessing, which outlive the PINs they were counted for.
+#[derive(Default)]
+struct Limits {
+    last_shown: Option<Instant>,
+    /// Wrong guesses since the last successful pairing or lockout
+    failures: u32,
+    locked_until: Option<Instant>,
+}
+
+impl Limits {
+    fn check_lockout(&mut self, now: Instant) -> Result<(), HttpError> {
+        let Some(until) = self.locked_until else {
+            return Ok(());
+        };
+        let left = until.saturating_duration_since(now);
+        if left.is_zero() {
+            self.locked_until = None;
+            return Ok(());
+        }
+        Err(HttpError::new(
+            StatusCode::TOO_MANY_REQUESTS,
+            format!(
+                "Too many wrong PINs, try again in {} minutes",
+                left.as_secs().div_ceil(60)
+            ),
+        ))
+    }
+
+    /// Check whether a new PIN may be shown, and count it as shown if so.
+    fn show_new(&mut self, now: Instant) -> Result<(), HttpError> {
+        self.check_lockout(now)?;
+        if let Some(last_shown) = self.last_shown {
+            let left = REQUEST_COOLDOWN.saturating_sub(now.saturating_duration_since(last_shown));
+            if !left.is_zero() {
+                return Err(HttpError::new(
+                    StatusCode::TOO_MANY_REQUESTS,
+                    format!(
+                        "A PIN was just shown, wait {} seconds for a new one",
+                        left.as_secs_f32().ceil()
+                    ),
+                ));
+            }
+        }
+        self.last_shown = Some(now);
+        Ok(())
+    }
+
+    /// Count a guess at the PIN that is shown, returning whether pairing is locked now.
+    fn count_guess(&mut self, correct: bool, now: Instant) -> bool {
+        if correct {
+            self.failures = 0;
+            return false;
+        }
+        self.failures += 1;
+        if self.failures < MAX_FAILURES {
+            return false;
+        }
+
+        println!("Too many wrong PINs, pairing is locked for {LOCKOUT:?}");
+        self.failures = 0;
+        self.locked_until = Some(now + LOCKOUT);
+        true
+    }
+}
+
+static LIMITS: LazyLock<Mutex<Limits>> = LazyLock::new(Mutex::default);
+
+/// Lets the pairing screen on the TV follow the PIN without pairing, since it is opened with
+/// this token in its URL. A new one is made every start.
+static SCREEN_TOKEN: LazyLock<Option<String>> = LazyLock::new(|| {
+    random_hex(16)
+        .inspect_err(|err| warn!("Failed to generate the pairing screen token: {:#}", err))
+        .ok()
+});
+
+/// The path of the pairing screen, with the token that lets it show the PIN.
+pub fn screen_path() -> Option<String> {
+    Some(format!("/pair/screen?token={}", SCREEN_TOKEN.as_deref()?))
+}
+
+pub fn is_screen_token(token: &str) -> bool {
+    SCREEN_TOKEN
+        .as_deref()
+        .is_some_and(|expected| constant_time_eq(expected.as_bytes(), token.as_bytes()))
+}
+
+/// Follow the PIN currently shown, if there is one.
+pub fn subscribe() -> watch::Receiver<Option<Pin>> {
+    PIN.subscribe()
+}
+
+/// Replace the PIN with a new one, and show it on the TV.
+pub fn show_new() -> Result<(), HttpError> {
+    LIMITS.lock().unwrap().show_new(Instant::now())?;
+
+    let code = match getrandom::u32() {
+        Ok(random) => format!("{:06}", random % 1_000_000),
+        Err(err) => {
+            return HttpError::internal_server_error(format!("Failed to generate a PIN: {err}"));
+        }
+    };
+    println!("Pairing PIN: {code}");
+
+    PIN.send_replace(Some(Pin {
+        code: code.clone(),
+        attempts: 0,
+    }));
+
+    #[cfg(target_os = "linux")]
+    tokio::spawn(notify(code.clone()));
+
+    tokio::spawn(async move {
+        sleep(PIN_LIFETIME).await;
+        PIN.send_if_modified(|pin| {
+            let expired = pin.as_ref().is_some_and(|pin| pin.code == code);
+            if expired {
+                *pin = None;
+            }
+            expired
+        });
+    });
+
+    Ok(())
+}
+
+/// Check a PIN entered on a phone. Each PIN can only be used once.
+pub fn check(code: &str) -> Result<(), HttpError> {
+    let now = Instant::now();
+    let mut limits = LIMITS.lock().unwrap();
+    limits.check_lockout(now)?;
+
+    // Only guesses at a PIN that is shown count, nothing can be guessed without one
+    let mut correct = None;
+    PIN.send_if_modified(|pin| {
+        let Some(current) = pin else {
+            return false;
+        };
+        let matches = constant_time_eq(current.code.as_bytes(), code.trim().as_bytes());
+        current.attempts += 1;
+        if matches || current.attempts >= MAX_ATTEMPTS {
+            *pin = None;
+        }
+        correct = Some(matches);
+        true
+    });
+
+    if let Some(correct) = correct {
+        let locked = limits.count_guess(correct, now);
+        if correct {
+            return Ok(());
+        }
+        if locked {
+            // Nobody gets to guess the PIN that is shown now either
+            PIN.send_replace(None);
+        }
+    }
+    HttpError::forbidden("Wrong or expired PIN, show a new one and try again")
+}
+
+#[cfg(target_os = "linux")]
+#[zbus::proxy(
+    interface = "org.freedesktop.Notifications",
+    default_service = "org.freedesktop.Notifications",
+    default_path = "/org/freedesktop/Notifications"
+)]
+trait Notifications {
+    #[allow(clippy::too_many_arguments)]
+    fn notify(
+        &self,
+        app_name: &str,
+        replaces_id: u32,
+        app_icon: &str,
+        summary: &str,
+        body: &str,
+        actions: &[&str],
+        hints: std::collections::HashMap<&str, zbus::zvariant::Value<'_>>,
+        expire_timeout: i32,
+    ) -> zbus::Result<u32>;
+}
+
+/// Show the PIN as a desktop notification, so it can be read from the couch.
+#[cfg(target_os = "linux")]
+async fn notify(code: String) {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use anyhow::Context as _;
+
+    /// The last notification, which is replaced instead of piling up PINs.
+    static NOTIFICATION: AtomicU32 = AtomicU32::new(0);
+
+    let result = async {
+        let connection = zbus::Connection::session()
+            .await
+            .context("Failed to connect to the session bus")?;
+        let proxy = NotificationsProxy::new(&connection).await?;
+        let id = proxy
+            .notify(
+                "EMPC",
+                NOTIFICATION.load(Ordering::Relaxed),
+                "phone",
+                "Pair a device",
+                &format!("Enter the PIN {code} on your phone"),
+                &[],
+                Default::default(),
+                PIN_LIFETIME.as_millis() as i32,
+            )
+            .await?;
+        NOTIFICATION.store(id, Ordering::Relaxed);
+        anyhow::Ok(())
+    };
+
+    if let Err(err) = result.await {
+        warn!("Failed to show the PIN as a notification: {:#}", err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_pins_wait_for_the_cooldown() {
+        let start = Instant::now();
+        let mut limits = Limits::default();
+        assert!(limits.show_new(start).is_ok());
+        assert!(limits.show_new(start + Duration::from_secs(10)).is_err());
+        assert!(limits.show_new(start + REQUEST_COOLDOWN).is_ok());
+        assert!(limits.show_new(start + REQUEST_COOLDOWN * 2).is_ok());
+    }
+
+    #[test]
+    fn wrong_guesses_lock_pairing() {
+        let start = Instant::now();
+        let mut limits = Limits::default();
+        for _ in 1..MAX_FAILURES {
+            assert!(!limits.count_guess(false, start));
+        }
+        // A correct guess starts counting again
+        assert!(!limits.count_guess(true, start));
+        for _ in 1..MAX_FAILURES {
+            assert!(!limits.count_guess(false, start));
+        }
+        assert!(limits.count_guess(false, start));
+
+        assert!(limits.check_lockout(start + LOCKOUT / 2).is_err());
+        assert!(limits.show_new(start + LOCKOUT / 2).is_err());
+        assert!(limits.check_lockout(start + LOCKOUT).is_ok());
+        assert_eq!(limits.failures, 0);
+        assert!(limits.show_new(start + LOCKOUT).is_ok());
+    }
+
+    #[test]
+    fn guesses_without_a_pin_are_not_counted() {
+        PIN.send_replace(None);
+        for _ in 0..MAX_FAILURES * 2 {
+            assert!(check("123456").is_err());
+        }
+        let limits = LIMITS.lock().unwrap();
+        assert_eq!(limits.failures, 0);
+        assert!(limits.locked_until.is_none());
+    }
+}
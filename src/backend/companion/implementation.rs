@@ -0,0 +1,136 @@
+use std::time::Duration;
+
+use anyhow::Context as _;
+use axum::{
+    extract::Request,
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Redirect, Response},
+};
+use dioxus::fullstack::reqwest::Client;
+use tokio::net::UdpSocket;
+
+/// Magic packets are sent a few times, since they are UDP and may get lost.
+const MAGIC_PACKET_REPEATS: usize = 3;
+const POLL_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Parse a MAC address like `aa:bb:cc:dd:ee:ff` or `aa-bb-cc-dd-ee-ff`.
+pub fn parse_mac(mac: &str) -> Option<[u8; 6]> {
+    let mut bytes = [0; 6];
+    let mut parts = mac.split([':', '-']);
+    for byte in &mut bytes {
+        *byte = u8::from_str_radix(parts.next()?, 16).ok()?;
+    }
+    parts.next().is_none().then_some(bytes)
+}
+
+/// Send a Wake-on-LAN magic packet, which is the MAC address 16 times after 6 bytes of `0xff`.
+pub async fn send_magic_packet(
+    mac: [u8; 6],
+    broadcast: std::net::SocketAddr,
+) -> anyhow::Result<()> {
+    let mut packet = vec![0xff; 6];
+    for _ in 0..16 {
+        packet.extend_from_slice(&mac);
+    }
+
+    let socket = UdpSocket::bind(("0.0.0.0", 0))
+        .await
+        .context("Failed to open a UDP socket")?;
+    socket.set_broadcast(true)?;
+    for _ in 0..MAGIC_PACKET_REPEATS {
+        socket
+            .send_to(&packet, broadcast)
+            .await
+            .with_context(|| format!("Failed to send the magic packet to {broadcast}"))?;
+    }
+    Ok(())
+}
+
+/// A client for [`is_awake`], which doesn't check certificates, since media PCs usually
+/// serve self-signed ones and only whether they answer matters.
+pub fn probe_client() -> anyhow::Result<Client> {
+    Client::builder()
+        .danger_accept_invalid_certs(true)
+        .build()
+        .context("Failed to create an HTTP client")
+}
+
+/// Whether EMPC answers at the given URL, with anything at all.
+pub async fn is_awake(client: &Client, url: &str) -> bool {
+    client.get(url).timeout(POLL_TIMEOUT).send().await.is_ok()
+}
+
+/// Only serve the companion page in companion mode, since there is nothing else to control.
+pub async fn companion_only(request: Request, next: Next) -> Response {
+    let path = request.uri().path().to_owned();
+    if path == "/companion" || path.starts_with("/api/companion/") {
+        return next.run(request).await;
+    }
+    if path.starts_with("/api/") {
+        return (StatusCode::NOT_FOUND, "This is an EMPC companion").into_response();
+    }
+
+    let html = request
+        .headers()
+        .get(axum::http::header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/html"));
+    if html {
+        return Redirect::to("/companion").into_response();
+    }
+    // The stylesheets and the wasm bundle
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::Router;
+    use axum_server::tls_rustls::RustlsConfig;
+    use rustls::{
+        ServerConfig,
+        pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject},
+    };
+
+    use super::*;
+
+    /// Serve HTTPS with a self-signed certificate on a free port, returning its URL.
+    fn serve_self_signed() -> String {
+        let generated = rcgen::generate_simple_self_signed(vec!["localhost".to_owned()]).unwrap();
+        let cert = CertificateDer::from_pem_slice(generated.cert.pem().as_bytes()).unwrap();
+        let key = PrivateKeyDer::from_pem_slice(generated.signing_key.serialize_pem().as_bytes())
+            .unwrap();
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let tls = ServerConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_no_client_auth()
+            .with_single_cert(vec![cert], key)
+            .unwrap();
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(
+            axum_server::from_tcp_rustls(listener, RustlsConfig::from_config(Arc::new(tls)))
+                .serve(Router::new().into_make_service()),
+        );
+        format!("https://{addr}/")
+    }
+
+    #[tokio::test]
+    async fn self_signed_media_pcs_are_awake() {
+        let url = serve_self_signed();
+        assert!(is_awake(&probe_client().unwrap(), &url).await);
+        // A client checking certificates would never see it wake up
+        assert!(!is_awake(&Client::new(), &url).await);
+
+        let closed = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        assert!(!is_awake(&probe_client().unwrap(), &format!("https://{closed}/")).await);
+    }
+}
@@ -0,0 +1,85 @@
+//! Companion mode, for an always-on device that wakes the media PC with Wake-on-LAN.
+//!
+//! Once the media PC answers, the browser is sent to it, so powering off and on again
+//! needs nothing outside the local network.
+
+#[cfg(feature = "server")]
+mod implementation;
+#[cfg(feature = "server")]
+pub use implementation::{companion_only, parse_mac};
+
+use dioxus::fullstack::{PostcardEncoding, WebSocketOptions, Websocket};
+use dioxus::prelude::*;
+
+#[cfg(feature = "server")]
+use crate::backend::config;
+use crate::frontend::companion::WakeStatus;
+
+/// The names of the media PCs that can be woken.
+#[get("/api/companion/machines")]
+pub async fn machines() -> Result<Vec<String>, HttpError> {
+    Ok(config::get()
+        .companion
+        .machines
+        .iter()
+        .map(|machine| machine.name.clone())
+        .collect())
+}
+
+/// Wake a media PC, and follow it waking up.
+#[get("/api/companion/wake?name")]
+pub async fn wake(
+    options: WebSocketOptions,
+    name: String,
+) -> Result<Websocket<(), WakeStatus, PostcardEncoding>, HttpError> {
+    use std::time::{Duration, Instant};
+
+    let config = config::get();
+    let Some(machine) = config
+        .companion
+        .machines
+        .iter()
+        .find(|machine| machine.name == name)
+        .cloned()
+    else {
+        return HttpError::not_found(format!("There is no media PC called {name:?}"));
+    };
+    let timeout = Duration::from_secs(config.companion.wake_timeout_secs);
+    let client = match implementation::probe_client() {
+        Ok(client) => client,
+        Err(err) => return HttpError::internal_server_error(format!("{err:#}")),
+    };
+
+    Ok(options.on_upgrade(move |mut socket| async move {
+        let started = Instant::now();
+        let mut ticks = tokio::time::interval(Duration::from_secs(2));
+
+        loop {
+            let status = if implementation::is_awake(&client, &machine.url).await {
+                WakeStatus::Awake(machine.url.clone())
+            } else if started.elapsed() > timeout {
+                WakeStatus::TimedOut
+            } else {
+                // Sent again and again, in case the media PC was still shutting down
+                let mac =
+                    parse_mac(&machine.mac).expect("MAC addresses are validated with the config");
+                match implementation::send_magic_packet(mac, machine.broadcast).await {
+                    Ok(()) => WakeStatus::Waking {
+                        secs: started.elapsed().as_secs(),
+                    },
+                    Err(err) => WakeStatus::Failed(format!("{err:#}")),
+                }
+            };
+            let done = !matches!(status, WakeStatus::Waking { .. });
+
+            if let Err(err) = socket.send(status).await {
+                warn!("Failed to send message: {}", err);
+                return;
+            }
+            if done {
+                return;
+            }
+            ticks.tick().await;
+        }
+    }))
+}
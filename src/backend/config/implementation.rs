@@ -0,0 +1,341 @@
+use std::{
+    io,
+    net::{Ipv4Addr, SocketAddr},
+    path::PathBuf,
+    sync::{Arc, LazyLock},
+};
+
+use anyhow::{Context as _, bail};
+use dioxus::prelude::*;
+use serde::Deserialize;
+use tokio::sync::watch;
+
+use crate::{backend::companion::parse_mac, frontend::remote::ScreencastSettings};
+
+/// Written to the settings page when there is no configuration file yet, matching [`Config::default`].
+pub const DEFAULT_CONFIG: &str = r#"# Changes take effect as soon as they are saved, except for the bind address,
+# which needs a restart.
+
+# Address to serve on. The IP and PORT environment variables take precedence.
+# The default only serves this machine, so phones can't connect and nothing is
+# advertised over mDNS. Use "0.0.0.0:8080" to serve the network.
+bind = "127.0.0.1:8080"
+
+# Directories Local Media may browse and play from. Everything may be browsed if empty.
+media_roots = []
+# Screen recordings are saved in this directory of the first media root, or of the
+# videos directory if there are no media roots
+recordings = "Recordings"
+
+[auth]
+# Let every connection from this machine in as the media PC, with admin rights and the PIN on
+# its pairing screen, without pairing. Only turn this on if nothing else on the machine makes
+# connections to EMPC, since a reverse proxy, an SSH tunnel or any local user would get in too.
+# Without it, the media PC pairs like any other device, and the PIN is shown as a notification
+# and on the pairing screen, whose URL with its token is printed at startup.
+trust_loopback = false
+
+# What the stream settings of the Remote page start as
+[screencast]
+# JPEG quality, from 1 to 100
+quality = 70
+# max_width = 1920
+# max_fps = 30
+adaptive = true
+
+[player]
+# Plays local media, with the path of the file appended
+command = ["xdg-open"]
+
+[power]
+# Seconds power actions count down, while they can still be cancelled, from 1 to 3600
+countdown_secs = 10
+# Milliseconds to wait after the countdown, so the browser can be redirected first
+delay_ms = 500
+# Where the browser is sent after powering off, like the /companion page of a companion
+# redirect = "http://raspberrypi.local:8080/companion"
+
+[companion]
+# Run as a companion on an always-on device like a Raspberry Pi, which only wakes the
+# media PCs below. Point power.redirect of those at its /companion page. Needs a restart.
+enabled = false
+# Seconds to wait for a media PC to answer after waking it
+wake_timeout_secs = 120
+
+# [[companion.machines]]
+# name = "Living room"
+# mac = "aa:bb:cc:dd:ee:ff"
+# url = "http://192.168.1.20:8080/"
+# broadcast = "255.255.255.255:9"
+
+[discovery]
+# Advertise EMPC over multicast DNS as an _http._tcp (or _https._tcp) service and as <hostname>.local,
+# so phones can find it. Check with `avahi-browse -rt _http._tcp` or
+# `avahi-resolve -n empc.local`. Needs a restart, and a bind address other than loopback,
+# so it is off with the default bind. Companions are never advertised.
+enabled = true
+# The name phones show for the service
+name = "EMPC"
+hostname = "empc"
+
+[tls]
+# Serve HTTPS, which browsers need for clipboard access, wake locks and service workers.
+# A self-signed certificate is generated on first run, unless cert and key are set, and
+# its fingerprint is shown on the pairing screen. Needs a restart. Paired devices keep
+# working, and their cookies are marked Secure the next time they load a page.
+enabled = false
+# cert = "/etc/empc/cert.pem"
+# key = "/etc/empc/key.pem"
+# Also serve plain HTTP on this port, redirecting to HTTPS
+# redirect_port = 8081
+"#;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub bind: SocketAddr,
+    pub media_roots: Vec<PathBuf>,
+    pub recordings: PathBuf,
+    pub auth: AuthConfig,
+    pub screencast: ScreencastSettings,
+    pub player: PlayerConfig,
+    pub power: PowerConfig,
+    pub companion: CompanionConfig,
+    pub discovery: DiscoveryConfig,
+    pub tls: TlsConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            bind: SocketAddr::from((Ipv4Addr::LOCALHOST, 8080)),
+            media_roots: Vec::new(),
+            recordings: PathBuf::from("Recordings"),
+            auth: AuthConfig::default(),
+            screencast: ScreencastSettings::default(),
+            player: PlayerConfig::default(),
+            power: PowerConfig::default(),
+            companion: CompanionConfig::default(),
+            discovery: DiscoveryConfig::default(),
+            tls: TlsConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    /// Treat loopback connections as the media PC
+    pub trust_loopback: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PlayerConfig {
+    pub command: Vec<String>,
+}
+
+impl Default for PlayerConfig {
+    fn default() -> Self {
+        Self {
+            command: vec!["xdg-open".to_owned()],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PowerConfig {
+    pub countdown_secs: u32,
+    pub delay_ms: u64,
+    pub redirect: Option<String>,
+}
+
+impl Default for PowerConfig {
+    fn default() -> Self {
+        Self {
+            countdown_secs: 10,
+            delay_ms: 500,
+            redirect: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CompanionConfig {
+    pub enabled: bool,
+    pub wake_timeout_secs: u64,
+    pub machines: Vec<Machine>,
+}
+
+impl Default for CompanionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            wake_timeout_secs: 120,
+            machines: Vec::new(),
+        }
+    }
+}
+
+/// A media PC the companion can wake.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Machine {
+    pub name: String,
+    pub mac: String,
+    /// Where EMPC answers once the machine is awake
+    pub url: String,
+    #[serde(default = "Machine::default_broadcast")]
+    pub broadcast: SocketAddr,
+}
+
+impl Machine {
+    fn default_broadcast() -> SocketAddr {
+        SocketAddr::from((Ipv4Addr::BROADCAST, 9))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DiscoveryConfig {
+    pub enabled: bool,
+    pub name: String,
+    /// Advertised as `<hostname>.local`
+    pub hostname: String,
+}
+
+impl Default for DiscoveryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            name: "EMPC".to_owned(),
+            hostname: "empc".to_owned(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TlsConfig {
+    pub enabled: bool,
+    /// PEM certificate chain, instead of the generated self-signed certificate
+    pub cert: Option<PathBuf>,
+    /// PEM private key of `cert`
+    pub key: Option<PathBuf>,
+    pub redirect_port: Option<u16>,
+}
+
+impl Config {
+    fn validate(&self) -> anyhow::Result<()> {
+        for root in &self.media_roots {
+            if !root.is_absolute() {
+                bail!("Media root {root:?} is not an absolute path");
+            }
+            // Not fatal, since it could be a drive that isn't plugged in
+            if !root.is_dir() {
+                warn!("Media root {:?} is not a directory", root);
+            }
+        }
+        if self.recordings.is_absolute() {
+            bail!(
+                "recordings {:?} has to be relative to the media root",
+                self.recordings
+            );
+        }
+        if let Err(err) = self.screencast.check() {
+            bail!("screencast.{err}");
+        }
+        if !(1..=3600).contains(&self.power.countdown_secs) {
+            bail!(
+                "power.countdown_secs must be from 1 to 3600, not {}",
+                self.power.countdown_secs
+            );
+        }
+        if self.player.command.is_empty() {
+            bail!("player.command needs at least the program to run");
+        }
+        for machine in &self.companion.machines {
+            if parse_mac(&machine.mac).is_none() {
+                bail!(
+                    "{:?} is not a MAC address, like aa:bb:cc:dd:ee:ff",
+                    machine.mac
+                );
+            }
+        }
+        let hostname = &self.discovery.hostname;
+        if hostname.is_empty()
+            || !hostname
+                .chars()
+                .all(|char| char.is_ascii_alphanumeric() || char == '-')
+        {
+            bail!("discovery.hostname {hostname:?} may only have letters, digits and dashes");
+        }
+        if self.tls.cert.is_some() != self.tls.key.is_some() {
+            bail!("tls.cert and tls.key have to be set together");
+        }
+        Ok(())
+    }
+}
+
+static CONFIG: LazyLock<watch::Sender<Arc<Config>>> =
+    LazyLock::new(|| watch::channel(Arc::default()).0);
+
+/// The current configuration.
+pub fn get() -> Arc<Config> {
+    CONFIG.borrow().clone()
+}
+
+pub fn set(config: Config) {
+    println!("Using the new configuration");
+    CONFIG.send_replace(Arc::new(config));
+}
+
+/// Read the configuration at startup, failing if it is invalid.
+pub fn load() -> anyhow::Result<()> {
+    let path = config_path()?;
+    let config = match std::fs::read_to_string(&path) {
+        Ok(text) => parse(&text).with_context(|| format!("Invalid configuration {path:?}"))?,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Config::default(),
+        Err(err) => {
+            return Err(err).with_context(|| format!("Failed to read configuration {path:?}"));
+        }
+    };
+    CONFIG.send_replace(Arc::new(config));
+    Ok(())
+}
+
+pub fn parse(text: &str) -> anyhow::Result<Config> {
+    let config: Config = toml::from_str(text)?;
+    config.validate()?;
+    Ok(config)
+}
+
+fn config_path() -> anyhow::Result<PathBuf> {
+    Ok(dirs::config_dir()
+        .context("Failed to find the config directory")?
+        .join("empc")
+        .join("config.toml"))
+}
+
+pub async fn read_file() -> anyhow::Result<Option<String>> {
+    let path = config_path()?;
+    match tokio::fs::read_to_string(&path).await {
+        Ok(text) => Ok(Some(text)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err).with_context(|| format!("Failed to read configuration {path:?}")),
+    }
+}
+
+pub async fn write_file(text: &str) -> anyhow::Result<()> {
+    let path = config_path()?;
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir)
+            .await
+            .with_context(|| format!("Failed to create config directory {dir:?}"))?;
+    }
+    tokio::fs::write(&path, text)
+        .await
+        .with_context(|| format!("Failed to write configuration {path:?}"))
+}
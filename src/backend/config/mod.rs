@@ -0,0 +1,67 @@
+//! The configuration file, `empc/config.toml` in the XDG config directory.
+//!
+//! It is read and validated once at startup, and again whenever it is saved from the
+//! settings page. Everything except the bind address is read when it is used, so
+//! changes take effect right away.
+
+#[cfg(feature = "server")]
+mod implementation;
+
+use dioxus::prelude::*;
+
+use crate::frontend::remote::ScreencastSettings;
+#[cfg(feature = "server")]
+use crate::{backend::auth::require_role, frontend::devices::Role};
+
+#[cfg(feature = "server")]
+pub use implementation::{Config, get, load};
+
+/// The configuration file as written, so comments survive editing it on the settings page.
+///
+/// If there is no configuration file yet, a commented one with the defaults is returned.
+#[get("/api/config")]
+pub async fn config_file() -> Result<String, HttpError> {
+    require_role(Role::Admin)?;
+
+    implementation::read_file()
+        .await
+        .map(|text| text.unwrap_or_else(|| implementation::DEFAULT_CONFIG.to_owned()))
+        .map_err(|err| HttpError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("{err:#}")))
+}
+
+/// Validate and save the configuration file, and use it from now on.
+#[post("/api/config/save")]
+pub async fn save_config(text: String) -> Result<(), HttpError> {
+    require_role(Role::Admin)?;
+
+    let config = implementation::parse(&text)
+        .map_err(|err| HttpError::new(StatusCode::BAD_REQUEST, format!("{err:#}")))?;
+    implementation::write_file(&text)
+        .await
+        .map_err(|err| HttpError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("{err:#}")))?;
+    implementation::set(config);
+
+    Ok(())
+}
+
+/// Read the configuration file again, after it was edited by hand.
+#[post("/api/config/reload")]
+pub async fn reload_config() -> Result<(), HttpError> {
+    require_role(Role::Admin)?;
+
+    let config = implementation::read_file()
+        .await
+        .and_then(|text| text.as_deref().map(implementation::parse).transpose())
+        .map_err(|err| HttpError::new(StatusCode::BAD_REQUEST, format!("{err:#}")))?;
+    implementation::set(config.unwrap_or_default());
+
+    Ok(())
+}
+
+/// The stream settings the Remote page starts with.
+#[get("/api/config/screencast")]
+pub async fn screencast_defaults() -> Result<ScreencastSettings, HttpError> {
+    require_role(Role::Household)?;
+
+    Ok(get().screencast)
+}
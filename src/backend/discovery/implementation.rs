@@ -0,0 +1,205 @@
+use std::{
+    env,
+    net::{IpAddr, Ipv4Addr},
+    sync::OnceLock,
+};
+
+use anyhow::{Context as _, bail};
+use dioxus::prelude::*;
+use mdns_sd::{ServiceDaemon, ServiceInfo};
+use qrcode::{
+    QrCode,
+    render::{svg, unicode::Dense1x2},
+};
+
+use crate::backend::config::{self, Config};
+
+/// Kept for as long as EMPC runs, since the service is unregistered when it shuts down.
+static DAEMON: OnceLock<ServiceDaemon> = OnceLock::new();
+
+/// Advertise EMPC over multicast DNS, and print its URL with a QR code for phones.
+pub fn start() {
+    let config = config::get();
+    let ips = match lan_ips() {
+        Ok(ips) => ips,
+        Err(err) => {
+            // Printed instead of logged, since this is why phones can't find or reach EMPC
+            println!("Phones can't reach EMPC: {err:#}");
+            return;
+        }
+    };
+
+    if let Some(url) = url() {
+        println!("Open {url} on your phone, or scan:");
+        match QrCode::new(&url) {
+            // Inverted, since terminals are usually light text on a dark background
+            Ok(code) => println!(
+                "{}",
+                code.render::<Dense1x2>()
+                    .dark_color(Dense1x2::Light)
+                    .light_color(Dense1x2::Dark)
+                    .build()
+            ),
+            Err(err) => warn!("Failed to make a QR code: {}", err),
+        }
+    }
+
+    if !config.discovery.enabled {
+        return;
+    }
+    // A companion only wakes media PCs, so phones looking for one shouldn't find it
+    if config.companion.enabled {
+        println!("Not advertising EMPC over mDNS, since it runs as a companion");
+        return;
+    }
+    match advertise(&ips) {
+        Ok(daemon) => {
+            let _ = DAEMON.set(daemon);
+            println!(
+                "Advertising {:?} as {}.local",
+                config.discovery.name, config.discovery.hostname
+            );
+        }
+        Err(err) => warn!("Failed to advertise EMPC over mDNS: {:#}", err),
+    }
+}
+
+fn advertise(ips: &[IpAddr]) -> anyhow::Result<ServiceDaemon> {
+    let daemon = ServiceDaemon::new().context("Failed to start the mDNS daemon")?;
+    daemon.register(service_info(&config::get(), ips, port()?)?)?;
+    Ok(daemon)
+}
+
+/// The service EMPC is advertised as.
+fn service_info(config: &Config, ips: &[IpAddr], port: u16) -> anyhow::Result<ServiceInfo> {
+    let service_type = if config.tls.enabled {
+        "_https._tcp.local."
+    } else {
+        "_http._tcp.local."
+    };
+    Ok(ServiceInfo::new(
+        service_type,
+        &config.discovery.name,
+        &format!("{}.local.", config.discovery.hostname),
+        ips,
+        port,
+        &[("path", "/")][..],
+    )?)
+}
+
+/// The URL phones can open, with an IP address since not every phone resolves `.local`.
+fn url() -> Option<String> {
+    let ip = *lan_ips().ok()?.first()?;
+    let port = port().ok()?;
+    let scheme = if config::get().tls.enabled {
+        "https"
+    } else {
+        "http"
+    };
+    Some(match ip {
+        IpAddr::V4(ip) => format!("{scheme}://{ip}:{port}/"),
+        IpAddr::V6(ip) => format!("{scheme}://[{ip}]:{port}/"),
+    })
+}
+
+/// The URL as a QR code, to show on the TV.
+pub fn qr_svg() -> anyhow::Result<String> {
+    let url = url().context("EMPC is not reachable from other devices")?;
+    let code = QrCode::new(&url)?;
+    Ok(code.render::<svg::Color>().min_dimensions(240, 240).build())
+}
+
+/// The addresses EMPC is served at that other devices can reach, IPv4 first.
+pub fn lan_ips() -> anyhow::Result<Vec<IpAddr>> {
+    // Set from the configured bind address at startup, unless it was overridden
+    let ip: IpAddr = env::var("IP")
+        .ok()
+        .and_then(|ip| ip.parse().ok())
+        .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+    if ip.is_loopback() {
+        bail!("EMPC is only served on {ip}, set bind to 0.0.0.0:<port> to serve phones");
+    }
+    if !ip.is_unspecified() {
+        return Ok(vec![ip]);
+    }
+
+    let mut ips: Vec<_> = if_addrs::get_if_addrs()
+        .context("Failed to list network interfaces")?
+        .into_iter()
+        .filter(|interface| !interface.is_loopback() && !interface.is_link_local())
+        .map(|interface| interface.ip())
+        .filter(|other| ip.is_ipv6() || other.is_ipv4())
+        .collect();
+    ips.sort_by_key(IpAddr::is_ipv6);
+    if ips.is_empty() {
+        bail!("There are no network interfaces other devices can reach");
+    }
+    Ok(ips)
+}
+
+fn port() -> anyhow::Result<u16> {
+    env::var("PORT")
+        .context("PORT is not set")?
+        .parse()
+        .context("PORT is not a port")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use mdns_sd::ServiceEvent;
+
+    use super::*;
+
+    /// Advertise a service and find it again with a resolver, like a phone would.
+    #[test]
+    fn resolver_finds_the_service() {
+        let ips: Vec<_> = if_addrs::get_if_addrs()
+            .unwrap()
+            .into_iter()
+            .filter(|interface| !interface.is_loopback() && interface.ip().is_ipv4())
+            .map(|interface| interface.ip())
+            .collect();
+        if ips.is_empty() {
+            eprintln!("Skipping the mDNS test, as there is no network interface");
+            return;
+        }
+
+        // Unique, so other instances on the network don't get in the way
+        let mut config = Config::default();
+        config.discovery.name = format!("EMPC test {}", std::process::id());
+        config.discovery.hostname = format!("empc-test-{}", std::process::id());
+        let responder = ServiceDaemon::new().unwrap();
+        responder
+            .register(service_info(&config, &ips, 18080).unwrap())
+            .unwrap();
+
+        let resolver = ServiceDaemon::new().unwrap();
+        let events = resolver.browse("_http._tcp.local.").unwrap();
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let service = loop {
+            let left = deadline.saturating_duration_since(Instant::now());
+            match events.recv_timeout(left) {
+                Ok(ServiceEvent::ServiceResolved(service))
+                    if service.get_fullname().starts_with(&config.discovery.name) =>
+                {
+                    break service;
+                }
+                Ok(_) => {}
+                Err(err) => panic!("The service was not resolved: {err}"),
+            }
+        };
+
+        assert_eq!(
+            service.get_hostname(),
+            format!("{}.local.", config.discovery.hostname)
+        );
+        assert_eq!(service.get_port(), 18080);
+        assert_eq!(service.get_property_val_str("path"), Some("/"));
+        assert!(ips.iter().any(|ip| service.get_addresses().contains(ip)));
+
+        let _ = resolver.shutdown();
+        let _ = responder.shutdown();
+    }
+}
@@ -0,0 +1,18 @@
+//! Lets phones find the media PC, by advertising it over multicast DNS and showing its
+//! URL as a QR code.
+
+#[cfg(feature = "server")]
+mod implementation;
+#[cfg(feature = "server")]
+pub use implementation::{lan_ips, start};
+
+use dioxus::prelude::*;
+
+/// The URL phones can open, as an SVG QR code.
+///
+/// Anybody may get it, since the pairing screen shows it before anything is paired.
+#[get("/api/discovery/qr")]
+pub async fn url_qr_code() -> Result<String, HttpError> {
+    implementation::qr_svg()
+        .map_err(|err| HttpError::new(StatusCode::NOT_FOUND, format!("{err:#}")))
+}
@@ -1,23 +1,36 @@
-use std::io::ErrorKind;
+use std::{
+    io::ErrorKind,
+    path::{Component, Path, PathBuf},
+};
 
 use dioxus::prelude::*;
-use tokio::fs;
+use tokio::{fs, process::Command};
 
-use super::DirEntry;
+use super::{DirEntry, FileType};
+use crate::backend::config;
 
 pub async fn list_files_impl(directory: String) -> Result<Vec<DirEntry>, HttpError> {
-    let mut iterator = match fs::read_dir(&directory).await {
-        Ok(iterator) => iterator,
-        Err(err) => {
-            let status = match err.kind() {
-                ErrorKind::NotFound => StatusCode::NOT_FOUND,
-                ErrorKind::PermissionDenied => StatusCode::FORBIDDEN,
-                ErrorKind::NotADirectory => StatusCode::BAD_REQUEST,
-                _ => StatusCode::INTERNAL_SERVER_ERROR,
-            };
-            return Err(HttpError::new(status, err.to_string()));
-        }
-    };
+    // With media roots, the top level lists just them, however it is spelled
+    let roots = config::get().media_roots.clone();
+    let is_top_level = Path::new(&directory).components().all(|component| {
+        matches!(
+            component,
+            Component::RootDir | Component::CurDir | Component::ParentDir
+        )
+    });
+    if is_top_level && !roots.is_empty() {
+        return Ok(roots
+            .into_iter()
+            .map(|root| DirEntry {
+                file_name: root.display().to_string(),
+                path: root,
+                file_type: FileType::Directory,
+            })
+            .collect());
+    }
+
+    let directory = check_path(&directory).await?;
+    let mut iterator = fs::read_dir(&directory).await.map_err(io_error)?;
 
     let mut entries = Vec::new();
     loop {
@@ -47,3 +60,67 @@ pub async fn list_files_impl(directory: String) -> Result<Vec<DirEntry>, HttpErr
 
     Ok(entries)
 }
+
+/// Play a file with the configured player command.
+pub async fn play_file_impl(path: PathBuf) -> Result<(), HttpError> {
+    let path = check_path(&path.to_string_lossy()).await?;
+    let command = config::get().player.command.clone();
+    let Some((program, args)) = command.split_first() else {
+        return Err(HttpError::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "No player command is configured",
+        ));
+    };
+
+    let mut handle = Command::new(program)
+        .args(args)
+        .arg(&path)
+        .spawn()
+        .map_err(|err| {
+            HttpError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to run {program:?}: {err}"),
+            )
+        })?;
+    println!("Playing {path:?}");
+
+    // Wait in the background, so the player doesn't become a zombie once it exits
+    tokio::spawn(async move {
+        if let Err(err) = handle.wait().await {
+            error!("Failed to wait for the player: {}", err);
+        }
+    });
+
+    Ok(())
+}
+
+/// Resolve a path, making sure it is inside one of the media roots if any are configured.
+async fn check_path(path: &str) -> Result<PathBuf, HttpError> {
+    let path = fs::canonicalize(path).await.map_err(io_error)?;
+
+    let roots = config::get().media_roots.clone();
+    if roots.is_empty() {
+        return Ok(path);
+    }
+    for root in roots {
+        if let Ok(root) = fs::canonicalize(&root).await
+            && path.starts_with(&root)
+        {
+            return Ok(path);
+        }
+    }
+    Err(HttpError::new(
+        StatusCode::FORBIDDEN,
+        format!("{path:?} is not in a media root"),
+    ))
+}
+
+fn io_error(err: std::io::Error) -> HttpError {
+    let status = match err.kind() {
+        ErrorKind::NotFound => StatusCode::NOT_FOUND,
+        ErrorKind::PermissionDenied => StatusCode::FORBIDDEN,
+        ErrorKind::NotADirectory => StatusCode::BAD_REQUEST,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    HttpError::new(status, err.to_string())
+}
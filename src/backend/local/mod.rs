@@ -6,6 +6,9 @@ use std::path::PathBuf;
 use dioxus::prelude::*;
 use serde::{Deserialize, Serialize};
 
+#[cfg(feature = "server")]
+use crate::{backend::auth::require_role, frontend::devices::Role};
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum FileType {
     File,
@@ -41,5 +44,13 @@ pub struct DirEntry {
 
 #[get("/api/local/files?directory")]
 pub async fn list_files(directory: String) -> Result<Vec<DirEntry>, HttpError> {
+    require_role(Role::Household)?;
     implementation::list_files_impl(directory).await
 }
+
+/// Play a local file on the media PC.
+#[post("/api/local/play")]
+pub async fn play_file(path: PathBuf) -> Result<(), HttpError> {
+    require_role(Role::Household)?;
+    implementation::play_file_impl(path).await
+}
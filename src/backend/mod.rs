@@ -1,4 +1,13 @@
+pub mod audio;
+pub mod auth;
+pub mod companion;
+pub mod config;
+pub mod discovery;
 pub mod local;
 pub mod playback;
 pub mod remote;
+pub mod schedule;
 pub mod shutdown;
+#[cfg(all(test, target_os = "linux", feature = "server"))]
+pub mod test_bus;
+pub mod tls;
@@ -0,0 +1,21 @@
+use std::{path::PathBuf, sync::LazyLock};
+
+use tokio::sync::watch;
+
+use crate::frontend::playback::{MediaPlayer, PlayerCommand};
+
+/// There is no MPRIS outside of linux, so there are never any players.
+static PLAYERS: LazyLock<watch::Sender<Vec<MediaPlayer>>> =
+    LazyLock::new(|| watch::channel(Vec::new()).0);
+
+pub fn players() -> watch::Receiver<Vec<MediaPlayer>> {
+    PLAYERS.subscribe()
+}
+
+pub async fn control(command: PlayerCommand) -> anyhow::Result<()> {
+    anyhow::bail!("There is no media player called {}", command.player)
+}
+
+pub fn artwork_path(_player: &str) -> Option<PathBuf> {
+    None
+}
@@ -1 +1,158 @@
+//! Controls the media players running on the media PC.
 
+use dioxus::fullstack::{PostcardEncoding, WebSocketOptions, Websocket};
+use dioxus::prelude::*;
+use dioxus_fullstack::response::Response;
+
+#[cfg(all(target_os = "linux", feature = "server"))]
+mod mpris;
+#[cfg(all(target_os = "linux", feature = "server"))]
+use mpris as implementation;
+
+#[cfg(all(not(target_os = "linux"), feature = "server"))]
+mod dummy;
+#[cfg(all(not(target_os = "linux"), feature = "server"))]
+use dummy as implementation;
+
+use crate::frontend::playback::{MediaPlayer, PlayerCommand};
+#[cfg(feature = "server")]
+use crate::frontend::playback::{PlaybackStatus, PlayerAction};
+#[cfg(feature = "server")]
+use crate::{backend::auth::require_role, frontend::devices::Role};
+
+/// Follow the media players, for things happening on the server like the sleep timer.
+#[cfg(feature = "server")]
+pub fn watch_players() -> tokio::sync::watch::Receiver<Vec<MediaPlayer>> {
+    implementation::players()
+}
+
+/// Pause every player that is playing.
+#[cfg(feature = "server")]
+pub async fn pause_all() -> anyhow::Result<()> {
+    let playing: Vec<_> = implementation::players()
+        .borrow()
+        .iter()
+        .filter(|player| player.status == PlaybackStatus::Playing)
+        .map(|player| player.id.clone())
+        .collect();
+
+    for player in playing {
+        implementation::control(PlayerCommand {
+            player,
+            action: PlayerAction::Pause,
+        })
+        .await?;
+    }
+    Ok(())
+}
+
+/// Follow the MPRIS media players on the media PC, and control them.
+///
+/// The current list of players is sent right away, and again whenever it changes.
+#[get("/api/playback/players")]
+pub async fn players(
+    options: WebSocketOptions,
+) -> Result<Websocket<PlayerCommand, Vec<MediaPlayer>, PostcardEncoding>, HttpError> {
+    require_role(Role::Guest)?;
+
+    Ok(options.on_upgrade(|mut socket| async move {
+        let mut players = implementation::players();
+        players.mark_changed();
+
+        loop {
+            tokio::select! {
+                command = socket.recv() => {
+                    let command = match command {
+                        Ok(command) => command,
+                        Err(err) => {
+                            error!("socket.recv() returned an error: {err}");
+                            return;
+                        }
+                    };
+
+                    if let Err(err) = implementation::control(command).await {
+                        warn!("Failed to control media player: {:#}", err);
+                    }
+                }
+                Ok(()) = players.changed() => {
+                    let list = players.borrow_and_update().clone();
+                    if let Err(err) = socket.send(list).await {
+                        warn!("Failed to send message: {}", err);
+                    }
+                }
+            }
+        }
+    }))
+}
+
+/// The extensions artwork may have, so players can't make us serve other files.
+#[cfg(feature = "server")]
+const ARTWORK_EXTENSIONS: [&str; 6] = ["png", "jpg", "jpeg", "gif", "webp", "bmp"];
+
+/// Get the type of an image from its first bytes, if it is one browsers can show safely.
+#[cfg(feature = "server")]
+fn image_content_type(image: &[u8]) -> Option<&'static str> {
+    match image {
+        [0x89, b'P', b'N', b'G', ..] => Some("image/png"),
+        [0xFF, 0xD8, 0xFF, ..] => Some("image/jpeg"),
+        [b'G', b'I', b'F', b'8', ..] => Some("image/gif"),
+        [
+            b'R',
+            b'I',
+            b'F',
+            b'F',
+            _,
+            _,
+            _,
+            _,
+            b'W',
+            b'E',
+            b'B',
+            b'P',
+            ..,
+        ] => Some("image/webp"),
+        [b'B', b'M', ..] => Some("image/bmp"),
+        _ => None,
+    }
+}
+
+/// Get the artwork of the current track of a player, if the player keeps it in a local file.
+///
+/// Only image files are served, since the path comes from whatever the player claims.
+#[get("/api/playback/art?player")]
+pub async fn artwork(player: String) -> Result<Response, HttpError> {
+    use axum::{
+        body::Body,
+        http::header::{CACHE_CONTROL, CONTENT_TYPE, X_CONTENT_TYPE_OPTIONS},
+    };
+
+    require_role(Role::Guest)?;
+
+    let Some(path) = implementation::artwork_path(&player) else {
+        return Err(HttpError::new(
+            StatusCode::NOT_FOUND,
+            "The player has no local artwork",
+        ));
+    };
+    let is_image = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| ARTWORK_EXTENSIONS.contains(&extension.to_lowercase().as_str()));
+    if !is_image {
+        return HttpError::forbidden("The artwork is not an image file");
+    }
+
+    let image = tokio::fs::read(&path)
+        .await
+        .map_err(|err| HttpError::new(StatusCode::NOT_FOUND, err.to_string()))?;
+    let Some(content_type) = image_content_type(&image) else {
+        return HttpError::forbidden("The artwork is not an image file");
+    };
+
+    Response::builder()
+        .header(CONTENT_TYPE, content_type)
+        .header(X_CONTENT_TYPE_OPTIONS, "nosniff")
+        .header(CACHE_CONTROL, "no-store")
+        .body(Body::from(image))
+        .map_err(|err| HttpError::new(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))
+}
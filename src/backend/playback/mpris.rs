@@ -0,0 +1,528 @@
+//! Follows the MPRIS media players on the D-Bus session bus, like browsers and Spotify.
+//!
+//! Players announce changes with `PropertiesChanged`, except for their position,
+//! which is read again every second while somebody is watching.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+    sync::LazyLock,
+    time::Duration,
+};
+
+use anyhow::Context as _;
+use dioxus::prelude::*;
+use futures::{
+    StreamExt,
+    stream::{BoxStream, SelectAll},
+};
+use tokio::{
+    sync::{OnceCell, watch},
+    time::{interval, sleep},
+};
+use zbus::{
+    Connection,
+    fdo::{DBusProxy, PropertiesProxy},
+    proxy::CacheProperties,
+    zvariant::OwnedValue,
+};
+
+use crate::frontend::playback::{MediaPlayer, PlaybackStatus, PlayerAction, PlayerCommand};
+
+/// Every MPRIS player owns a bus name starting with this.
+const BUS_NAME_PREFIX: &str = "org.mpris.MediaPlayer2.";
+const OBJECT_PATH: &str = "/org/mpris/MediaPlayer2";
+
+const POSITION_INTERVAL: Duration = Duration::from_secs(1);
+const RETRY_DELAY: Duration = Duration::from_secs(10);
+
+#[zbus::proxy(
+    interface = "org.mpris.MediaPlayer2",
+    default_path = "/org/mpris/MediaPlayer2"
+)]
+trait Root {
+    #[zbus(property)]
+    fn identity(&self) -> zbus::Result<String>;
+}
+
+#[zbus::proxy(
+    interface = "org.mpris.MediaPlayer2.Player",
+    default_path = "/org/mpris/MediaPlayer2"
+)]
+trait Player {
+    fn play_pause(&self) -> zbus::Result<()>;
+    fn pause(&self) -> zbus::Result<()>;
+    fn next(&self) -> zbus::Result<()>;
+    fn previous(&self) -> zbus::Result<()>;
+    fn seek(&self, offset: i64) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn playback_status(&self) -> zbus::Result<String>;
+    #[zbus(property)]
+    fn metadata(&self) -> zbus::Result<HashMap<String, OwnedValue>>;
+    #[zbus(property)]
+    fn position(&self) -> zbus::Result<i64>;
+    #[zbus(property)]
+    fn volume(&self) -> zbus::Result<f64>;
+    #[zbus(property)]
+    fn set_volume(&self, volume: f64) -> zbus::Result<()>;
+}
+
+static CONNECTION: OnceCell<Connection> = OnceCell::const_new();
+
+/// The players, which are followed from the first time somebody asks for them.
+static PLAYERS: LazyLock<watch::Sender<Vec<MediaPlayer>>> = LazyLock::new(|| {
+    let (tx, _) = watch::channel(Vec::new());
+    tokio::spawn(follow_players(tx.clone()));
+    tx
+});
+
+async fn connection() -> anyhow::Result<&'static Connection> {
+    CONNECTION
+        .get_or_try_init(Connection::session)
+        .await
+        .context("Failed to connect to the session bus")
+}
+
+pub fn players() -> watch::Receiver<Vec<MediaPlayer>> {
+    PLAYERS.subscribe()
+}
+
+pub async fn control(command: PlayerCommand) -> anyhow::Result<()> {
+    control_on(connection().await?, command).await
+}
+
+async fn control_on(connection: &Connection, command: PlayerCommand) -> anyhow::Result<()> {
+    if !command.player.starts_with(BUS_NAME_PREFIX) {
+        anyhow::bail!("{} is not a media player", command.player);
+    }
+    let player = player_proxy(connection, &command.player).await?;
+
+    match command.action {
+        PlayerAction::PlayPause => player.play_pause().await?,
+        PlayerAction::Pause => player.pause().await?,
+        PlayerAction::Next => player.next().await?,
+        PlayerAction::Previous => player.previous().await?,
+        PlayerAction::Seek(offset) => player.seek(offset).await?,
+        PlayerAction::SetVolume(volume) => player.set_volume(volume.clamp(0.0, 1.0)).await?,
+    }
+    Ok(())
+}
+
+/// Get the path of the artwork of the current track of a player, if it is a local file.
+pub fn artwork_path(player: &str) -> Option<PathBuf> {
+    let players = PLAYERS.borrow();
+    let url = players
+        .iter()
+        .find(|other| other.id == player)?
+        .art_url
+        .as_deref()?;
+    url.strip_prefix("file://")
+        .map(|path| PathBuf::from(percent_decode(path)))
+}
+
+/// Decode the `%XX` escapes of a URL path.
+fn percent_decode(path: &str) -> String {
+    let mut bytes = Vec::with_capacity(path.len());
+    let mut rest = path.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        let escaped = match (byte, tail) {
+            (b'%', [high, low, ..]) => std::str::from_utf8(&[*high, *low])
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+            _ => None,
+        };
+        match escaped {
+            Some(decoded) => {
+                bytes.push(decoded);
+                rest = &tail[2..];
+            }
+            None => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+async fn player_proxy(connection: &Connection, name: &str) -> anyhow::Result<PlayerProxy<'static>> {
+    Ok(PlayerProxy::builder(connection)
+        .destination(name.to_owned())?
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await?)
+}
+
+/// Follow the players forever, starting over if the session bus goes away.
+async fn follow_players(tx: watch::Sender<Vec<MediaPlayer>>) {
+    loop {
+        let result = async { follow_players_once(connection().await?, &tx).await }.await;
+        if let Err(err) = result {
+            warn!("Failed to follow media players: {:#}", err);
+        }
+        tx.send_replace(Vec::new());
+        sleep(RETRY_DELAY).await;
+    }
+}
+
+async fn follow_players_once(
+    connection: &Connection,
+    tx: &watch::Sender<Vec<MediaPlayer>>,
+) -> anyhow::Result<()> {
+    let dbus = DBusProxy::new(connection).await?;
+    let mut owner_changes = dbus.receive_name_owner_changed().await?;
+
+    let mut players = BTreeMap::new();
+    for name in dbus.list_names().await? {
+        if name.starts_with(BUS_NAME_PREFIX) {
+            update_player(connection, &mut players, name.to_string()).await;
+        }
+    }
+    let mut changes = property_changes(connection, players.keys()).await?;
+    let mut position_ticks = interval(POSITION_INTERVAL);
+
+    loop {
+        tx.send_if_modified(|current| {
+            let new: Vec<_> = players.values().cloned().collect();
+            let modified = *current != new;
+            *current = new;
+            modified
+        });
+
+        tokio::select! {
+            signal = owner_changes.next() => {
+                let Some(signal) = signal else {
+                    anyhow::bail!("The session bus stopped sending signals");
+                };
+                let args = signal.args()?;
+                let name = args.name().to_string();
+                if !name.starts_with(BUS_NAME_PREFIX) {
+                    continue;
+                }
+
+                if args.new_owner().is_some() {
+                    update_player(connection, &mut players, name).await;
+                } else {
+                    players.remove(&name);
+                }
+                changes = property_changes(connection, players.keys()).await?;
+            }
+            Some(name) = changes.next() => {
+                update_player(connection, &mut players, name).await;
+            }
+            _ = position_ticks.tick() => {
+                // Nobody is watching, so the position does not matter
+                if tx.is_closed() {
+                    continue;
+                }
+                for (name, player) in &mut players {
+                    if let Ok(proxy) = player_proxy(connection, name).await {
+                        player.position_us = proxy.position().await.ok();
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Get notified with the name of a player whenever one of its properties changes.
+async fn property_changes(
+    connection: &Connection,
+    names: impl Iterator<Item = &String>,
+) -> anyhow::Result<SelectAll<BoxStream<'static, String>>> {
+    let mut changes = SelectAll::new();
+    for name in names {
+        let properties = PropertiesProxy::builder(connection)
+            .destination(name.clone())?
+            .path(OBJECT_PATH)?
+            .build()
+            .await?;
+        let name = name.clone();
+        let stream = properties.receive_properties_changed().await?;
+        changes.push(stream.map(move |_| name.clone()).boxed());
+    }
+    Ok(changes)
+}
+
+/// Read the state of a player again, forgetting it if that fails.
+async fn update_player(
+    connection: &Connection,
+    players: &mut BTreeMap<String, MediaPlayer>,
+    name: String,
+) {
+    match read_player(connection, &name).await {
+        Ok(player) => {
+            players.insert(name, player);
+        }
+        Err(err) => {
+            warn!("Failed to read media player {}: {:#}", name, err);
+            players.remove(&name);
+        }
+    }
+}
+
+async fn read_player(connection: &Connection, name: &str) -> anyhow::Result<MediaPlayer> {
+    let root = RootProxy::builder(connection)
+        .destination(name)?
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await?;
+    let player = player_proxy(connection, name).await?;
+    let metadata = player.metadata().await.unwrap_or_default();
+
+    let status = match player.playback_status().await?.as_str() {
+        "Playing" => PlaybackStatus::Playing,
+        "Paused" => PlaybackStatus::Paused,
+        _ => PlaybackStatus::Stopped,
+    };
+    let name_without_prefix = name.trim_start_matches(BUS_NAME_PREFIX).to_owned();
+
+    Ok(MediaPlayer {
+        id: name.to_owned(),
+        name: root.identity().await.unwrap_or(name_without_prefix),
+        status,
+        title: metadata_string(&metadata, "xesam:title"),
+        artists: metadata
+            .get("xesam:artist")
+            .and_then(|value| value.try_clone().ok())
+            .and_then(|value| Vec::<String>::try_from(value).ok())
+            .unwrap_or_default(),
+        album: metadata_string(&metadata, "xesam:album"),
+        art_url: metadata_string(&metadata, "mpris:artUrl"),
+        // The length should be signed, but some players send it unsigned
+        length_us: metadata.get("mpris:length").and_then(|value| {
+            i64::try_from(value)
+                .ok()
+                .or_else(|| u64::try_from(value).ok().map(|length| length as i64))
+        }),
+        position_us: player.position().await.ok(),
+        volume: player.volume().await.ok(),
+    })
+}
+
+fn metadata_string(metadata: &HashMap<String, OwnedValue>, key: &str) -> Option<String> {
+    let value = <&str>::try_from(metadata.get(key)?).ok()?;
+    Some(value.to_owned()).filter(|value| !value.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use tokio::time::timeout;
+    use zbus::{
+        object_server::SignalEmitter,
+        zvariant::{OwnedValue, Value},
+    };
+
+    use super::*;
+    use crate::backend::test_bus::TestBus;
+
+    const NAME: &str = "org.mpris.MediaPlayer2.Test";
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    struct TestRoot;
+
+    #[zbus::interface(name = "org.mpris.MediaPlayer2")]
+    impl TestRoot {
+        #[zbus(property)]
+        fn identity(&self) -> String {
+            "Test Player".to_owned()
+        }
+    }
+
+    /// A player that records what it was told to do.
+    struct TestPlayer {
+        playing: bool,
+        volume: f64,
+        calls: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl TestPlayer {
+        fn record(&self, call: String) {
+            self.calls.lock().unwrap().push(call);
+        }
+    }
+
+    fn owned(value: Value<'_>) -> OwnedValue {
+        OwnedValue::try_from(value).unwrap()
+    }
+
+    #[zbus::interface(name = "org.mpris.MediaPlayer2.Player")]
+    impl TestPlayer {
+        async fn play_pause(
+            &mut self,
+            #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        ) -> zbus::fdo::Result<()> {
+            self.record("PlayPause".to_owned());
+            self.playing = !self.playing;
+            self.playback_status_changed(&emitter).await?;
+            Ok(())
+        }
+
+        fn pause(&mut self) {
+            self.record("Pause".to_owned());
+        }
+
+        fn next(&mut self) {
+            self.record("Next".to_owned());
+        }
+
+        fn previous(&mut self) {
+            self.record("Previous".to_owned());
+        }
+
+        fn seek(&mut self, offset: i64) {
+            self.record(format!("Seek({offset})"));
+        }
+
+        #[zbus(property)]
+        fn playback_status(&self) -> String {
+            if self.playing { "Playing" } else { "Paused" }.to_owned()
+        }
+
+        #[zbus(property)]
+        fn metadata(&self) -> HashMap<String, OwnedValue> {
+            HashMap::from([
+                ("xesam:title".to_owned(), owned(Value::from("Song"))),
+                (
+                    "xesam:artist".to_owned(),
+                    owned(Value::from(vec!["Artist"])),
+                ),
+                ("xesam:album".to_owned(), owned(Value::from(""))),
+                (
+                    "mpris:length".to_owned(),
+                    owned(Value::from(180_000_000_i64)),
+                ),
+            ])
+        }
+
+        #[zbus(property)]
+        fn position(&self) -> i64 {
+            42_000_000
+        }
+
+        #[zbus(property)]
+        fn volume(&self) -> f64 {
+            self.volume
+        }
+
+        #[zbus(property)]
+        fn set_volume(&mut self, volume: f64) {
+            self.record(format!("SetVolume({volume})"));
+            self.volume = volume;
+        }
+    }
+
+    async fn wait_for(
+        players: &mut watch::Receiver<Vec<MediaPlayer>>,
+        condition: impl FnMut(&Vec<MediaPlayer>) -> bool,
+    ) -> Vec<MediaPlayer> {
+        timeout(TIMEOUT, players.wait_for(condition))
+            .await
+            .expect("The players didn't change in time")
+            .unwrap()
+            .clone()
+    }
+
+    #[tokio::test]
+    async fn follows_and_controls_players() {
+        let Some(bus) = TestBus::start() else {
+            return;
+        };
+        let connection = bus.connect().await;
+        let (tx, mut players) = watch::channel(Vec::new());
+        let follower = tokio::spawn({
+            let connection = connection.clone();
+            async move { follow_players_once(&connection, &tx).await }
+        });
+
+        // Players that start later are found too
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let player = bus
+            .builder()
+            .name(NAME)
+            .unwrap()
+            .serve_at(OBJECT_PATH, TestRoot)
+            .unwrap()
+            .serve_at(
+                OBJECT_PATH,
+                TestPlayer {
+                    playing: false,
+                    volume: 0.5,
+                    calls: calls.clone(),
+                },
+            )
+            .unwrap()
+            .build()
+            .await
+            .unwrap();
+
+        let list = wait_for(&mut players, |players| !players.is_empty()).await;
+        assert_eq!(
+            list,
+            [MediaPlayer {
+                id: NAME.to_owned(),
+                name: "Test Player".to_owned(),
+                status: PlaybackStatus::Paused,
+                title: Some("Song".to_owned()),
+                artists: vec!["Artist".to_owned()],
+                album: None,
+                art_url: None,
+                length_us: Some(180_000_000),
+                position_us: Some(42_000_000),
+                volume: Some(0.5),
+            }]
+        );
+
+        let command = |action| PlayerCommand {
+            player: NAME.to_owned(),
+            action,
+        };
+        control_on(&connection, command(PlayerAction::PlayPause))
+            .await
+            .unwrap();
+        wait_for(&mut players, |players| {
+            players[0].status == PlaybackStatus::Playing
+        })
+        .await;
+
+        control_on(&connection, command(PlayerAction::SetVolume(1.5)))
+            .await
+            .unwrap();
+        wait_for(&mut players, |players| players[0].volume == Some(1.0)).await;
+
+        for action in [
+            PlayerAction::Pause,
+            PlayerAction::Next,
+            PlayerAction::Previous,
+            PlayerAction::Seek(-5_000_000),
+        ] {
+            control_on(&connection, command(action)).await.unwrap();
+        }
+        assert_eq!(
+            *calls.lock().unwrap(),
+            [
+                "PlayPause",
+                "SetVolume(1)",
+                "Pause",
+                "Next",
+                "Previous",
+                "Seek(-5000000)"
+            ]
+        );
+
+        // Only MPRIS players can be controlled
+        let other = PlayerCommand {
+            player: "org.freedesktop.DBus".to_owned(),
+            action: PlayerAction::Pause,
+        };
+        assert!(control_on(&connection, other).await.is_err());
+
+        // Players that quit are forgotten
+        player.release_name(NAME).await.unwrap();
+        wait_for(&mut players, Vec::is_empty).await;
+        follower.abort();
+    }
+}
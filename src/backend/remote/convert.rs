@@ -0,0 +1,502 @@
+//! Conversion of captured frames to tightly packed RGB.
+
+use std::{
+    borrow::Cow,
+    hash::{DefaultHasher, Hash, Hasher},
+};
+
+use anyhow::{Context, bail};
+
+/// The pixel formats we know how to convert.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PixelFormat {
+    Rgb,
+    Bgr,
+    Rgbx,
+    Bgrx,
+    Xrgb,
+    Xbgr,
+    Rgba,
+    Bgra,
+    Argb,
+    Abgr,
+    /// Packed 4:2:2 YUV, ordered as Y0 U Y1 V
+    Yuy2,
+    /// Packed 4:2:2 YUV, ordered as U Y0 V Y1
+    Uyvy,
+    /// Planar 4:2:0 YUV with a Y, a U and a V plane
+    I420,
+    /// Planar 4:2:0 YUV with a Y, a V and a U plane
+    Yv12,
+    /// 4:2:0 YUV with a Y plane and an interleaved UV plane
+    Nv12,
+}
+
+impl PixelFormat {
+    /// The number of planes the format consists of.
+    pub fn plane_count(self) -> usize {
+        match self {
+            Self::I420 | Self::Yv12 => 3,
+            Self::Nv12 => 2,
+            _ => 1,
+        }
+    }
+
+    /// The index of the red, green and blue channel and the number of bytes per pixel
+    /// if this is a packed RGB format.
+    fn rgb_layout(self) -> Option<([usize; 3], usize)> {
+        match self {
+            Self::Rgb => Some(([0, 1, 2], 3)),
+            Self::Bgr => Some(([2, 1, 0], 3)),
+            // Alpha is ignored, as a screen capture has nothing to be transparent against
+            Self::Rgbx | Self::Rgba => Some(([0, 1, 2], 4)),
+            Self::Bgrx | Self::Bgra => Some(([2, 1, 0], 4)),
+            Self::Xrgb | Self::Argb => Some(([1, 2, 3], 4)),
+            Self::Xbgr | Self::Abgr => Some(([3, 2, 1], 4)),
+            _ => None,
+        }
+    }
+}
+
+/// A single plane of image data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Plane {
+    pub data: Vec<u8>,
+    /// The number of bytes between the start of two rows, which may include padding
+    pub stride: usize,
+}
+
+/// A frame as captured from the screen.
+///
+/// Planar formats can either have one [`Plane`] per plane,
+/// or a single [`Plane`] containing all planes after each other.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawFrame {
+    pub format: PixelFormat,
+    pub width: usize,
+    pub height: usize,
+    pub planes: Vec<Plane>,
+}
+
+/// A frame of tightly packed 8-bit RGB.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RgbFrame {
+    pub width: usize,
+    pub height: usize,
+    pub data: Vec<u8>,
+}
+
+/// A borrowed view of a single plane.
+#[derive(Clone, Copy)]
+struct PlaneRef<'a> {
+    data: &'a [u8],
+    stride: usize,
+}
+
+impl<'a> PlaneRef<'a> {
+    /// Get the first `len` bytes of row `y`.
+    fn row(&self, y: usize, len: usize) -> anyhow::Result<&'a [u8]> {
+        let start = y * self.stride;
+        self.data
+            .get(start..start + len)
+            .with_context(|| format!("Row {y} is outside of the plane"))
+    }
+}
+
+impl RawFrame {
+    /// Hash the contents of the frame, so identical frames can be skipped.
+    pub fn content_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.format.hash(&mut hasher);
+        self.width.hash(&mut hasher);
+        self.height.hash(&mut hasher);
+        for plane in &self.planes {
+            plane.stride.hash(&mut hasher);
+            plane.data.hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+
+    /// Convert the frame to tightly packed 8-bit RGB.
+    pub fn to_rgb(&self) -> anyhow::Result<RgbFrame> {
+        let (width, height) = (self.width, self.height);
+        let planes = self.plane_refs()?;
+        let mut rgb = Vec::with_capacity(width * height * 3);
+
+        if let Some((channels, bytes_per_pixel)) = self.format.rgb_layout() {
+            for y in 0..height {
+                let row = planes[0].row(y, width * bytes_per_pixel)?;
+                for pixel in row.chunks_exact(bytes_per_pixel) {
+                    rgb.extend(channels.map(|channel| pixel[channel]));
+                }
+            }
+        } else {
+            self.convert_yuv(&planes, &mut rgb)?;
+        }
+
+        Ok(RgbFrame {
+            width,
+            height,
+            data: rgb,
+        })
+    }
+
+    /// Convert the YUV planes of the frame to RGB, appending the result to `rgb`.
+    fn convert_yuv(&self, planes: &[PlaneRef<'_>], rgb: &mut Vec<u8>) -> anyhow::Result<()> {
+        let (width, height) = (self.width, self.height);
+
+        match self.format {
+            PixelFormat::Yuy2 | PixelFormat::Uyvy => {
+                // Index of Y0, U, Y1 and V in each macropixel
+                let [y0_index, u_index, y1_index, v_index] = match self.format {
+                    PixelFormat::Yuy2 => [0, 1, 2, 3],
+                    _ => [1, 0, 3, 2],
+                };
+                for y in 0..height {
+                    let row = planes[0].row(y, width.div_ceil(2) * 4)?;
+                    for (x, macropixel) in row.chunks_exact(4).enumerate() {
+                        let (u, v) = (macropixel[u_index], macropixel[v_index]);
+                        rgb.extend(yuv_to_rgb(macropixel[y0_index], u, v));
+                        if 2 * x + 1 < width {
+                            rgb.extend(yuv_to_rgb(macropixel[y1_index], u, v));
+                        }
+                    }
+                }
+            }
+            PixelFormat::I420 | PixelFormat::Yv12 => {
+                let (u_plane, v_plane) = match self.format {
+                    PixelFormat::I420 => (planes[1], planes[2]),
+                    _ => (planes[2], planes[1]),
+                };
+                for y in 0..height {
+                    let y_row = planes[0].row(y, width)?;
+                    let u_row = u_plane.row(y / 2, width.div_ceil(2))?;
+                    let v_row = v_plane.row(y / 2, width.div_ceil(2))?;
+                    for (x, &luma) in y_row.iter().enumerate() {
+                        rgb.extend(yuv_to_rgb(luma, u_row[x / 2], v_row[x / 2]));
+                    }
+                }
+            }
+            PixelFormat::Nv12 => {
+                for y in 0..height {
+                    let y_row = planes[0].row(y, width)?;
+                    let uv_row = planes[1].row(y / 2, width.div_ceil(2) * 2)?;
+                    for (x, &luma) in y_row.iter().enumerate() {
+                        let uv = 2 * (x / 2);
+                        rgb.extend(yuv_to_rgb(luma, uv_row[uv], uv_row[uv + 1]));
+                    }
+                }
+            }
+            format => bail!("{:?} is not a YUV format", format),
+        }
+
+        Ok(())
+    }
+
+    /// Get one [`PlaneRef`] per plane of the format, splitting up the data if all planes are stored together.
+    fn plane_refs(&self) -> anyhow::Result<Vec<PlaneRef<'_>>> {
+        let plane_count = self.format.plane_count();
+
+        if self.planes.len() >= plane_count {
+            return Ok(self.planes[..plane_count]
+                .iter()
+                .map(|plane| PlaneRef {
+                    data: &plane.data,
+                    stride: plane.stride,
+                })
+                .collect());
+        }
+
+        let [plane] = self.planes.as_slice() else {
+            bail!(
+                "Expected {} planes for {:?}, got {}",
+                plane_count,
+                self.format,
+                self.planes.len()
+            );
+        };
+
+        // The planes are stored after each other, with the chroma rows being half as long
+        // (for I420 and YV12) or as long (for NV12) as the luma rows
+        let chroma_rows = self.height.div_ceil(2);
+        let chroma_stride = match self.format {
+            PixelFormat::Nv12 => plane.stride,
+            _ => plane.stride.div_ceil(2),
+        };
+
+        let mut plane_refs = Vec::with_capacity(plane_count);
+        let mut start = 0;
+        for (index, (stride, rows)) in [(plane.stride, self.height)]
+            .into_iter()
+            .chain([(chroma_stride, chroma_rows); 2])
+            .take(plane_count)
+            .enumerate()
+        {
+            let end = start + stride * rows;
+            let Some(data) = plane.data.get(start..end.min(plane.data.len())) else {
+                bail!("Plane {index} of {:?} frame is missing", self.format);
+            };
+            plane_refs.push(PlaneRef { data, stride });
+            start = end;
+        }
+
+        Ok(plane_refs)
+    }
+}
+
+impl RgbFrame {
+    /// Scale the frame down so it is at most `max_width` pixels wide, keeping the aspect ratio.
+    pub fn fit_width(&self, max_width: usize) -> Cow<'_, RgbFrame> {
+        if max_width == 0 || self.width <= max_width {
+            return Cow::Borrowed(self);
+        }
+
+        let width = max_width;
+        let height = (self.height * width / self.width).max(1);
+
+        // Each output pixel is the average of the source pixels it covers
+        let mut data = Vec::with_capacity(width * height * 3);
+        for y in 0..height {
+            let y_start = y * self.height / height;
+            let y_end = ((y + 1) * self.height / height).max(y_start + 1);
+            for x in 0..width {
+                let x_start = x * self.width / width;
+                let x_end = ((x + 1) * self.width / width).max(x_start + 1);
+
+                let mut sum = [0usize; 3];
+                for source_y in y_start..y_end {
+                    let row = &self.data[(source_y * self.width + x_start) * 3..]
+                        [..(x_end - x_start) * 3];
+                    for pixel in row.chunks_exact(3) {
+                        sum[0] += usize::from(pixel[0]);
+                        sum[1] += usize::from(pixel[1]);
+                        sum[2] += usize::from(pixel[2]);
+                    }
+                }
+
+                let count = (y_end - y_start) * (x_end - x_start);
+                data.extend(sum.map(|channel| (channel / count) as u8));
+            }
+        }
+
+        Cow::Owned(RgbFrame {
+            width,
+            height,
+            data,
+        })
+    }
+}
+
+/// Convert a limited range BT.601 YUV pixel to RGB.
+fn yuv_to_rgb(y: u8, u: u8, v: u8) -> [u8; 3] {
+    let c = i32::from(y) - 16;
+    let d = i32::from(u) - 128;
+    let e = i32::from(v) - 128;
+
+    let r = (298 * c + 409 * e + 128) >> 8;
+    let g = (298 * c - 100 * d - 208 * e + 128) >> 8;
+    let b = (298 * c + 516 * d + 128) >> 8;
+
+    [r, g, b].map(|channel| channel.clamp(0, 255) as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK: [u8; 3] = [0, 0, 0];
+    const WHITE: [u8; 3] = [255, 255, 255];
+    // Limited range BT.601 values of pure red and blue
+    const RED_YUV: [u8; 3] = [81, 90, 240];
+    const BLUE_YUV: [u8; 3] = [41, 240, 110];
+
+    fn frame(
+        format: PixelFormat,
+        width: usize,
+        height: usize,
+        planes: &[(&[u8], usize)],
+    ) -> RawFrame {
+        RawFrame {
+            format,
+            width,
+            height,
+            planes: planes
+                .iter()
+                .map(|&(data, stride)| Plane {
+                    data: data.to_vec(),
+                    stride,
+                })
+                .collect(),
+        }
+    }
+
+    fn pixels(colors: &[[u8; 3]]) -> Vec<u8> {
+        colors.concat()
+    }
+
+    #[test]
+    fn yuv_to_rgb_known_values() {
+        assert_eq!(yuv_to_rgb(16, 128, 128), BLACK);
+        assert_eq!(yuv_to_rgb(235, 128, 128), WHITE);
+        let [y, u, v] = RED_YUV;
+        assert_eq!(yuv_to_rgb(y, u, v), [255, 0, 0]);
+        let [y, u, v] = BLUE_YUV;
+        assert_eq!(yuv_to_rgb(y, u, v), [0, 0, 255]);
+    }
+
+    #[test]
+    fn rgb_variants() {
+        let cases = [
+            (PixelFormat::Rgb, [10, 20, 30]),
+            (PixelFormat::Bgr, [30, 20, 10]),
+            (PixelFormat::Rgbx, [10, 20, 30]),
+            (PixelFormat::Rgba, [10, 20, 30]),
+            (PixelFormat::Bgrx, [30, 20, 10]),
+            (PixelFormat::Bgra, [30, 20, 10]),
+            (PixelFormat::Xrgb, [20, 30, 40]),
+            (PixelFormat::Argb, [20, 30, 40]),
+            (PixelFormat::Xbgr, [40, 30, 20]),
+            (PixelFormat::Abgr, [40, 30, 20]),
+        ];
+        for (format, expected) in cases {
+            let (_, bytes_per_pixel) = format.rgb_layout().unwrap();
+            let data = &[10, 20, 30, 40][..bytes_per_pixel];
+            let rgb = frame(format, 1, 1, &[(data, bytes_per_pixel)])
+                .to_rgb()
+                .unwrap();
+            assert_eq!(rgb.data, expected, "{format:?}");
+        }
+    }
+
+    #[test]
+    fn stride_padding_is_skipped() {
+        // Two rows of two RGB pixels, each padded with two bytes
+        let data = [
+            1, 2, 3, 4, 5, 6, 0xee, 0xee, 7, 8, 9, 10, 11, 12, 0xee, 0xee,
+        ];
+        let rgb = frame(PixelFormat::Rgb, 2, 2, &[(&data, 8)])
+            .to_rgb()
+            .unwrap();
+        assert_eq!(rgb.data, (1..=12).collect::<Vec<u8>>());
+
+        let data = [
+            1, 2, 3, 0, 0xee, 0xee, 0xee, 0xee, 4, 5, 6, 0, 0xee, 0xee, 0xee, 0xee,
+        ];
+        let rgb = frame(PixelFormat::Rgbx, 1, 2, &[(&data, 8)])
+            .to_rgb()
+            .unwrap();
+        assert_eq!(rgb.data, [1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn short_plane_is_an_error() {
+        let data = [0; 5];
+        assert!(
+            frame(PixelFormat::Rgb, 2, 1, &[(&data, 6)])
+                .to_rgb()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn packed_yuv_with_odd_width() {
+        let [red_y, red_u, red_v] = RED_YUV;
+        // The second Y of the last macropixel is outside the frame
+        let yuy2 = [red_y, red_u, red_y, red_v, 16, 128, 235, 128];
+        let uyvy = [red_u, red_y, red_v, red_y, 128, 16, 128, 235];
+        let expected = pixels(&[[255, 0, 0], [255, 0, 0], BLACK]);
+
+        for (format, data) in [(PixelFormat::Yuy2, yuy2), (PixelFormat::Uyvy, uyvy)] {
+            let rgb = frame(format, 3, 1, &[(&data, 8)]).to_rgb().unwrap();
+            assert_eq!(rgb.data, expected, "{format:?}");
+        }
+    }
+
+    /// A 4x2 frame with a red left half and a blue right half.
+    fn planar_expected() -> Vec<u8> {
+        let row = pixels(&[[255, 0, 0], [255, 0, 0], [0, 0, 255], [0, 0, 255]]);
+        [row.clone(), row].concat()
+    }
+
+    fn planar_yuv() -> ([u8; 8], [u8; 2], [u8; 2]) {
+        let (red_y, blue_y) = (RED_YUV[0], BLUE_YUV[0]);
+        let y = [red_y, red_y, blue_y, blue_y, red_y, red_y, blue_y, blue_y];
+        (y, [RED_YUV[1], BLUE_YUV[1]], [RED_YUV[2], BLUE_YUV[2]])
+    }
+
+    #[test]
+    fn planar_yuv_separate_planes() {
+        let (y, u, v) = planar_yuv();
+
+        let i420 = frame(PixelFormat::I420, 4, 2, &[(&y, 4), (&u, 2), (&v, 2)]);
+        assert_eq!(i420.to_rgb().unwrap().data, planar_expected());
+
+        let yv12 = frame(PixelFormat::Yv12, 4, 2, &[(&y, 4), (&v, 2), (&u, 2)]);
+        assert_eq!(yv12.to_rgb().unwrap().data, planar_expected());
+
+        let uv = [u[0], v[0], u[1], v[1]];
+        let nv12 = frame(PixelFormat::Nv12, 4, 2, &[(&y, 4), (&uv, 4)]);
+        assert_eq!(nv12.to_rgb().unwrap().data, planar_expected());
+    }
+
+    #[test]
+    fn planar_yuv_single_buffer() {
+        let (y, u, v) = planar_yuv();
+
+        let data = [&y[..], &u, &v].concat();
+        let i420 = frame(PixelFormat::I420, 4, 2, &[(&data, 4)]);
+        let planes = i420.plane_refs().unwrap();
+        assert_eq!(planes.len(), 3);
+        assert_eq!((planes[0].data, planes[0].stride), (&y[..], 4));
+        assert_eq!((planes[1].data, planes[1].stride), (&u[..], 2));
+        assert_eq!((planes[2].data, planes[2].stride), (&v[..], 2));
+        assert_eq!(i420.to_rgb().unwrap().data, planar_expected());
+
+        let data = [&y[..], &v, &u].concat();
+        let yv12 = frame(PixelFormat::Yv12, 4, 2, &[(&data, 4)]);
+        assert_eq!(yv12.to_rgb().unwrap().data, planar_expected());
+
+        let uv = [u[0], v[0], u[1], v[1]];
+        let data = [&y[..], &uv].concat();
+        let nv12 = frame(PixelFormat::Nv12, 4, 2, &[(&data, 4)]);
+        let planes = nv12.plane_refs().unwrap();
+        assert_eq!(planes.len(), 2);
+        assert_eq!((planes[1].data, planes[1].stride), (&uv[..], 4));
+        assert_eq!(nv12.to_rgb().unwrap().data, planar_expected());
+    }
+
+    #[test]
+    fn single_buffer_missing_plane() {
+        let (y, u, _) = planar_yuv();
+        let data = [&y[..], &u].concat();
+        let i420 = frame(PixelFormat::I420, 4, 2, &[(&data, 4)]);
+        assert!(i420.to_rgb().is_err());
+    }
+
+    #[test]
+    fn fit_width_odd_widths() {
+        let gray = |value: u8| [value; 3];
+        let frame = RgbFrame {
+            width: 5,
+            height: 1,
+            data: pixels(&[gray(0), gray(10), gray(20), gray(30), gray(40)]),
+        };
+
+        let scaled = frame.fit_width(2);
+        assert_eq!((scaled.width, scaled.height), (2, 1));
+        // The first output pixel covers two source pixels, the second covers three
+        assert_eq!(scaled.data, pixels(&[gray(5), gray(30)]));
+
+        let frame = RgbFrame {
+            width: 7,
+            height: 3,
+            data: vec![100; 7 * 3 * 3],
+        };
+        let scaled = frame.fit_width(3);
+        assert_eq!((scaled.width, scaled.height), (3, 1));
+        assert_eq!(scaled.data, vec![100; 3 * 3]);
+
+        assert!(matches!(frame.fit_width(7), Cow::Borrowed(_)));
+        assert!(matches!(frame.fit_width(0), Cow::Borrowed(_)));
+    }
+}
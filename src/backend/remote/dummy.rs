@@ -1,10 +1,111 @@
+use std::{path::PathBuf, sync::LazyLock};
+
+use dioxus::fullstack::{PostcardEncoding, WebSocketOptions, Websocket};
 use dioxus::prelude::*;
+use dioxus_fullstack::response::Response;
+use tokio::sync::watch;
+
+use crate::{
+    backend::remote::{CursorState, ScreenshotOptions},
+    frontend::remote::{Interaction, Monitor, ScreencastSettings, SessionStatus},
+};
+
+/// There is no screencast, so the cursor never changes.
+static CURSOR: LazyLock<watch::Sender<CursorState>> =
+    LazyLock::new(|| watch::channel(CursorState::default()).0);
+
+static STATUS: LazyLock<watch::Sender<SessionStatus>> =
+    LazyLock::new(|| watch::channel(SessionStatus::Unsupported).0);
 
 pub type ScreencastResponse = ();
 
-pub async fn screencast() -> Result<ScreencastResponse, HttpError> {
+pub async fn screencast(_settings: ScreencastSettings) -> Result<ScreencastResponse, HttpError> {
+    Err(HttpError {
+        status: StatusCode::NOT_IMPLEMENTED,
+        message: None,
+    })
+}
+
+pub async fn tiles(
+    _options: WebSocketOptions,
+    _settings: ScreencastSettings,
+) -> Result<Websocket<(), (), PostcardEncoding>, HttpError> {
+    Err(HttpError {
+        status: StatusCode::NOT_IMPLEMENTED,
+        message: None,
+    })
+}
+
+pub async fn h264(
+    _options: WebSocketOptions,
+    _settings: ScreencastSettings,
+) -> Result<Websocket<(), (), PostcardEncoding>, HttpError> {
+    Err(HttpError {
+        status: StatusCode::NOT_IMPLEMENTED,
+        message: None,
+    })
+}
+
+pub async fn screenshot(_options: ScreenshotOptions) -> Result<Response, HttpError> {
+    Err(HttpError {
+        status: StatusCode::NOT_IMPLEMENTED,
+        message: None,
+    })
+}
+
+pub async fn start_recording() -> Result<PathBuf, HttpError> {
     Err(HttpError {
         status: StatusCode::NOT_IMPLEMENTED,
         message: None,
     })
 }
+
+pub async fn stop_recording() -> Result<PathBuf, HttpError> {
+    Err(HttpError {
+        status: StatusCode::NOT_IMPLEMENTED,
+        message: None,
+    })
+}
+
+pub async fn monitors() -> Result<Vec<Monitor>, HttpError> {
+    // There are no monitors to share
+    Ok(Vec::new())
+}
+
+pub async fn select_monitor(_id: u32) -> Result<(), HttpError> {
+    Err(HttpError::new(
+        StatusCode::NOT_FOUND,
+        "There are no monitors",
+    ))
+}
+
+pub fn cursor() -> watch::Receiver<CursorState> {
+    CURSOR.subscribe()
+}
+
+pub fn status() -> watch::Receiver<SessionStatus> {
+    STATUS.subscribe()
+}
+
+pub async fn clipboard() -> anyhow::Result<String> {
+    anyhow::bail!("There is no clipboard to read")
+}
+
+pub async fn interact(_interaction: Interaction) -> anyhow::Result<()> {
+    // There is nothing to control
+    Ok(())
+}
+
+pub async fn request_permission() -> Result<(), HttpError> {
+    Err(HttpError::new(
+        StatusCode::NOT_IMPLEMENTED,
+        "There is no desktop to control",
+    ))
+}
+
+pub async fn revoke_permission() -> Result<(), HttpError> {
+    Err(HttpError::new(
+        StatusCode::NOT_IMPLEMENTED,
+        "There is no desktop to control",
+    ))
+}
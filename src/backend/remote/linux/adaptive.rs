@@ -0,0 +1,235 @@
+use std::time::Duration;
+
+use dioxus::prelude::*;
+use futures::Stream;
+use tokio::{
+    sync::broadcast::{self, error::RecvError},
+    time::Instant,
+};
+
+use super::{
+    JpegFrame,
+    encoder::{self, FrameReceiver},
+};
+use crate::frontend::remote::ScreencastSettings;
+
+/// How long we measure a client before deciding whether to change its settings.
+const WINDOW: Duration = Duration::from_secs(2);
+
+/// How many good windows in a row we need before improving the settings.
+const CALM_WINDOWS: u32 = 3;
+
+/// The quality (in percent of the requested quality) and frame rate limit of each level,
+/// from best to worst.
+const LEVELS: [(u16, Option<u32>); 5] = [
+    (100, None),
+    (80, Some(20)),
+    (60, Some(15)),
+    (45, Some(10)),
+    (30, Some(5)),
+];
+
+/// Statistics about a client over the current [`WINDOW`].
+struct Stats {
+    start: Instant,
+    sent: u64,
+    skipped: u64,
+    bytes: usize,
+    /// How long we spent waiting for the client to accept the frames we sent it
+    busy: Duration,
+}
+
+impl Stats {
+    fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            sent: 0,
+            skipped: 0,
+            bytes: 0,
+            busy: Duration::ZERO,
+        }
+    }
+}
+
+struct Client {
+    requested: ScreencastSettings,
+    level: usize,
+    calm_windows: u32,
+    frames: FrameReceiver,
+    /// The last frame of the encoder, sent before waiting for the next one after subscribing
+    pending: Option<JpegFrame>,
+    receiver: broadcast::Receiver<JpegFrame>,
+    stats: Stats,
+    last_sent: Option<Instant>,
+}
+
+impl Client {
+    fn new(requested: ScreencastSettings, frames: FrameReceiver) -> Self {
+        let (pending, receiver) = encoder::subscribe(level_settings(requested, 0), &frames);
+        Self {
+            requested,
+            level: 0,
+            calm_windows: 0,
+            frames,
+            pending,
+            receiver,
+            stats: Stats::new(),
+            last_sent: None,
+        }
+    }
+
+    async fn next_frame(&mut self) -> Option<JpegFrame> {
+        // We only get polled again once the previous frame has been written,
+        // so the time since we sent it tells us how slow the client is
+        if let Some(last_sent) = self.last_sent.take() {
+            self.stats.busy += last_sent.elapsed();
+        }
+
+        if self.requested.adaptive && self.stats.start.elapsed() >= WINDOW {
+            self.adapt();
+        }
+
+        loop {
+            let frame = match self.pending.take() {
+                Some(frame) => Ok(frame),
+                None => self.receiver.recv().await,
+            };
+            match frame {
+                Ok(frame) => {
+                    self.stats.sent += 1;
+                    self.stats.bytes += frame.0.len();
+                    self.last_sent = Some(Instant::now());
+                    return Some(frame);
+                }
+                // The client is too slow to get every frame, skip to the newest one
+                Err(RecvError::Lagged(skipped)) => self.stats.skipped += skipped,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+
+    /// Change the level based on the statistics of the last window.
+    fn adapt(&mut self) {
+        let stats = std::mem::replace(&mut self.stats, Stats::new());
+        let elapsed = stats.start.elapsed();
+        let busy_ratio = stats.busy.as_secs_f64() / elapsed.as_secs_f64();
+        let throughput = stats.bytes as f64 / elapsed.as_secs_f64();
+        debug!(
+            "Client at level {}: {:.0} kB/s, {} frames sent, {} skipped, busy {:.0}% of the time",
+            self.level,
+            throughput / 1000.0,
+            stats.sent,
+            stats.skipped,
+            busy_ratio * 100.0,
+        );
+
+        let struggling = busy_ratio > 0.8 || stats.skipped * 4 > stats.sent;
+        let relaxed = busy_ratio < 0.4 && stats.skipped == 0;
+
+        let new_level = if struggling {
+            self.calm_windows = 0;
+            (self.level + 1).min(LEVELS.len() - 1)
+        } else if relaxed && self.level > 0 {
+            self.calm_windows += 1;
+            if self.calm_windows >= CALM_WINDOWS {
+                self.calm_windows = 0;
+                self.level - 1
+            } else {
+                self.level
+            }
+        } else {
+            self.calm_windows = 0;
+            self.level
+        };
+
+        if new_level != self.level {
+            info!(
+                "Changing screencast level from {} to {} ({:.0} kB/s)",
+                self.level,
+                new_level,
+                throughput / 1000.0
+            );
+            self.level = new_level;
+            (self.pending, self.receiver) =
+                encoder::subscribe(level_settings(self.requested, new_level), &self.frames);
+        }
+    }
+}
+
+/// Get the settings to use at the given level, never exceeding the requested settings.
+fn level_settings(requested: ScreencastSettings, level: usize) -> ScreencastSettings {
+    let (quality_percent, max_fps) = LEVELS[level];
+    let requested_quality = u16::from(requested.quality.clamp(1, 100));
+    // Below 10 the frames are unrecognizable, unless that is what was asked for
+    let quality = (requested_quality * quality_percent / 100)
+        .max(10)
+        .min(requested_quality);
+
+    ScreencastSettings {
+        quality: quality as u8,
+        max_width: requested.max_width,
+        max_fps: match (requested.max_fps, max_fps) {
+            (Some(requested), Some(limit)) => Some(requested.min(limit)),
+            (requested, limit) => requested.or(limit),
+        },
+        adaptive: requested.adaptive,
+    }
+}
+
+/// Create a stream of frames for a single client.
+///
+/// If the client asked for adaptive settings, the quality and frame rate are lowered when
+/// the client can't keep up, and raised again when it can.
+pub fn frame_stream(
+    settings: ScreencastSettings,
+    frames: FrameReceiver,
+) -> impl Stream<Item = anyhow::Result<JpegFrame>> + Send + 'static {
+    let client = Client::new(settings, frames);
+    futures::stream::unfold(client, |mut client| async move {
+        let frame = client.next_frame().await?;
+        Some((Ok(frame), client))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(quality: u8, max_fps: Option<u32>) -> ScreencastSettings {
+        ScreencastSettings {
+            quality,
+            max_width: Some(1280),
+            max_fps,
+            adaptive: true,
+        }
+    }
+
+    #[test]
+    fn levels_lower_quality_and_frame_rate() {
+        assert_eq!(level_settings(settings(70, None), 0), settings(70, None));
+        assert_eq!(
+            level_settings(settings(70, None), 1),
+            settings(56, Some(20))
+        );
+        assert_eq!(level_settings(settings(70, None), 4), settings(21, Some(5)));
+    }
+
+    #[test]
+    fn levels_never_exceed_the_request() {
+        // The requested frame rate is kept when it is lower than the level's
+        assert_eq!(
+            level_settings(settings(70, Some(10)), 1),
+            settings(56, Some(10))
+        );
+        assert_eq!(
+            level_settings(settings(70, Some(30)), 2),
+            settings(42, Some(15))
+        );
+
+        // Quality doesn't drop below 10, but doesn't rise above what was asked for either
+        assert_eq!(level_settings(settings(20, None), 4), settings(10, Some(5)));
+        assert_eq!(level_settings(settings(5, None), 0), settings(5, None));
+        assert_eq!(level_settings(settings(5, None), 4), settings(5, Some(5)));
+        assert_eq!(level_settings(settings(0, None), 0), settings(1, None));
+    }
+}
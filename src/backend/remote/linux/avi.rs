@@ -0,0 +1,214 @@
+//! A minimal writer for AVI files containing a single MJPEG video stream.
+
+use std::io::{self, Seek, SeekFrom, Write};
+
+/// Offset of the total frame count in the main AVI header.
+const TOTAL_FRAMES_OFFSET: u64 = 48;
+/// Offset of the suggested buffer size in the main AVI header.
+const AVIH_BUFFER_SIZE_OFFSET: u64 = 60;
+/// Offset of the stream length in the stream header.
+const STREAM_LENGTH_OFFSET: u64 = 140;
+/// Offset of the suggested buffer size in the stream header.
+const STRH_BUFFER_SIZE_OFFSET: u64 = 144;
+/// Offset of the size of the `movi` list.
+const MOVI_SIZE_OFFSET: u64 = 216;
+/// Offset of the `movi` fourcc, which the index offsets are relative to.
+const MOVI_OFFSET: u64 = 220;
+
+const AVIF_HASINDEX: u32 = 0x10;
+const AVIIF_KEYFRAME: u32 = 0x10;
+
+pub struct AviWriter<W: Write + Seek> {
+    writer: W,
+    /// Offset and size of every frame, for the index
+    frames: Vec<(u32, u32)>,
+    /// Number of bytes written so far
+    size: u64,
+    largest_frame: u32,
+}
+
+impl<W: Write + Seek> AviWriter<W> {
+    /// Write the headers of a video with the given size and frame rate.
+    pub fn new(mut writer: W, width: u32, height: u32, fps: u32) -> io::Result<Self> {
+        let mut header = Vec::with_capacity(224);
+
+        header.extend_from_slice(b"RIFF");
+        push_u32(&mut header, 0); // patched in finish
+        header.extend_from_slice(b"AVI ");
+
+        header.extend_from_slice(b"LIST");
+        push_u32(&mut header, 192);
+        header.extend_from_slice(b"hdrl");
+
+        header.extend_from_slice(b"avih");
+        push_u32(&mut header, 56);
+        push_u32(&mut header, 1_000_000 / fps); // microseconds per frame
+        push_u32(&mut header, 0); // max bytes per second
+        push_u32(&mut header, 0); // padding granularity
+        push_u32(&mut header, AVIF_HASINDEX);
+        push_u32(&mut header, 0); // total frames, patched in finish
+        push_u32(&mut header, 0); // initial frames
+        push_u32(&mut header, 1); // streams
+        push_u32(&mut header, 0); // suggested buffer size, patched in finish
+        push_u32(&mut header, width);
+        push_u32(&mut header, height);
+        header.extend_from_slice(&[0; 16]);
+
+        header.extend_from_slice(b"LIST");
+        push_u32(&mut header, 116);
+        header.extend_from_slice(b"strl");
+
+        header.extend_from_slice(b"strh");
+        push_u32(&mut header, 56);
+        header.extend_from_slice(b"vids");
+        header.extend_from_slice(b"MJPG");
+        push_u32(&mut header, 0); // flags
+        push_u32(&mut header, 0); // priority and language
+        push_u32(&mut header, 0); // initial frames
+        push_u32(&mut header, 1); // scale
+        push_u32(&mut header, fps); // rate
+        push_u32(&mut header, 0); // start
+        push_u32(&mut header, 0); // length, patched in finish
+        push_u32(&mut header, 0); // suggested buffer size, patched in finish
+        push_u32(&mut header, u32::MAX); // default quality
+        push_u32(&mut header, 0); // sample size
+        for value in [0, 0, width as u16, height as u16] {
+            header.extend_from_slice(&value.to_le_bytes());
+        }
+
+        header.extend_from_slice(b"strf");
+        push_u32(&mut header, 40);
+        push_u32(&mut header, 40); // size of the bitmap info header
+        push_u32(&mut header, width);
+        push_u32(&mut header, height);
+        header.extend_from_slice(&1u16.to_le_bytes()); // planes
+        header.extend_from_slice(&24u16.to_le_bytes()); // bits per pixel
+        header.extend_from_slice(b"MJPG");
+        push_u32(&mut header, width * height * 3);
+        header.extend_from_slice(&[0; 16]);
+
+        header.extend_from_slice(b"LIST");
+        push_u32(&mut header, 0); // patched in finish
+        header.extend_from_slice(b"movi");
+
+        debug_assert_eq!(header.len() as u64, MOVI_OFFSET + 4);
+        writer.write_all(&header)?;
+
+        Ok(Self {
+            writer,
+            frames: Vec::new(),
+            size: header.len() as u64,
+            largest_frame: 0,
+        })
+    }
+
+    /// The number of bytes written so far.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Append a JPEG image as the next frame.
+    pub fn write_frame(&mut self, jpeg: &[u8]) -> io::Result<()> {
+        let len = u32::try_from(jpeg.len()).map_err(io::Error::other)?;
+        let offset = u32::try_from(self.size - MOVI_OFFSET).map_err(io::Error::other)?;
+
+        self.writer.write_all(b"00dc")?;
+        self.writer.write_all(&len.to_le_bytes())?;
+        self.writer.write_all(jpeg)?;
+        // Chunks are padded to an even size
+        let padding = jpeg.len() % 2;
+        self.writer.write_all(&[0; 1][..padding])?;
+
+        self.frames.push((offset, len));
+        self.size += 8 + jpeg.len() as u64 + padding as u64;
+        self.largest_frame = self.largest_frame.max(len);
+        Ok(())
+    }
+
+    /// Write the index and fill in the sizes in the headers.
+    pub fn finish(mut self) -> io::Result<W> {
+        let movi_size = self.size - MOVI_OFFSET;
+
+        let mut index = Vec::with_capacity(8 + self.frames.len() * 16);
+        index.extend_from_slice(b"idx1");
+        push_u32(&mut index, self.frames.len() as u32 * 16);
+        for (offset, len) in &self.frames {
+            index.extend_from_slice(b"00dc");
+            push_u32(&mut index, AVIIF_KEYFRAME);
+            push_u32(&mut index, *offset);
+            push_u32(&mut index, *len);
+        }
+        self.writer.write_all(&index)?;
+        let riff_size = self.size + index.len() as u64 - 8;
+
+        let frame_count = self.frames.len() as u32;
+        for (offset, value) in [
+            (4, riff_size as u32),
+            (TOTAL_FRAMES_OFFSET, frame_count),
+            (AVIH_BUFFER_SIZE_OFFSET, self.largest_frame),
+            (STREAM_LENGTH_OFFSET, frame_count),
+            (STRH_BUFFER_SIZE_OFFSET, self.largest_frame),
+            (MOVI_SIZE_OFFSET, movi_size as u32),
+        ] {
+            self.writer.seek(SeekFrom::Start(offset))?;
+            self.writer.write_all(&value.to_le_bytes())?;
+        }
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()?;
+
+        Ok(self.writer)
+    }
+}
+
+fn push_u32(buffer: &mut Vec<u8>, value: u32) {
+    buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn sizes_and_index_match_the_frames() {
+        let frames: [&[u8]; 3] = [b"first", b"second", b"third!!"];
+        let mut writer = AviWriter::new(Cursor::new(Vec::new()), 640, 480, 10).unwrap();
+        for frame in frames {
+            writer.write_frame(frame).unwrap();
+        }
+        let avi = writer.finish().unwrap().into_inner();
+
+        assert_eq!(&avi[0..4], b"RIFF");
+        assert_eq!(u32_at(&avi, 4) as usize, avi.len() - 8);
+        assert_eq!(&avi[8..12], b"AVI ");
+        assert_eq!(u32_at(&avi, TOTAL_FRAMES_OFFSET as usize), 3);
+        assert_eq!(u32_at(&avi, STREAM_LENGTH_OFFSET as usize), 3);
+        assert_eq!(u32_at(&avi, AVIH_BUFFER_SIZE_OFFSET as usize), 7);
+
+        // Odd chunks are padded, so the list ends where the index starts
+        let movi = MOVI_OFFSET as usize;
+        assert_eq!(&avi[movi..movi + 4], b"movi");
+        let movi_size = u32_at(&avi, MOVI_SIZE_OFFSET as usize) as usize;
+        let index = movi + movi_size;
+        assert_eq!(movi_size, 4 + (8 + 6) + (8 + 6) + (8 + 8));
+        assert_eq!(&avi[index..index + 4], b"idx1");
+        assert_eq!(u32_at(&avi, index + 4), 3 * 16);
+        assert_eq!(index + 8 + 3 * 16, avi.len());
+
+        for (i, frame) in frames.iter().enumerate() {
+            let entry = index + 8 + i * 16;
+            assert_eq!(&avi[entry..entry + 4], b"00dc");
+            assert_eq!(u32_at(&avi, entry + 4), AVIIF_KEYFRAME);
+            let chunk = movi + u32_at(&avi, entry + 8) as usize;
+            assert_eq!(&avi[chunk..chunk + 4], b"00dc");
+            assert_eq!(u32_at(&avi, chunk + 4) as usize, frame.len());
+            assert_eq!(u32_at(&avi, entry + 12) as usize, frame.len());
+            assert_eq!(&avi[chunk + 8..chunk + 8 + frame.len()], *frame);
+        }
+    }
+}
@@ -0,0 +1,122 @@
+//! The interface between the remote desktop session and whatever captures the screen and injects input.
+
+use std::env;
+
+use anyhow::Context as _;
+use dioxus::prelude::*;
+use futures::future::BoxFuture;
+use tokio::sync::{mpsc, watch};
+
+use super::{
+    fake::FakeBackend,
+    robot::{self, Robot},
+    x11::X11Backend,
+};
+use crate::{
+    backend::remote::{CursorState, convert::RawFrame},
+    frontend::remote::{Monitor, MouseButton},
+};
+
+/// What the screen is streamed to, returned by [`RemoteBackend::start_streaming`].
+pub struct StreamReceivers {
+    pub frames: mpsc::Receiver<RawFrame>,
+    /// Gets the reason whenever the stream stops by itself
+    pub failures: mpsc::UnboundedReceiver<String>,
+}
+
+/// Captures the screen of a desktop and injects input into it.
+///
+/// Methods return boxed futures, so the session can hold any backend as a `Box<dyn RemoteBackend>`.
+pub trait RemoteBackend: Send + Sync {
+    /// Start streaming the selected monitor. Frames are sent to the returned receivers,
+    /// while the cursor, which is not part of the frames, is sent to `cursor_tx`.
+    fn start_streaming(
+        &mut self,
+        cursor_tx: watch::Sender<CursorState>,
+    ) -> BoxFuture<'_, anyhow::Result<StreamReceivers>>;
+
+    /// Wait until the desktop closes the session, which can only be waited for once.
+    fn closed(&self) -> BoxFuture<'static, ()>;
+
+    /// Stop streaming and close the session.
+    fn close(self: Box<Self>) -> BoxFuture<'static, anyhow::Result<()>>;
+
+    /// The monitors that can be streamed.
+    fn monitors(&self) -> Vec<Monitor>;
+
+    /// Stream and control the monitor with the given id instead.
+    fn select_monitor(&mut self, id: u32) -> BoxFuture<'_, anyhow::Result<()>>;
+
+    /// The size of the selected monitor, in the coordinates [`RemoteBackend::move_pointer`] expects.
+    fn monitor_size(&self) -> Option<(i32, i32)>;
+
+    /// Move the pointer to a position on the selected monitor.
+    fn move_pointer(&self, x: f64, y: f64) -> BoxFuture<'_, anyhow::Result<()>>;
+
+    fn pointer_button(
+        &self,
+        button: MouseButton,
+        pressed: bool,
+    ) -> BoxFuture<'_, anyhow::Result<()>>;
+
+    /// Scroll vertically by about the given number of pixels, where positive values scroll down.
+    fn scroll(&self, pixels: f64) -> BoxFuture<'_, anyhow::Result<()>>;
+
+    /// Press or release the key producing the given X11 keysym.
+    fn key(&self, keysym: i32, pressed: bool) -> BoxFuture<'_, anyhow::Result<()>>;
+
+    /// Put text on the clipboard of the desktop.
+    fn set_clipboard(&self, text: String) -> BoxFuture<'_, anyhow::Result<()>>;
+
+    /// Read the text on the clipboard of the desktop.
+    fn clipboard(&self) -> BoxFuture<'_, anyhow::Result<String>>;
+}
+
+/// The kinds of backends there are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Fake,
+    X11,
+    Portal,
+}
+
+/// Find the backend that fits the desktop we are running in,
+/// or the fake backend if the `fake-remote` feature is enabled.
+///
+/// X11 is only used if there is no Wayland display, as X11 clients on Wayland can't see other windows.
+fn kind() -> Kind {
+    if cfg!(feature = "fake-remote") {
+        Kind::Fake
+    } else if env::var_os("WAYLAND_DISPLAY").is_none() && env::var_os("DISPLAY").is_some() {
+        Kind::X11
+    } else {
+        Kind::Portal
+    }
+}
+
+/// Whether the user has to allow controlling the desktop on the media PC before a session can start.
+pub async fn needs_permission() -> bool {
+    kind() == Kind::Portal && !robot::has_restore_token().await
+}
+
+/// Start a session with the backend that fits the desktop we are running in.
+pub async fn connect() -> anyhow::Result<Box<dyn RemoteBackend>> {
+    match kind() {
+        Kind::Fake => {
+            info!("Using the fake remote desktop backend");
+            Ok(Box::new(FakeBackend::default()))
+        }
+        Kind::X11 => {
+            info!("Using the X11 remote desktop backend");
+            let backend = X11Backend::connect().context("Failed to connect to the X server")?;
+            Ok(Box::new(backend))
+        }
+        Kind::Portal => {
+            info!("Using the portal remote desktop backend");
+            let robot = Robot::new()
+                .await
+                .context("Failed to start the portal session")?;
+            Ok(Box::new(robot))
+        }
+    }
+}
@@ -0,0 +1,270 @@
+//! Screenshots and recordings of the screencast.
+
+use std::{
+    fs::{self, File},
+    io::BufWriter,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Context as _;
+use axum::{
+    body::Body,
+    http::header::{CACHE_CONTROL, CONTENT_TYPE},
+};
+use dioxus::prelude::*;
+use dioxus_fullstack::response::Response;
+use jpeg_encoder::ColorType;
+use tokio::time::timeout;
+
+use super::{CONTEXT, avi::AviWriter};
+use crate::backend::{
+    config,
+    remote::{ImageFormat, ScreenshotOptions, convert::RgbFrame},
+};
+
+/// How long to wait for a new frame, in case nobody was watching and the frames were not converted.
+const FRESH_FRAME_TIMEOUT: Duration = Duration::from_millis(1500);
+
+const SCREENSHOT_JPEG_QUALITY: u8 = 95;
+
+const RECORDING_FPS: u32 = 15;
+const RECORDING_JPEG_QUALITY: u8 = 85;
+/// Recordings are stopped before they get too big for the 32-bit sizes of AVI files.
+const RECORDING_MAX_SIZE: u64 = 2 * 1024 * 1024 * 1024;
+
+struct Recording {
+    path: PathBuf,
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<anyhow::Result<()>>,
+}
+
+static RECORDING: Mutex<Option<Recording>> = Mutex::new(None);
+
+/// Get the newest frame, giving the worker a chance to catch up if nobody was watching.
+async fn fresh_frame() -> Result<Arc<RgbFrame>, HttpError> {
+    let mut frames = CONTEXT.frame_receiver.clone();
+    frames.mark_unchanged();
+    // If the screen doesn't change, no new frame arrives and the current one is up to date
+    let _ = timeout(FRESH_FRAME_TIMEOUT, frames.changed()).await;
+
+    frames.borrow().clone().ok_or_else(|| {
+        HttpError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "No frame has been captured yet",
+        )
+    })
+}
+
+pub async fn screenshot(options: ScreenshotOptions) -> Result<Response, HttpError> {
+    let frame = fresh_frame().await?;
+
+    let encoded = tokio::task::spawn_blocking(move || encode_image(&frame, options.format))
+        .await
+        .map_err(|err| HttpError::new(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?
+        .map_err(|err| HttpError::new(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?;
+
+    let content_type = match options.format {
+        ImageFormat::Png => "image/png",
+        ImageFormat::Jpeg => "image/jpeg",
+    };
+    Response::builder()
+        .header(CONTENT_TYPE, content_type)
+        .header(CACHE_CONTROL, "no-store")
+        .body(Body::from(encoded))
+        .map_err(|err| HttpError::new(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))
+}
+
+fn encode_image(frame: &RgbFrame, format: ImageFormat) -> anyhow::Result<Vec<u8>> {
+    let mut encoded = Vec::new();
+    match format {
+        ImageFormat::Png => {
+            let mut encoder =
+                png::Encoder::new(&mut encoded, frame.width as u32, frame.height as u32);
+            encoder.set_color(png::ColorType::Rgb);
+            encoder.set_depth(png::BitDepth::Eight);
+            let mut writer = encoder.write_header()?;
+            writer.write_image_data(&frame.data)?;
+            writer.finish()?;
+        }
+        ImageFormat::Jpeg => encode_jpeg(frame, SCREENSHOT_JPEG_QUALITY, &mut encoded)?,
+    }
+    Ok(encoded)
+}
+
+fn encode_jpeg(frame: &RgbFrame, quality: u8, output: &mut Vec<u8>) -> anyhow::Result<()> {
+    jpeg_encoder::Encoder::new(output, quality).encode(
+        &frame.data,
+        frame.width as u16,
+        frame.height as u16,
+        ColorType::Rgb,
+    )?;
+    Ok(())
+}
+
+/// The directory recordings are saved to, in the first media root if there is one.
+fn recordings_dir() -> anyhow::Result<PathBuf> {
+    let config = config::get();
+    let root = match config.media_roots.first() {
+        Some(root) => root.clone(),
+        None => dirs::video_dir()
+            .or_else(dirs::home_dir)
+            .context("Neither a videos nor a home directory is known")?,
+    };
+    Ok(root.join(&config.recordings))
+}
+
+pub async fn start_recording() -> Result<PathBuf, HttpError> {
+    let mut recording = RECORDING.lock().unwrap();
+    if let Some(recording) = &*recording
+        && !recording.thread.is_finished()
+    {
+        return Err(HttpError::new(
+            StatusCode::CONFLICT,
+            format!("Already recording to {}", recording.path.display()),
+        ));
+    }
+
+    let path = recordings_dir()
+        .and_then(|dir| {
+            fs::create_dir_all(&dir)?;
+            let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+            Ok(dir.join(format!("Recording {timestamp}.avi")))
+        })
+        .map_err(|err| HttpError::new(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?;
+
+    info!("Recording to {}", path.display());
+    let stop = Arc::new(AtomicBool::new(false));
+    let thread = {
+        let path = path.clone();
+        let stop = stop.clone();
+        thread::spawn(move || {
+            let result = record(&path, &stop);
+            if let Err(err) = &result {
+                warn!("Recording to {} failed: {}", path.display(), err);
+            }
+            result
+        })
+    };
+
+    *recording = Some(Recording {
+        path: path.clone(),
+        stop,
+        thread,
+    });
+    Ok(path)
+}
+
+pub async fn stop_recording() -> Result<PathBuf, HttpError> {
+    let Some(recording) = RECORDING.lock().unwrap().take() else {
+        return Err(HttpError::new(StatusCode::CONFLICT, "Not recording"));
+    };
+
+    recording.stop.store(true, Ordering::Relaxed);
+    let result = tokio::task::spawn_blocking(move || recording.thread.join())
+        .await
+        .map_err(|err| HttpError::new(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?;
+
+    match result {
+        Ok(Ok(())) => {
+            info!("Saved recording to {}", recording.path.display());
+            Ok(recording.path)
+        }
+        Ok(Err(err)) => Err(HttpError::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            err.to_string(),
+        )),
+        Err(_) => Err(HttpError::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "The recording thread panicked",
+        )),
+    }
+}
+
+/// Write frames to `path` at [`RECORDING_FPS`] until `stop` is set,
+/// repeating the previous frame when the screen did not change.
+fn record(path: &Path, stop: &AtomicBool) -> anyhow::Result<()> {
+    let mut writer = None;
+    let result = write_frames(path, stop, &mut writer);
+
+    // Finish even after an error, so what was recorded so far can still be played
+    match writer {
+        Some(writer) => {
+            let finished = writer.finish();
+            result?;
+            finished?;
+            Ok(())
+        }
+        None => {
+            result?;
+            anyhow::bail!("No frames were captured")
+        }
+    }
+}
+
+/// Write frames until `stop` is set, the recording gets too big, or the screen changes size,
+/// creating the writer with the first frame.
+fn write_frames(
+    path: &Path,
+    stop: &AtomicBool,
+    writer: &mut Option<AviWriter<BufWriter<File>>>,
+) -> anyhow::Result<()> {
+    // Holding a receiver keeps the worker converting frames
+    let mut frames = CONTEXT.frame_receiver.clone();
+    frames.mark_changed();
+
+    let interval = Duration::from_secs(1) / RECORDING_FPS;
+    let mut next_frame = Instant::now();
+    let mut size = (0, 0);
+    let mut jpeg = Vec::new();
+
+    while !stop.load(Ordering::Relaxed) {
+        thread::sleep(next_frame.saturating_duration_since(Instant::now()));
+        next_frame += interval;
+
+        if frames.has_changed().context("Frame sender closed")? {
+            let Some(frame) = frames.borrow_and_update().clone() else {
+                continue;
+            };
+
+            // Every frame of an AVI has the size in its header
+            if writer.is_some() && size != (frame.width, frame.height) {
+                warn!(
+                    "The screen changed size, stopping the recording to {}",
+                    path.display()
+                );
+                return Ok(());
+            }
+
+            jpeg.clear();
+            encode_jpeg(&frame, RECORDING_JPEG_QUALITY, &mut jpeg)?;
+
+            if writer.is_none() {
+                let file = BufWriter::new(File::create(path)?);
+                size = (frame.width, frame.height);
+                *writer = Some(AviWriter::new(
+                    file,
+                    frame.width as u32,
+                    frame.height as u32,
+                    RECORDING_FPS,
+                )?);
+            }
+        }
+
+        let Some(writer) = writer else {
+            continue;
+        };
+        writer.write_frame(&jpeg)?;
+
+        if writer.size() >= RECORDING_MAX_SIZE {
+            warn!("Recording to {} is too big, stopping", path.display());
+            break;
+        }
+    }
+    Ok(())
+}
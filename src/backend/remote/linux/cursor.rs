@@ -0,0 +1,205 @@
+use std::{mem::size_of, slice, sync::Arc};
+
+use pipewire::spa::{
+    param::video::VideoFormat,
+    pod::{ChoiceValue, Object, Property, Value, serialize::PodSerializer},
+    sys as spa_sys,
+    utils::{Choice, ChoiceEnum, ChoiceFlags, Id, Rectangle},
+};
+
+use crate::{
+    backend::remote::{CursorImage, CursorState},
+    frontend::remote::{CursorShape, RelativePosition, RelativeSize},
+};
+
+/// The size of the cursor metadata for a bitmap of the given size.
+const fn meta_size(width: usize, height: usize) -> i32 {
+    (size_of::<spa_sys::spa_meta_cursor>()
+        + size_of::<spa_sys::spa_meta_bitmap>()
+        + width * height * 4) as i32
+}
+
+/// Create a serialized `ParamMeta` pod that asks PipeWire to attach cursor metadata to our buffers.
+pub fn cursor_meta_param() -> anyhow::Result<Vec<u8>> {
+    let obj = Object {
+        type_: spa_sys::SPA_TYPE_OBJECT_ParamMeta,
+        id: spa_sys::SPA_PARAM_Meta,
+        properties: vec![
+            Property::new(
+                spa_sys::SPA_PARAM_META_type,
+                Value::Id(Id(spa_sys::SPA_META_Cursor)),
+            ),
+            Property::new(
+                spa_sys::SPA_PARAM_META_size,
+                Value::Choice(ChoiceValue::Int(Choice(
+                    ChoiceFlags::empty(),
+                    ChoiceEnum::Range {
+                        default: meta_size(64, 64),
+                        min: meta_size(1, 1),
+                        max: meta_size(256, 256),
+                    },
+                ))),
+            ),
+        ],
+    };
+
+    let values = PodSerializer::serialize(std::io::Cursor::new(Vec::new()), &Value::Object(obj))
+        .map_err(|err| anyhow::anyhow!("Failed to serialize cursor meta param: {:?}", err))?
+        .0
+        .into_inner();
+    Ok(values)
+}
+
+/// Keeps track of the cursor metadata attached to screencast buffers.
+#[derive(Default)]
+pub struct CursorTracker {
+    state: CursorState,
+    serial: u64,
+}
+
+impl CursorTracker {
+    /// Read the cursor metadata of `buffer`, returning the new cursor state if it changed.
+    ///
+    /// # Safety
+    ///
+    /// `buffer` must point to a valid buffer that has been dequeued from the stream.
+    pub unsafe fn update(
+        &mut self,
+        buffer: *mut spa_sys::spa_buffer,
+        frame_size: Rectangle,
+    ) -> Option<CursorState> {
+        if frame_size.width == 0 || frame_size.height == 0 {
+            return None;
+        }
+
+        let meta = unsafe { spa_sys::spa_buffer_find_meta(buffer, spa_sys::SPA_META_Cursor) };
+        if meta.is_null() {
+            return None;
+        }
+        // Everything in the metadata has to fit in this, since it comes from PipeWire
+        let meta_size = unsafe { (*meta).size } as usize;
+        let meta = unsafe { (*meta).data }.cast::<spa_sys::spa_meta_cursor>();
+        if meta.is_null() || meta_size < size_of::<spa_sys::spa_meta_cursor>() {
+            return None;
+        }
+        let cursor = unsafe { &*meta };
+
+        let frame_width = frame_size.width as f32;
+        let frame_height = frame_size.height as f32;
+
+        let mut state = self.state.clone();
+        if cursor.id == 0 {
+            // An id of zero means the cursor is not on this stream
+            state.position = None;
+        } else {
+            state.position = Some(RelativePosition {
+                x: cursor.position.x as f32 / frame_width,
+                y: cursor.position.y as f32 / frame_height,
+            });
+        }
+
+        // The bitmap is only included when the cursor image changed
+        let bitmap_offset = cursor.bitmap_offset as usize;
+        if cursor.id != 0
+            && bitmap_offset >= size_of::<spa_sys::spa_meta_cursor>()
+            && bitmap_offset + size_of::<spa_sys::spa_meta_bitmap>() <= meta_size
+        {
+            let bitmap = unsafe {
+                &*meta
+                    .cast::<u8>()
+                    .add(bitmap_offset)
+                    .cast::<spa_sys::spa_meta_bitmap>()
+            };
+            let width = bitmap.size.width as usize;
+            let height = bitmap.size.height as usize;
+            let stride = bitmap.stride as usize;
+            let pixels_end = stride
+                .checked_mul(height)
+                .and_then(|len| len.checked_add(bitmap.offset as usize))
+                .and_then(|end| end.checked_add(bitmap_offset));
+
+            if width > 0
+                && height > 0
+                && stride >= width * 4
+                && pixels_end.is_some_and(|end| end <= meta_size)
+            {
+                let pixels = unsafe {
+                    slice::from_raw_parts(
+                        (bitmap as *const spa_sys::spa_meta_bitmap)
+                            .cast::<u8>()
+                            .add(bitmap.offset as usize),
+                        stride * height,
+                    )
+                };
+
+                let format = VideoFormat::from_raw(bitmap.format);
+                match encode_bitmap(format, width, height, stride, pixels) {
+                    Ok(png) => {
+                        self.serial += 1;
+                        state.image = Some(CursorImage {
+                            shape: CursorShape {
+                                serial: self.serial,
+                                size: RelativeSize {
+                                    width: width as f32 / frame_width,
+                                    height: height as f32 / frame_height,
+                                },
+                                hotspot: RelativePosition {
+                                    x: cursor.hotspot.x as f32 / frame_width,
+                                    y: cursor.hotspot.y as f32 / frame_height,
+                                },
+                            },
+                            png: Arc::new(png),
+                        });
+                    }
+                    Err(err) => println!("Failed to encode cursor bitmap: {}", err),
+                }
+            }
+        }
+
+        if state == self.state {
+            return None;
+        }
+        self.state = state.clone();
+        Some(state)
+    }
+}
+
+/// Convert a cursor bitmap to a PNG image.
+fn encode_bitmap(
+    format: VideoFormat,
+    width: usize,
+    height: usize,
+    stride: usize,
+    pixels: &[u8],
+) -> anyhow::Result<Vec<u8>> {
+    // Index of the red, green, blue and alpha channel of each pixel
+    let [r, g, b, a] = match format {
+        VideoFormat::RGBA => [0, 1, 2, 3],
+        VideoFormat::BGRA => [2, 1, 0, 3],
+        VideoFormat::ARGB => [1, 2, 3, 0],
+        VideoFormat::ABGR => [3, 2, 1, 0],
+        f => anyhow::bail!("Unsupported cursor format: {:?}", f),
+    };
+
+    let mut rgba = Vec::with_capacity(width * height * 4);
+    for row in pixels.chunks_exact(stride).take(height) {
+        for pixel in row[..width * 4].chunks_exact(4) {
+            rgba.extend_from_slice(&[pixel[r], pixel[g], pixel[b], pixel[a]]);
+        }
+    }
+
+    encode_png(width, height, &rgba)
+}
+
+/// Encode a cursor image with tightly packed, non-premultiplied RGBA pixels as PNG.
+pub fn encode_png(width: usize, height: usize, rgba: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(rgba)?;
+    writer.finish()?;
+
+    Ok(png)
+}
@@ -0,0 +1,163 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, LazyLock, Mutex},
+    time::Duration,
+};
+
+use dioxus::prelude::*;
+use jpeg_encoder::ColorType;
+use tokio::{
+    sync::{broadcast, watch},
+    task,
+    time::{Instant, sleep_until, timeout},
+};
+
+use super::JpegFrame;
+use crate::{backend::remote::convert::RgbFrame, frontend::remote::ScreencastSettings};
+
+pub type FrameReceiver = watch::Receiver<Option<Arc<RgbFrame>>>;
+
+/// How long an encoder waits for a new frame before checking whether it is still used.
+const RECEIVER_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Clone)]
+struct Encoder {
+    frame_tx: broadcast::Sender<JpegFrame>,
+    /// The last frame that was sent, since frames are only sent when the screen changes
+    latest: watch::Sender<Option<JpegFrame>>,
+}
+
+/// The running encoders, one for each distinct [`ScreencastSettings`].
+static ENCODERS: LazyLock<Mutex<HashMap<ScreencastSettings, Encoder>>> =
+    LazyLock::new(Default::default);
+
+/// Get the last frame encoded with the given settings, if any, and a receiver of the next ones,
+/// starting a new encoder if nobody else is using the same settings.
+pub fn subscribe(
+    settings: ScreencastSettings,
+    frames: &FrameReceiver,
+) -> (Option<JpegFrame>, broadcast::Receiver<JpegFrame>) {
+    // Adapting to the connection is done per client, so it does not affect the encoding
+    let settings = ScreencastSettings {
+        adaptive: false,
+        ..settings.normalized()
+    };
+
+    let mut encoders = ENCODERS.lock().unwrap();
+    if let Some(encoder) = encoders.get(&settings) {
+        return (
+            encoder.latest.borrow().clone(),
+            encoder.frame_tx.subscribe(),
+        );
+    }
+
+    info!("Starting encoder with {:?}", settings);
+    let (frame_tx, frame_rx) = broadcast::channel(1);
+    let encoder = Encoder {
+        frame_tx,
+        latest: watch::Sender::new(None),
+    };
+    encoders.insert(settings, encoder.clone());
+    tokio::spawn(encoder_task(settings, frames.clone(), encoder));
+
+    (None, frame_rx)
+}
+
+async fn encoder_task(settings: ScreencastSettings, mut frames: FrameReceiver, encoder: Encoder) {
+    let min_interval = settings
+        .max_fps
+        // Zero is rejected with the settings, but would panic here
+        .filter(|&max_fps| max_fps > 0)
+        .map(|max_fps| Duration::from_secs_f64(1.0 / f64::from(max_fps)));
+    let mut last_frame = Option::<Instant>::None;
+
+    loop {
+        if let (Some(min_interval), Some(last_frame)) = (min_interval, last_frame) {
+            sleep_until(last_frame + min_interval).await;
+        }
+
+        match timeout(RECEIVER_CHECK_INTERVAL, frames.changed()).await {
+            Ok(Ok(())) => {}
+            Ok(Err(_)) => {
+                warn!("Frame sender closed, stopping encoder");
+                break;
+            }
+            // The screen did not change for a while, so make sure somebody still wants our frames
+            Err(_) => {
+                if stop_if_unused(settings, &encoder.frame_tx) {
+                    break;
+                }
+                continue;
+            }
+        }
+        let Some(frame) = frames.borrow_and_update().clone() else {
+            continue;
+        };
+        last_frame = Some(Instant::now());
+
+        let encoded = match task::spawn_blocking(move || encode(&frame, settings)).await {
+            Ok(Ok(encoded)) => encoded,
+            Ok(Err(err)) => {
+                warn!("Encoding failed: {err}");
+                continue;
+            }
+            Err(err) => {
+                warn!("Encoding task failed: {err}");
+                continue;
+            }
+        };
+
+        if publish(settings, &encoder, JpegFrame(Arc::new(encoded))) {
+            break;
+        }
+    }
+}
+
+/// Send a frame to the subscribers, or remove the encoder if there are none,
+/// returning whether it was removed.
+fn publish(settings: ScreencastSettings, encoder: &Encoder, frame: JpegFrame) -> bool {
+    // Under the lock, so a new subscriber gets this frame either as the latest or from the channel
+    let mut encoders = ENCODERS.lock().unwrap();
+    if encoder.frame_tx.receiver_count() == 0 {
+        info!("Stopping encoder with {:?}", settings);
+        encoders.remove(&settings);
+        return true;
+    }
+
+    encoder.latest.send_replace(Some(frame.clone()));
+    // This can only fail if everybody unsubscribed since we checked, and we notice that next time
+    let _ = encoder.frame_tx.send(frame);
+    false
+}
+
+/// Remove the encoder with `settings` if nobody is subscribed to it, returning whether it was removed.
+fn stop_if_unused(settings: ScreencastSettings, frame_tx: &broadcast::Sender<JpegFrame>) -> bool {
+    // Check for receivers while holding the lock, so nobody can subscribe after we decide to stop
+    let mut encoders = ENCODERS.lock().unwrap();
+    if frame_tx.receiver_count() != 0 {
+        return false;
+    }
+
+    info!("Stopping encoder with {:?}", settings);
+    encoders.remove(&settings);
+    true
+}
+
+/// Scale and encode a frame according to `settings`.
+fn encode(frame: &RgbFrame, settings: ScreencastSettings) -> anyhow::Result<Vec<u8>> {
+    let frame = match settings.max_width {
+        Some(max_width) => frame.fit_width(max_width as usize),
+        None => std::borrow::Cow::Borrowed(frame),
+    };
+
+    let mut encoded = Vec::<u8>::new();
+    let enc = jpeg_encoder::Encoder::new(&mut encoded, settings.quality);
+    enc.encode(
+        &frame.data,
+        frame.width as u16,
+        frame.height as u16,
+        ColorType::Rgb,
+    )?;
+
+    Ok(encoded)
+}
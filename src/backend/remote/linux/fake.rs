@@ -0,0 +1,293 @@
+//! A remote desktop that only exists in memory, for testing everything around it.
+//!
+//! It streams a moving test pattern and records the input it gets instead of injecting it.
+//! Building with the `fake-remote` feature uses it instead of a real desktop.
+
+use std::{
+    future,
+    sync::{LazyLock, Mutex},
+    time::Duration,
+};
+
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    sync::{mpsc, watch},
+    task::JoinHandle,
+    time::{MissedTickBehavior, interval},
+};
+
+use super::backend::{RemoteBackend, StreamReceivers};
+use crate::{
+    backend::remote::{
+        CursorState,
+        convert::{PixelFormat, Plane, RawFrame},
+    },
+    frontend::remote::{Monitor, MouseButton, RelativePosition},
+};
+
+const FPS: u32 = 30;
+
+/// The monitors of the fake desktop, as id, width and height.
+const MONITORS: [(u32, usize, usize); 2] = [(1, 1280, 720), (2, 800, 600)];
+
+/// Old inputs are forgotten once this many are recorded, so they don't pile up if nobody takes them.
+const MAX_RECORDED_INPUTS: usize = 10_000;
+
+/// The colors of the bars in the test pattern.
+pub const BARS: [[u8; 3]; 8] = [
+    [255, 255, 255],
+    [255, 255, 0],
+    [0, 255, 255],
+    [0, 255, 0],
+    [255, 0, 255],
+    [255, 0, 0],
+    [0, 0, 255],
+    [0, 0, 0],
+];
+pub const SQUARE_COLOR: [u8; 3] = [255, 128, 0];
+
+/// Input that was injected into the fake desktop.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum FakeInput {
+    PointerMotion { monitor: u32, x: f64, y: f64 },
+    PointerButton { button: MouseButton, pressed: bool },
+    Scroll { pixels: f64 },
+    Key { keysym: i32, pressed: bool },
+    Clipboard { text: String },
+}
+
+static RECORDED_INPUTS: LazyLock<Mutex<Vec<FakeInput>>> = LazyLock::new(Mutex::default);
+
+fn record(input: FakeInput) {
+    let mut inputs = RECORDED_INPUTS.lock().unwrap();
+    if inputs.len() >= MAX_RECORDED_INPUTS {
+        inputs.remove(0);
+    }
+    inputs.push(input);
+}
+
+/// Take the input that was recorded since the last call.
+#[cfg(feature = "fake-remote")]
+pub fn take_recorded_inputs() -> Vec<FakeInput> {
+    std::mem::take(&mut *RECORDED_INPUTS.lock().unwrap())
+}
+
+#[derive(Default)]
+pub struct FakeBackend {
+    /// Index into [`MONITORS`] of the monitor that is streamed
+    selected: usize,
+    outputs: Option<FakeOutputs>,
+    generator: Option<JoinHandle<()>>,
+    clipboard: Mutex<String>,
+}
+
+struct FakeOutputs {
+    frame_tx: mpsc::Sender<RawFrame>,
+    cursor_tx: watch::Sender<CursorState>,
+    /// Never used, as nothing can go wrong, but the stream counts as stopped once it is dropped
+    _failure_tx: mpsc::UnboundedSender<String>,
+}
+
+impl FakeBackend {
+    /// Start generating frames for the selected monitor, stopping the previous generator.
+    fn spawn_generator(&mut self) {
+        if let Some(generator) = self.generator.take() {
+            generator.abort();
+        }
+        let Some(outputs) = &self.outputs else {
+            return;
+        };
+
+        let frame_tx = outputs.frame_tx.clone();
+        let (_, width, height) = MONITORS[self.selected];
+        self.generator = Some(tokio::spawn(async move {
+            let mut ticks = interval(Duration::from_secs(1) / FPS);
+            ticks.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+            for frame in 0.. {
+                ticks.tick().await;
+                if frame_tx
+                    .send(test_pattern(width, height, frame))
+                    .await
+                    .is_err()
+                {
+                    return;
+                }
+            }
+        }));
+    }
+}
+
+impl Drop for FakeBackend {
+    fn drop(&mut self) {
+        if let Some(generator) = &self.generator {
+            generator.abort();
+        }
+    }
+}
+
+/// Draw color bars scrolling to the right, with a square bouncing around on top of them.
+fn test_pattern(width: usize, height: usize, frame: u64) -> RawFrame {
+    let offset = (frame * 4) as usize;
+    let row: Vec<u8> = (0..width)
+        .flat_map(|x| {
+            let shifted = (x + width - offset % width) % width;
+            BARS[shifted * BARS.len() / width]
+        })
+        .collect();
+
+    let side = (height / 4).max(1);
+    let square_x = bounce(frame * 5, width.saturating_sub(side));
+    let square_y = bounce(frame * 3, height.saturating_sub(side));
+
+    let mut data = Vec::with_capacity(width * height * 3);
+    for y in 0..height {
+        let start = data.len();
+        data.extend_from_slice(&row);
+        if (square_y..square_y + side).contains(&y) {
+            let square = &mut data[start + square_x * 3..start + (square_x + side) * 3];
+            for pixel in square.chunks_exact_mut(3) {
+                pixel.copy_from_slice(&SQUARE_COLOR);
+            }
+        }
+    }
+
+    RawFrame {
+        format: PixelFormat::Rgb,
+        width,
+        height,
+        planes: vec![Plane {
+            data,
+            stride: width * 3,
+        }],
+    }
+}
+
+/// Move back and forth between 0 and `max`.
+fn bounce(distance: u64, max: usize) -> usize {
+    if max == 0 {
+        return 0;
+    }
+    let position = (distance % (2 * max as u64)) as usize;
+    if position > max {
+        2 * max - position
+    } else {
+        position
+    }
+}
+
+impl RemoteBackend for FakeBackend {
+    fn start_streaming(
+        &mut self,
+        cursor_tx: watch::Sender<CursorState>,
+    ) -> BoxFuture<'_, anyhow::Result<StreamReceivers>> {
+        Box::pin(async move {
+            if self.outputs.is_some() {
+                anyhow::bail!("Already streaming");
+            }
+
+            let (frame_tx, frames) = mpsc::channel(1);
+            let (failure_tx, failures) = mpsc::unbounded_channel();
+            self.outputs = Some(FakeOutputs {
+                frame_tx,
+                cursor_tx,
+                _failure_tx: failure_tx,
+            });
+            self.spawn_generator();
+
+            Ok(StreamReceivers { frames, failures })
+        })
+    }
+
+    fn closed(&self) -> BoxFuture<'static, ()> {
+        Box::pin(future::pending())
+    }
+
+    fn close(self: Box<Self>) -> BoxFuture<'static, anyhow::Result<()>> {
+        // Dropping stops the generator
+        Box::pin(future::ready(Ok(())))
+    }
+
+    fn monitors(&self) -> Vec<Monitor> {
+        let mut x = 0;
+        MONITORS
+            .iter()
+            .enumerate()
+            .map(|(index, &(id, width, height))| {
+                let monitor = Monitor {
+                    id,
+                    position: Some((x, 0)),
+                    size: Some((width as i32, height as i32)),
+                    selected: index == self.selected,
+                };
+                x += width as i32;
+                monitor
+            })
+            .collect()
+    }
+
+    fn select_monitor(&mut self, id: u32) -> BoxFuture<'_, anyhow::Result<()>> {
+        Box::pin(async move {
+            let Some(index) = MONITORS.iter().position(|&(other, ..)| other == id) else {
+                anyhow::bail!("There is no monitor with id {id}");
+            };
+            if index != self.selected {
+                self.selected = index;
+                self.spawn_generator();
+            }
+            Ok(())
+        })
+    }
+
+    fn monitor_size(&self) -> Option<(i32, i32)> {
+        let (_, width, height) = MONITORS[self.selected];
+        Some((width as i32, height as i32))
+    }
+
+    fn move_pointer(&self, x: f64, y: f64) -> BoxFuture<'_, anyhow::Result<()>> {
+        let (monitor, width, height) = MONITORS[self.selected];
+        record(FakeInput::PointerMotion { monitor, x, y });
+
+        // Pretend the pointer followed, so the page shows it where it was moved
+        if let Some(outputs) = &self.outputs {
+            outputs.cursor_tx.send_modify(|cursor| {
+                cursor.position = Some(RelativePosition {
+                    x: (x / width as f64) as f32,
+                    y: (y / height as f64) as f32,
+                });
+            });
+        }
+        Box::pin(future::ready(Ok(())))
+    }
+
+    fn pointer_button(
+        &self,
+        button: MouseButton,
+        pressed: bool,
+    ) -> BoxFuture<'_, anyhow::Result<()>> {
+        record(FakeInput::PointerButton { button, pressed });
+        Box::pin(future::ready(Ok(())))
+    }
+
+    fn scroll(&self, pixels: f64) -> BoxFuture<'_, anyhow::Result<()>> {
+        record(FakeInput::Scroll { pixels });
+        Box::pin(future::ready(Ok(())))
+    }
+
+    fn key(&self, keysym: i32, pressed: bool) -> BoxFuture<'_, anyhow::Result<()>> {
+        record(FakeInput::Key { keysym, pressed });
+        Box::pin(future::ready(Ok(())))
+    }
+
+    fn set_clipboard(&self, text: String) -> BoxFuture<'_, anyhow::Result<()>> {
+        record(FakeInput::Clipboard { text: text.clone() });
+        *self.clipboard.lock().unwrap() = text;
+        Box::pin(future::ready(Ok(())))
+    }
+
+    fn clipboard(&self) -> BoxFuture<'_, anyhow::Result<String>> {
+        let text = self.clipboard.lock().unwrap().clone();
+        Box::pin(future::ready(Ok(text)))
+    }
+}
@@ -0,0 +1,200 @@
+//! A screencast encoded as H.264, which needs much less bandwidth than MJPEG.
+//!
+//! Every client gets its own software encoder, so it can start with a keyframe.
+//! Each binary websocket message contains a single encoded frame: one byte that is `1` for
+//! keyframes and `0` otherwise, followed by the frame as Annex-B NAL units.
+
+use std::borrow::Cow;
+
+use dioxus::fullstack::{Message, PostcardEncoding, WebSocketOptions, Websocket};
+use dioxus::prelude::*;
+use openh264::{
+    OpenH264API,
+    encoder::{BitRate, Encoder, EncoderConfig, FrameRate, FrameType, UsageType},
+    formats::{RgbSliceU8, YUVBuffer},
+};
+use tokio::task;
+
+use super::{CONTEXT, pacer::Pacer};
+use crate::{backend::remote::convert::RgbFrame, frontend::remote::ScreencastSettings};
+
+/// The frame rate we tell the encoder about if the client did not limit it.
+const DEFAULT_FPS: u32 = 30;
+
+/// Bits per pixel per frame at a quality of 100.
+const MAX_BITS_PER_PIXEL: f64 = 0.05;
+
+pub async fn h264(
+    options: WebSocketOptions,
+    settings: ScreencastSettings,
+) -> Result<Websocket<(), (), PostcardEncoding>, HttpError> {
+    let settings = settings.normalized();
+    let mut pacer = Pacer::new(settings, CONTEXT.frame_receiver.clone());
+
+    Ok(options.on_upgrade(move |mut socket| async move {
+        let mut stream = Option::<H264Stream>::None;
+        // Whether the last frame was too small to encode, so it is only logged once
+        let mut too_small = false;
+
+        while let Some(frame) = pacer.next_frame(&mut socket).await {
+            let previous_stream = stream.take();
+            let result = task::spawn_blocking(move || {
+                let Some(frame) =
+                    even_size(frame.fit_width(settings.max_width.unwrap_or_default() as usize))
+                else {
+                    return anyhow::Ok(None);
+                };
+
+                // A new encoder is needed when the size changes, which also starts with a keyframe
+                let mut stream = match previous_stream {
+                    Some(stream) if stream.size == (frame.width, frame.height) => stream,
+                    _ => H264Stream::new(&frame, settings)?,
+                };
+                let message = stream.encode(&frame)?;
+                anyhow::Ok(Some((message, stream)))
+            })
+            .await;
+
+            let message = match result {
+                Ok(Ok(Some((message, new_stream)))) => {
+                    too_small = false;
+                    stream = Some(new_stream);
+                    message
+                }
+                Ok(Ok(None)) => {
+                    if !too_small {
+                        warn!("Skipping frames smaller than 2x2 pixels, which H.264 can't encode");
+                        too_small = true;
+                    }
+                    stream = None;
+                    continue;
+                }
+                Ok(Err(err)) => {
+                    warn!("Encoding H.264 failed: {err}");
+                    continue;
+                }
+                Err(err) => {
+                    warn!("H.264 encoding task failed: {err}");
+                    continue;
+                }
+            };
+
+            if let Some(message) = message
+                && let Err(err) = socket.send_raw(Message::Binary(message.into())).await
+            {
+                warn!("Failed to send H.264 frame: {}", err);
+                return;
+            }
+        }
+    }))
+}
+
+/// An encoder for frames of a fixed size.
+struct H264Stream {
+    encoder: Encoder,
+    size: (usize, usize),
+}
+
+impl H264Stream {
+    fn new(frame: &RgbFrame, settings: ScreencastSettings) -> anyhow::Result<Self> {
+        let fps = settings.max_fps.unwrap_or(DEFAULT_FPS);
+        let bits_per_pixel = MAX_BITS_PER_PIXEL * f64::from(settings.quality) / 100.0;
+        let bitrate = (frame.width * frame.height) as f64 * f64::from(fps) * bits_per_pixel;
+
+        info!(
+            "Starting H.264 encoder for {}x{} at {} fps and {:.0} kbit/s",
+            frame.width,
+            frame.height,
+            fps,
+            bitrate / 1000.0
+        );
+        let config = EncoderConfig::new()
+            .bitrate(BitRate::from_bps(bitrate as u32))
+            .max_frame_rate(FrameRate::from_hz(fps as f32))
+            .usage_type(UsageType::ScreenContentRealTime);
+        let encoder = Encoder::with_api_config(OpenH264API::from_source(), config)?;
+
+        Ok(Self {
+            encoder,
+            size: (frame.width, frame.height),
+        })
+    }
+
+    /// Encode a frame into a websocket message, or return `None` if the encoder skipped it.
+    fn encode(&mut self, frame: &RgbFrame) -> anyhow::Result<Option<Vec<u8>>> {
+        let yuv =
+            YUVBuffer::from_rgb_source(RgbSliceU8::new(&frame.data, (frame.width, frame.height)));
+        let bitstream = self.encoder.encode(&yuv)?;
+
+        let keyframe = match bitstream.frame_type() {
+            FrameType::Skip | FrameType::Invalid => return Ok(None),
+            FrameType::IDR | FrameType::I => true,
+            _ => false,
+        };
+
+        let mut message = vec![u8::from(keyframe)];
+        bitstream.write_vec(&mut message);
+        Ok(Some(message))
+    }
+}
+
+/// Crop a frame to an even width and height, as required for 4:2:0 chroma subsampling.
+///
+/// Returns `None` if nothing would be left.
+fn even_size(frame: Cow<'_, RgbFrame>) -> Option<Cow<'_, RgbFrame>> {
+    let width = frame.width & !1;
+    let height = frame.height & !1;
+    if width == 0 || height == 0 {
+        return None;
+    }
+    if width == frame.width && height == frame.height {
+        return Some(frame);
+    }
+
+    let data = frame
+        .data
+        .chunks_exact(frame.width * 3)
+        .take(height)
+        .flat_map(|row| &row[..width * 3])
+        .copied()
+        .collect();
+
+    Some(Cow::Owned(RgbFrame {
+        width,
+        height,
+        data,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(width: usize, height: usize) -> RgbFrame {
+        RgbFrame {
+            width,
+            height,
+            data: (0..width * height * 3).map(|byte| byte as u8).collect(),
+        }
+    }
+
+    #[test]
+    fn even_size_crops_odd_sizes() {
+        let even = frame(4, 2);
+        assert!(matches!(
+            even_size(Cow::Borrowed(&even)),
+            Some(Cow::Borrowed(_))
+        ));
+
+        let cropped = even_size(Cow::Owned(frame(3, 3))).unwrap();
+        assert_eq!((cropped.width, cropped.height), (2, 2));
+        assert_eq!(cropped.data, [0, 1, 2, 3, 4, 5, 9, 10, 11, 12, 13, 14]);
+    }
+
+    #[test]
+    fn even_size_rejects_tiny_frames() {
+        assert!(even_size(Cow::Owned(frame(1, 720))).is_none());
+        assert!(even_size(Cow::Owned(frame(1280, 1))).is_none());
+        assert!(even_size(Cow::Owned(frame(0, 0))).is_none());
+    }
+}
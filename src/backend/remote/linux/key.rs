@@ -1,55 +1,4 @@
-use std::{fmt::Display, time::Duration};
-
-use anyhow::Context;
-use ashpd::desktop::{
-    Session,
-    remote_desktop::{KeyState, RemoteDesktop},
-};
-use tokio::time;
-
-pub async fn press_key(
-    proxy: &RemoteDesktop,
-    session: &Session<'static, RemoteDesktop>,
-    key: Key,
-) -> anyhow::Result<()> {
-    press_single_key(proxy, session, key, KeyState::Pressed).await?;
-    time::sleep(Duration::from_millis(10)).await;
-    press_single_key(proxy, session, key, KeyState::Released).await?;
-    Ok(())
-}
-
-pub async fn press_key_with_modifiers(
-    proxy: &RemoteDesktop,
-    session: &Session<'static, RemoteDesktop>,
-    key: Key,
-    modifiers: &[Key],
-) -> anyhow::Result<()> {
-    for modifier in modifiers {
-        press_single_key(proxy, session, *modifier, KeyState::Pressed).await?;
-    }
-
-    press_single_key(proxy, session, key, KeyState::Pressed).await?;
-    time::sleep(Duration::from_millis(10)).await;
-    press_single_key(proxy, session, key, KeyState::Released).await?;
-
-    for modifier in modifiers.iter().rev() {
-        press_single_key(proxy, session, *modifier, KeyState::Released).await?;
-    }
-
-    Ok(())
-}
-
-async fn press_single_key(
-    proxy: &RemoteDesktop,
-    session: &Session<'static, RemoteDesktop>,
-    key: Key,
-    state: KeyState,
-) -> anyhow::Result<()> {
-    proxy
-        .notify_keyboard_keysym(session, key.symbol(), state)
-        .await
-        .with_context(|| format!("Failed to set key '{}' to state '{:?}'", key, state))
-}
+use std::fmt::Display;
 
 /// Keyboard keys.
 #[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd, Hash)]
@@ -236,6 +185,20 @@ pub enum Key {
     F33,
     F34,
     F35,
+
+    // ----------------------------------------------
+    // Multimedia (XF86) keys:
+    /// Opens the home screen of media centers like Kodi
+    HomePage,
+    AudioPlay,
+    AudioPrev,
+    AudioNext,
+    AudioRaiseVolume,
+    AudioLowerVolume,
+    AudioMute,
+    /// Goes back in browsers and file managers
+    Back,
+    Forward,
 }
 
 impl Key {
@@ -358,6 +321,16 @@ impl Key {
             Self::F33 => "F33",
             Self::F34 => "F34",
             Self::F35 => "F35",
+
+            Self::HomePage => "Home page",
+            Self::AudioPlay => "Play",
+            Self::AudioPrev => "Previous",
+            Self::AudioNext => "Next",
+            Self::AudioRaiseVolume => "Volume up",
+            Self::AudioLowerVolume => "Volume down",
+            Self::AudioMute => "Mute",
+            Self::Back => "Back",
+            Self::Forward => "Forward",
         }
     }
 
@@ -480,6 +453,16 @@ impl Key {
             Self::F33 => 0xffde,
             Self::F34 => 0xffdf,
             Self::F35 => 0xffe0,
+
+            Self::HomePage => 0x1008ff18,
+            Self::AudioPlay => 0x1008ff14,
+            Self::AudioPrev => 0x1008ff16,
+            Self::AudioNext => 0x1008ff17,
+            Self::AudioRaiseVolume => 0x1008ff13,
+            Self::AudioLowerVolume => 0x1008ff11,
+            Self::AudioMute => 0x1008ff12,
+            Self::Back => 0x1008ff26,
+            Self::Forward => 0x1008ff27,
         }
     }
 }
@@ -489,3 +472,41 @@ impl Display for Key {
         f.write_str(self.name())
     }
 }
+
+impl From<crate::frontend::remote::Key> for Key {
+    fn from(key: crate::frontend::remote::Key) -> Self {
+        use crate::frontend::remote::Key as RemoteKey;
+
+        match key {
+            RemoteKey::Backspace => Self::Backspace,
+            RemoteKey::Escape => Self::Escape,
+            RemoteKey::Space => Self::Space,
+            RemoteKey::Enter => Self::Enter,
+            RemoteKey::ArrowUp => Self::ArrowUp,
+            RemoteKey::ArrowDown => Self::ArrowDown,
+            RemoteKey::ArrowLeft => Self::ArrowLeft,
+            RemoteKey::ArrowRight => Self::ArrowRight,
+            RemoteKey::HomePage => Self::HomePage,
+            RemoteKey::AudioPlay => Self::AudioPlay,
+            RemoteKey::AudioPrev => Self::AudioPrev,
+            RemoteKey::AudioNext => Self::AudioNext,
+            RemoteKey::AudioRaiseVolume => Self::AudioRaiseVolume,
+            RemoteKey::AudioLowerVolume => Self::AudioLowerVolume,
+            RemoteKey::AudioMute => Self::AudioMute,
+            RemoteKey::Back => Self::Back,
+            RemoteKey::Forward => Self::Forward,
+        }
+    }
+}
+
+/// Get the keysym that types `character`.
+pub fn char_symbol(character: char) -> i32 {
+    match character {
+        '\n' => Key::Enter.symbol(),
+        '\t' => Key::Tab.symbol(),
+        // Latin-1 characters have their code point as keysym
+        ' '..='~' | '\u{a0}'..='\u{ff}' => character as i32,
+        // Other Unicode characters have dedicated keysyms
+        _ => 0x0100_0000 | character as i32,
+    }
+}
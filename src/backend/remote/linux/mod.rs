@@ -1,24 +1,43 @@
+mod adaptive;
+mod avi;
+mod backend;
+mod capture;
+mod cursor;
+mod encoder;
+pub mod fake;
+mod h264;
 mod key;
+mod pacer;
 mod robot;
+mod session;
+#[cfg(all(test, feature = "fake-remote"))]
+mod tests;
+mod tiles;
+mod x11;
 
+use backend::RemoteBackend;
 use dioxus::{
     fullstack::{ClientResponse, FromResponse, response::IntoResponse},
     prelude::*,
     server::Bytes,
 };
-use jpeg_encoder::ColorType;
-use pipewire::spa::param::video::VideoFormat;
-use robot::Robot;
-use std::future::Future;
+use futures::Stream;
 use std::{
     io::Write,
     pin::Pin,
-    sync::{Arc, LazyLock, Mutex},
-    task,
+    sync::{Arc, LazyLock},
     thread::{self, JoinHandle},
     time::Duration,
 };
-use tokio::{sync::broadcast, time::sleep};
+use tokio::sync::{Mutex, watch};
+
+use crate::{
+    backend::remote::CursorState,
+    frontend::remote::{ClipboardRequest, Interaction, Monitor, ScreencastSettings, SessionStatus},
+};
+
+/// How long keys are held down when typing.
+const KEY_PRESS_DURATION: Duration = Duration::from_millis(10);
 
 #[derive(Clone)]
 struct JpegFrame(Arc<Vec<u8>>);
@@ -41,111 +60,62 @@ impl From<JpegFrame> for Bytes {
 
 struct Context {
     _thread: JoinHandle<()>,
-    _robot: Arc<Mutex<Option<Robot>>>,
-    super_receiver: broadcast::Receiver<JpegFrame>,
+    backend: Arc<Mutex<Option<Box<dyn RemoteBackend>>>>,
+    frame_receiver: encoder::FrameReceiver,
+    cursor_receiver: watch::Receiver<CursorState>,
+    status_receiver: watch::Receiver<SessionStatus>,
 }
 
-fn worker_thread(frame_tx: broadcast::Sender<JpegFrame>, robot: Arc<Mutex<Option<Robot>>>) {
-    let rt = tokio::runtime::Builder::new_current_thread()
+fn worker_thread(outputs: session::Outputs) {
+    let rt = match tokio::runtime::Builder::new_current_thread()
         .enable_all()
         .build()
-        .unwrap();
-    rt.block_on(async {
-        let mut rob = robot::Robot::new().await.unwrap();
-        let rx = rob.start_streaming().await.unwrap();
-        *robot.lock().unwrap() = Some(rob);
-
-        for frame in rx {
-            if frame_tx.receiver_count() == 1 {
-                // We don't have anyone waiting for the frame,
-                // so we sleep a second to apply backpressure
-                // then don't encode it
-                sleep(Duration::from_secs(1)).await;
-                continue;
-            }
-
-            let color_type = match frame.format.format() {
-                VideoFormat::BGRx => ColorType::Bgra,
-                VideoFormat::BGRA => ColorType::Bgra,
-                VideoFormat::RGBx => ColorType::Rgba,
-                VideoFormat::RGBA => ColorType::Rgba,
-                VideoFormat::RGB => ColorType::Rgb,
-                VideoFormat::BGR => ColorType::Bgr,
-                f => {
-                    warn!("Unknown pixel format: {:?}", f);
-                    continue;
-                }
-            };
-
-            let mut encoded = Vec::<u8>::new();
-            let enc = jpeg_encoder::Encoder::new(&mut encoded, 70);
-            let size = frame.format.size();
-            if let Err(err) = enc.encode(
-                &frame.buffer,
-                size.width as u16,
-                size.height as u16,
-                color_type,
-            ) {
-                warn!("Encoding failed: {err}");
-                continue;
-            };
-
-            let frame = Arc::new(encoded);
-            if let Err(err) = frame_tx.send(JpegFrame(frame)) {
-                info!("Worker thread exiting: {err}");
-                break;
-            }
+    {
+        Ok(rt) => rt,
+        Err(err) => {
+            error!("Failed to start the remote desktop runtime: {}", err);
+            return;
         }
-    });
+    };
+    rt.block_on(session::run(outputs));
 }
 
 impl Context {
     pub fn new() -> Self {
-        let robot = Arc::new(Mutex::<Option<Robot>>::new(None));
-        let (tx, rx) = broadcast::channel::<JpegFrame>(1);
+        let backend = Arc::new(Mutex::new(None));
+        let (tx, rx) = watch::channel(None);
+        let (cursor_tx, cursor_rx) = watch::channel(CursorState::default());
+        let (status_tx, status_rx) = watch::channel(SessionStatus::Idle);
 
         let thread = {
-            let robot = robot.clone();
-            thread::spawn(move || {
-                worker_thread(tx, robot);
-            })
+            let outputs = session::Outputs {
+                frame_tx: tx,
+                cursor_tx,
+                status_tx,
+                backend: backend.clone(),
+            };
+            thread::spawn(move || worker_thread(outputs))
         };
 
         Self {
-            _robot: robot,
+            backend,
             _thread: thread,
-            super_receiver: rx,
+            frame_receiver: rx,
+            cursor_receiver: cursor_rx,
+            status_receiver: status_rx,
         }
     }
 }
 
 static CONTEXT: LazyLock<Context> = LazyLock::new(Context::new);
 
-struct JpegStream(broadcast::Receiver<JpegFrame>);
-
-impl futures::Stream for JpegStream {
-    type Item = anyhow::Result<JpegFrame>;
-
-    fn poll_next(
-        mut self: Pin<&mut Self>,
-        cx: &mut task::Context<'_>,
-    ) -> task::Poll<Option<Self::Item>> {
-        match std::pin::pin!(self.0.recv()).poll(cx) {
-            task::Poll::Ready(Ok(x)) => task::Poll::Ready(Some(Ok(x))),
-            task::Poll::Ready(Err(_)) => task::Poll::Ready(None),
-            task::Poll::Pending => task::Poll::Pending,
-        }
-    }
-}
-
-pub struct ScreencastResponse(broadcast::Receiver<JpegFrame>);
+pub struct ScreencastResponse(Pin<Box<dyn Stream<Item = anyhow::Result<JpegFrame>> + Send>>);
 
 impl IntoResponse for ScreencastResponse {
     fn into_response(self) -> axum::response::Response {
         use axum::body::Body;
 
-        let stream = JpegStream(self.0);
-        let mut res = axum::response::Response::new(Body::from_stream(stream));
+        let mut res = axum::response::Response::new(Body::from_stream(self.0));
 
         let content_type = "multipart/x-mixed-replace;boundary=EMPC_FRAME_BOUNDARY";
         res.headers_mut().insert(
@@ -165,7 +135,130 @@ impl FromResponse for ScreencastResponse {
     }
 }
 
-pub async fn screencast() -> Result<ScreencastResponse, HttpError> {
-    let rx = CONTEXT.super_receiver.resubscribe();
-    Ok(ScreencastResponse(rx))
+pub use capture::{screenshot, start_recording, stop_recording};
+pub use h264::h264;
+pub use tiles::tiles;
+
+pub async fn screencast(settings: ScreencastSettings) -> Result<ScreencastResponse, HttpError> {
+    let stream = adaptive::frame_stream(settings, CONTEXT.frame_receiver.clone());
+    Ok(ScreencastResponse(Box::pin(stream)))
+}
+
+pub fn cursor() -> watch::Receiver<CursorState> {
+    CONTEXT.cursor_receiver.clone()
+}
+
+pub fn status() -> watch::Receiver<SessionStatus> {
+    CONTEXT.status_receiver.clone()
+}
+
+pub async fn monitors() -> Result<Vec<Monitor>, HttpError> {
+    let backend = CONTEXT.backend.lock().await;
+    let Some(backend) = backend.as_ref() else {
+        return Err(HttpError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "The remote desktop session has not started yet",
+        ));
+    };
+
+    Ok(backend.monitors())
+}
+
+pub async fn select_monitor(id: u32) -> Result<(), HttpError> {
+    let mut backend = CONTEXT.backend.lock().await;
+    let Some(backend) = backend.as_mut() else {
+        return Err(HttpError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "The remote desktop session has not started yet",
+        ));
+    };
+
+    backend
+        .select_monitor(id)
+        .await
+        .map_err(|err| HttpError::new(StatusCode::BAD_REQUEST, err.to_string()))
+}
+
+/// Inject an interaction from the remote page into the session.
+pub async fn interact(interaction: Interaction) -> anyhow::Result<()> {
+    let backend = CONTEXT.backend.lock().await;
+    let Some(backend) = backend.as_ref() else {
+        anyhow::bail!("The remote desktop session has not started yet");
+    };
+
+    match interaction {
+        Interaction::Position(position) => {
+            let Some((width, height)) = backend.monitor_size() else {
+                anyhow::bail!("The size of the remote screen is unknown");
+            };
+            backend
+                .move_pointer(
+                    f64::from(position.x) * f64::from(width),
+                    f64::from(position.y) * f64::from(height),
+                )
+                .await
+        }
+        Interaction::MouseDown(button) => backend.pointer_button(button, true).await,
+        Interaction::MouseUp(button) => backend.pointer_button(button, false).await,
+        Interaction::Scroll(pixels) => backend.scroll(f64::from(pixels)).await,
+        Interaction::Text(text) => {
+            for character in text.chars() {
+                tap_key(backend.as_ref(), key::char_symbol(character)).await?;
+            }
+            Ok(())
+        }
+        Interaction::Key(key) => tap_key(backend.as_ref(), key::Key::from(key).symbol()).await,
+        Interaction::Clipboard(ClipboardRequest::Set { text, paste }) => {
+            backend.set_clipboard(text).await?;
+            if paste {
+                let control = key::Key::LeftControl.symbol();
+                backend.key(control, true).await?;
+                let result = tap_key(backend.as_ref(), key::char_symbol('v')).await;
+                backend.key(control, false).await?;
+                result?;
+            }
+            Ok(())
+        }
+        Interaction::Clipboard(ClipboardRequest::Get) => {
+            anyhow::bail!("Reading the clipboard is answered by the interaction socket")
+        }
+    }
+}
+
+/// Read the text on the clipboard of the desktop.
+pub async fn clipboard() -> anyhow::Result<String> {
+    let backend = CONTEXT.backend.lock().await;
+    let Some(backend) = backend.as_ref() else {
+        anyhow::bail!("The remote desktop session has not started yet");
+    };
+
+    backend.clipboard().await
+}
+
+/// Press and release a key.
+async fn tap_key(backend: &dyn RemoteBackend, keysym: i32) -> anyhow::Result<()> {
+    backend.key(keysym, true).await?;
+    tokio::time::sleep(KEY_PRESS_DURATION).await;
+    backend.key(keysym, false).await
+}
+
+pub async fn request_permission() -> Result<(), HttpError> {
+    if *CONTEXT.status_receiver.borrow() != SessionStatus::PermissionRequired {
+        return Err(HttpError::new(
+            StatusCode::CONFLICT,
+            "The remote desktop session is not waiting for permission",
+        ));
+    }
+
+    session::request_permission();
+    Ok(())
+}
+
+pub async fn revoke_permission() -> Result<(), HttpError> {
+    robot::revoke_restore_token()
+        .await
+        .map_err(|err| HttpError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("{err:#}")))?;
+
+    session::restart();
+    Ok(())
 }
@@ -0,0 +1,62 @@
+use std::{sync::Arc, time::Duration};
+
+use dioxus::fullstack::{PostcardEncoding, TypedWebsocket};
+use dioxus::prelude::*;
+use tokio::time::{Instant, sleep_until};
+
+use super::encoder::FrameReceiver;
+use crate::{backend::remote::convert::RgbFrame, frontend::remote::ScreencastSettings};
+
+/// A websocket that only sends data to the client.
+pub type SendOnlySocket = TypedWebsocket<(), (), PostcardEncoding>;
+
+/// Hands out new frames to a websocket transport, at most `max_fps` times per second.
+pub struct Pacer {
+    frames: FrameReceiver,
+    min_interval: Option<Duration>,
+    last_frame: Option<Instant>,
+}
+
+impl Pacer {
+    pub fn new(settings: ScreencastSettings, frames: FrameReceiver) -> Self {
+        Self {
+            frames,
+            min_interval: settings
+                .max_fps
+                // Zero is rejected with the settings, but would panic here
+                .filter(|&max_fps| max_fps > 0)
+                .map(|max_fps| Duration::from_secs_f64(1.0 / f64::from(max_fps))),
+            last_frame: None,
+        }
+    }
+
+    /// Wait for the next frame, returning `None` once the socket or the frame sender is closed.
+    pub async fn next_frame(&mut self, socket: &mut SendOnlySocket) -> Option<Arc<RgbFrame>> {
+        loop {
+            if let (Some(min_interval), Some(last_frame)) = (self.min_interval, self.last_frame) {
+                sleep_until(last_frame + min_interval).await;
+            }
+
+            tokio::select! {
+                changed = self.frames.changed() => {
+                    if changed.is_err() {
+                        warn!("Frame sender closed, stopping websocket screencast");
+                        return None;
+                    }
+                }
+                // The client never sends anything, so this only returns once the socket is closed
+                message = socket.recv_raw() => {
+                    if message.is_err() {
+                        return None;
+                    }
+                    continue;
+                }
+            }
+
+            if let Some(frame) = self.frames.borrow_and_update().clone() {
+                self.last_frame = Some(Instant::now());
+                return Some(frame);
+            }
+        }
+    }
+}
@@ -1,58 +1,236 @@
 #![expect(dead_code)]
 
 use std::{
+    cell::RefCell,
     io,
     os::fd::OwnedFd,
-    sync::mpsc,
+    path::PathBuf,
+    pin::Pin,
+    rc::Rc,
+    slice,
+    sync::{Arc, Mutex},
     thread::{self, JoinHandle},
-    time::Duration,
 };
 
 use anyhow::Context;
 use ashpd::desktop::{
     PersistMode, Session,
+    clipboard::Clipboard,
     remote_desktop::{DeviceType, KeyState, RemoteDesktop, SelectedDevices},
     screencast::{CursorMode, Screencast, SourceType, Stream},
 };
 
 use pipewire as pw;
 use pw::{properties::properties, spa};
-use spa::param::video::VideoInfoRaw;
+use spa::param::video::{VideoFormat, VideoInfoRaw};
 
+use futures::{StreamExt, future::BoxFuture};
 use tokio::fs;
-use tokio::io::AsyncWriteExt;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinHandle as TaskHandle;
+
+use super::backend::{RemoteBackend, StreamReceivers};
+use super::cursor::{CursorTracker, cursor_meta_param};
+use crate::{
+    backend::remote::{
+        CursorState,
+        convert::{PixelFormat, Plane, RawFrame},
+    },
+    frontend::remote::{Monitor, MouseButton},
+};
 
-use super::key::{Key, press_key, press_key_with_modifiers};
+/// Linux input event codes of the mouse buttons, as the portal expects them.
+const BTN_LEFT: i32 = 0x110;
+const BTN_RIGHT: i32 = 0x111;
+const BTN_MIDDLE: i32 = 0x112;
 
+/// Remote control through the remote desktop and screencast portals,
+/// streaming the screen with PipeWire.
 pub struct Robot {
     session: Session<'static, RemoteDesktop>,
     remote: RemoteDesktop,
     screencast: Screencast,
     response: SelectedDevices,
-    stream: Stream,
-    streaming_thread: Option<JoinHandle<()>>,
+    /// One stream per shared monitor
+    streams: Vec<Stream>,
+    /// Index into `streams` of the monitor that is streamed and controlled
+    selected: usize,
+    outputs: Option<StreamOutputs>,
+    streaming_thread: Option<StreamingThread>,
+    /// Yields once the portal closes the session
+    closed: Mutex<Option<Pin<Box<dyn futures::Stream<Item = ()> + Send>>>>,
+    /// `None` if the portal does not support sharing the clipboard
+    clipboard: Option<Clipboard>,
+    /// The text we put on the clipboard, which is sent to applications asking for it
+    clipboard_text: Arc<Mutex<Option<String>>>,
+    /// Answers requests for `clipboard_text`
+    clipboard_task: Option<TaskHandle<()>>,
+}
+
+impl Drop for Robot {
+    fn drop(&mut self) {
+        if let Some(task) = &self.clipboard_task {
+            task.abort();
+        }
+    }
+}
+
+/// The MIME types we offer and ask for when sharing the clipboard.
+const TEXT_MIME_TYPES: [&str; 2] = ["text/plain;charset=utf-8", "text/plain"];
+
+/// Where the streaming thread sends what it captures.
+#[derive(Clone)]
+struct StreamOutputs {
+    frame_tx: mpsc::Sender<RawFrame>,
+    cursor_tx: watch::Sender<CursorState>,
+    /// Gets the reason the stream stopped, unless it was stopped on purpose
+    failure_tx: mpsc::UnboundedSender<String>,
+}
+
+struct StreamingThread {
+    _handle: JoinHandle<()>,
+    /// Stops the main loop of the thread
+    quit: pw::channel::Sender<()>,
+}
+
+impl Drop for StreamingThread {
+    fn drop(&mut self) {
+        // This only fails if the thread already stopped
+        let _ = self.quit.send(());
+    }
 }
 
-pub struct Frame {
-    pub format: VideoInfoRaw,
-    pub buffer: Vec<u8>,
-    pub stride: u32,
+/// Get the [`PixelFormat`] corresponding to a PipeWire [`VideoFormat`].
+fn pixel_format(format: VideoFormat) -> Option<PixelFormat> {
+    Some(match format {
+        VideoFormat::RGB => PixelFormat::Rgb,
+        VideoFormat::BGR => PixelFormat::Bgr,
+        VideoFormat::RGBx => PixelFormat::Rgbx,
+        VideoFormat::BGRx => PixelFormat::Bgrx,
+        VideoFormat::xRGB => PixelFormat::Xrgb,
+        VideoFormat::xBGR => PixelFormat::Xbgr,
+        VideoFormat::RGBA => PixelFormat::Rgba,
+        VideoFormat::BGRA => PixelFormat::Bgra,
+        VideoFormat::ARGB => PixelFormat::Argb,
+        VideoFormat::ABGR => PixelFormat::Abgr,
+        VideoFormat::YUY2 => PixelFormat::Yuy2,
+        VideoFormat::UYVY => PixelFormat::Uyvy,
+        VideoFormat::I420 => PixelFormat::I420,
+        VideoFormat::YV12 => PixelFormat::Yv12,
+        VideoFormat::NV12 => PixelFormat::Nv12,
+        _ => return None,
+    })
+}
+
+/// Copy the data planes of `buffer` into a [`RawFrame`].
+///
+/// Returns `None` if the buffer contains no image data,
+/// which happens when only the cursor metadata was updated.
+///
+/// # Safety
+///
+/// `buffer` must point to a valid buffer that has been dequeued from the stream.
+unsafe fn read_frame(buffer: *mut spa::sys::spa_buffer, format: VideoInfoRaw) -> Option<RawFrame> {
+    let buffer = unsafe { &*buffer };
+    if buffer.n_datas == 0 || buffer.datas.is_null() {
+        return None;
+    }
+    let datas = unsafe {
+        slice::from_raw_parts_mut(
+            buffer.datas.cast::<spa::buffer::Data>(),
+            buffer.n_datas as usize,
+        )
+    };
+
+    let Some(pixel_format) = pixel_format(format.format()) else {
+        println!("Unknown pixel format: {:?}", format.format());
+        return None;
+    };
+
+    let mut planes = Vec::with_capacity(datas.len());
+    for data in datas {
+        let chunk = data.chunk();
+        let (offset, size, stride) = (
+            chunk.offset() as usize,
+            chunk.size() as usize,
+            chunk.stride() as usize,
+        );
+        if size == 0 {
+            continue;
+        }
+
+        let slice = data.data()?;
+        let Some(slice) = slice.get(offset..offset + size) else {
+            println!("Chunk is outside of the buffer");
+            return None;
+        };
+        planes.push(Plane {
+            data: Vec::from(slice),
+            stride,
+        });
+    }
+
+    if planes.is_empty() {
+        return None;
+    }
+
+    Some(RawFrame {
+        format: pixel_format,
+        width: format.size().width as usize,
+        height: format.size().height as usize,
+        planes,
+    })
 }
 
 fn streaming_thread(
     fd: OwnedFd,
     stream: Stream,
-    tx: mpsc::SyncSender<Frame>,
+    outputs: StreamOutputs,
+    quit: pw::channel::Receiver<()>,
 ) -> anyhow::Result<()> {
     let node_id = stream.pipe_wire_node_id();
+    let StreamOutputs {
+        frame_tx: tx,
+        cursor_tx,
+        ..
+    } = outputs;
 
     pw::init();
-    let mainloop = pw::main_loop::MainLoopBox::new(None).context("Failed to create main loop")?;
+    let mainloop = pw::main_loop::MainLoopRc::new(None).context("Failed to create main loop")?;
+    let _quit = quit.attach(mainloop.loop_(), {
+        let mainloop = mainloop.clone();
+        move |()| mainloop.quit()
+    });
+
+    // Stops the main loop because something went wrong, remembering the first reason
+    let failure = Rc::new(RefCell::new(Option::<String>::None));
+    let fail = {
+        let mainloop = mainloop.clone();
+        let failure = failure.clone();
+        move |reason: String| {
+            failure.borrow_mut().get_or_insert(reason);
+            mainloop.quit();
+        }
+    };
+
     let context =
         pw::context::ContextBox::new(mainloop.loop_(), None).context("Failed to create context")?;
     let core = context
         .connect_fd(fd, None)
         .context("Failed to connect file descriptor")?;
+    let _core_listener = core
+        .add_listener_local()
+        .error({
+            let fail = fail.clone();
+            move |id, _seq, res, message| {
+                // Errors on the core object itself mean the connection to PipeWire is gone
+                if id == pw::core::PW_ID_CORE {
+                    fail(format!("PipeWire connection failed ({res}): {message}"));
+                }
+            }
+        })
+        .register();
 
     let stream = pw::stream::StreamBox::new(
         &core,
@@ -68,10 +246,18 @@ fn streaming_thread(
     let format_data: spa::param::video::VideoInfoRaw = Default::default();
     let _listener = stream
         .add_local_listener_with_user_data(format_data)
-        .state_changed(|_, _, old, new| {
-            println!("State changed: {:?} -> {:?}", old, new);
+        .state_changed({
+            let fail = fail.clone();
+            move |_, _, old, new| {
+                println!("State changed: {:?} -> {:?}", old, new);
+                match new {
+                    pw::stream::StreamState::Error(err) => fail(format!("Stream failed: {err}")),
+                    pw::stream::StreamState::Unconnected => fail("Stream disconnected".to_owned()),
+                    _ => {}
+                }
+            }
         })
-        .param_changed(|_, format_data, id, param| {
+        .param_changed(|stream, format_data, id, param| {
             let Some(param) = param else {
                 return;
             };
@@ -91,7 +277,10 @@ fn streaming_thread(
                 return;
             }
 
-            format_data.parse(param).expect("Failed to parse param");
+            if let Err(err) = format_data.parse(param) {
+                println!("Failed to parse video format: {:?}", err);
+                return;
+            }
 
             println!("got video format:");
             println!(
@@ -110,23 +299,50 @@ fn streaming_thread(
                 format_data.framerate().denom
             );
 
-            // prepare to render video of this size
+            // Now that the format is known, ask for the cursor to be sent as metadata
+            let meta_param = match cursor_meta_param() {
+                Ok(meta_param) => meta_param,
+                Err(err) => {
+                    println!("{}", err);
+                    return;
+                }
+            };
+            let Some(meta_pod) = spa::pod::Pod::from_bytes(&meta_param) else {
+                println!("Failed to create cursor meta pod");
+                return;
+            };
+            if let Err(err) = stream.update_params(&mut [meta_pod]) {
+                println!("Failed to request cursor metadata: {}", err);
+            }
         })
-        .process(move |stream, format_data| match stream.dequeue_buffer() {
-            None => println!("out of buffers"),
-            Some(mut buffer) => {
-                let datas = buffer.datas_mut();
-                let data = &mut datas[0];
-                let Some(slice) = data.data() else {
+        .process({
+            let mut cursor = CursorTracker::default();
+            move |stream, format_data| {
+                let buffer = unsafe { stream.dequeue_raw_buffer() };
+                if buffer.is_null() {
+                    println!("out of buffers");
                     return;
+                }
+
+                // SAFETY: The buffer was just dequeued, and is only queued again after we are done reading it
+                let spa_buffer = unsafe { (*buffer).buffer };
+                let (cursor_state, frame) = unsafe {
+                    (
+                        cursor.update(spa_buffer, format_data.size()),
+                        read_frame(spa_buffer, *format_data),
+                    )
                 };
+                unsafe { stream.queue_raw_buffer(buffer) };
 
-                tx.send(Frame {
-                    format: *format_data,
-                    buffer: Vec::from(slice),
-                    stride: data.chunk().stride() as u32,
-                })
-                .expect("TODO: implement proper stream teardown");
+                if let Some(cursor_state) = cursor_state {
+                    cursor_tx.send_replace(cursor_state);
+                }
+                // Blocking here applies backpressure until the frame is taken
+                if let Some(frame) = frame
+                    && tx.blocking_send(frame).is_err()
+                {
+                    println!("Frame receiver closed");
+                }
             }
         })
         .register()
@@ -154,11 +370,20 @@ fn streaming_thread(
             Id,
             pw::spa::param::video::VideoFormat::RGB,
             pw::spa::param::video::VideoFormat::RGB,
+            pw::spa::param::video::VideoFormat::BGR,
             pw::spa::param::video::VideoFormat::RGBA,
+            pw::spa::param::video::VideoFormat::BGRA,
             pw::spa::param::video::VideoFormat::RGBx,
             pw::spa::param::video::VideoFormat::BGRx,
+            pw::spa::param::video::VideoFormat::xRGB,
+            pw::spa::param::video::VideoFormat::xBGR,
+            pw::spa::param::video::VideoFormat::ARGB,
+            pw::spa::param::video::VideoFormat::ABGR,
             pw::spa::param::video::VideoFormat::YUY2,
+            pw::spa::param::video::VideoFormat::UYVY,
             pw::spa::param::video::VideoFormat::I420,
+            pw::spa::param::video::VideoFormat::YV12,
+            pw::spa::param::video::VideoFormat::NV12,
         ),
         pw::spa::pod::property!(
             pw::spa::param::format::FormatProperties::VideoSize,
@@ -195,11 +420,11 @@ fn streaming_thread(
         std::io::Cursor::new(Vec::new()),
         &pw::spa::pod::Value::Object(obj),
     )
-    .unwrap()
+    .map_err(|err| anyhow::anyhow!("Failed to serialize format param: {:?}", err))?
     .0
     .into_inner();
 
-    let mut params = [spa::pod::Pod::from_bytes(&values).unwrap()];
+    let mut params = [spa::pod::Pod::from_bytes(&values).context("Failed to create format pod")?];
 
     stream
         .connect(
@@ -213,57 +438,162 @@ fn streaming_thread(
     println!("Connected stream");
 
     mainloop.run();
-    Ok(())
+
+    match failure.take() {
+        Some(reason) => Err(anyhow::anyhow!(reason)),
+        None => Ok(()),
+    }
 }
 
-impl Robot {
-    pub async fn new() -> anyhow::Result<Self> {
-        let state_dir = dirs::state_dir().unwrap().join("wlrobot");
-        fs::create_dir_all(&state_dir)
-            .await
-            .with_context(|| format!("Failed to create state directory {:?}", state_dir))?;
-        let tok_file = state_dir.join("token");
+/// Where the restore token of the portal session is stored.
+fn restore_token_path() -> anyhow::Result<PathBuf> {
+    Ok(dirs::state_dir()
+        .context("Failed to find the state directory")?
+        .join("wlrobot")
+        .join("token"))
+}
 
-        let restore_token = match fs::read_to_string(&tok_file).await {
-            Ok(tok) => Some(tok),
-            Err(err) if err.kind() == io::ErrorKind::NotFound => None,
-            Err(err) => return Err(err.into()),
-        };
+async fn read_restore_token() -> anyhow::Result<Option<String>> {
+    let path = restore_token_path()?;
+    match fs::read_to_string(&path).await {
+        Ok(token) => Ok(Some(token)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err).with_context(|| format!("Failed to read restore token {path:?}")),
+    }
+}
 
-        let rd_proxy_1 = RemoteDesktop::new()
-            .await
-            .context("Failed to create RemoteDesktop 1")?;
-        let rd_session_1 = rd_proxy_1
-            .create_session()
-            .await
-            .context("Failed to create remote desktop session 1")?;
-        rd_proxy_1
-            .select_devices(
-                &rd_session_1,
-                DeviceType::Keyboard | DeviceType::Pointer,
-                restore_token.as_deref(),
-                PersistMode::ExplicitlyRevoked,
-            )
+async fn write_restore_token(token: &str) -> anyhow::Result<()> {
+    let path = restore_token_path()?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
             .await
-            .context("Failed to select input devices to remote control session 1")?;
+            .with_context(|| format!("Failed to create state directory {dir:?}"))?;
+    }
+    let mut file = fs::File::create(&path)
+        .await
+        .with_context(|| format!("Failed to create restore token file {path:?}"))?;
+    file.write_all(token.as_bytes())
+        .await
+        .with_context(|| format!("Failed to write restore token file {path:?}"))
+}
 
-        let response_1 = rd_proxy_1
-            .start(&rd_session_1, None)
-            .await
-            .context("Failed to start remote desktop session 1")?
-            .response()
-            .context("Failed to get remote desktop session response 1")?;
+/// Whether the user allowed us to control the desktop before, so it can be done without asking again.
+pub async fn has_restore_token() -> bool {
+    matches!(read_restore_token().await, Ok(Some(_)))
+}
 
-        if restore_token.as_deref() != response_1.restore_token()
-            && let Some(tok) = response_1.restore_token()
-        {
-            let mut f = fs::File::create(&tok_file)
-                .await
-                .with_context(|| format!("Failed to create restore token file {:?}", tok_file))?;
-            f.write(tok.as_bytes())
+#[zbus::proxy(
+    interface = "org.freedesktop.impl.portal.PermissionStore",
+    default_service = "org.freedesktop.impl.portal.PermissionStore",
+    default_path = "/org/freedesktop/impl/portal/PermissionStore"
+)]
+trait PermissionStore {
+    fn delete(&self, table: &str, id: &str) -> zbus::Result<()>;
+}
+
+/// Delete the grant the portal keeps for a restore token.
+///
+/// xdg-desktop-portal stores what was shared in its permission store, keyed by the token,
+/// so removing only our copy of the token would leave the grant behind.
+async fn delete_portal_grant(token: &str) -> anyhow::Result<()> {
+    let connection = zbus::Connection::session()
+        .await
+        .context("Failed to connect to the session bus")?;
+    let proxy = PermissionStoreProxy::new(&connection).await?;
+    match proxy.delete("remote-desktop", token).await {
+        Ok(()) => Ok(()),
+        // The portal already forgot it, e.g. because it expired
+        Err(zbus::Error::MethodError(name, ..)) if name.as_str().ends_with(".NotFound") => Ok(()),
+        Err(err) => Err(err).context("Failed to delete the grant from the permission store"),
+    }
+}
+
+/// Forget the permission the user gave us, so the next session has to ask for it again.
+///
+/// If the portal's grant can't be deleted, it stays in the permission store, but it can't be
+/// used without the token, and the portal drops it once it expires.
+pub async fn revoke_restore_token() -> anyhow::Result<()> {
+    if let Ok(Some(token)) = read_restore_token().await
+        && let Err(err) = delete_portal_grant(&token).await
+    {
+        println!("{err:#}");
+    }
+
+    let path = restore_token_path()?;
+    match fs::remove_file(&path).await {
+        Ok(()) => {
+            println!("Removed restore token {path:?}");
+            Ok(())
+        }
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(err).with_context(|| format!("Failed to remove restore token {path:?}")),
+    }
+}
+
+async fn request_clipboard(session: &Session<'static, RemoteDesktop>) -> anyhow::Result<Clipboard> {
+    let clipboard = Clipboard::new()
+        .await
+        .context("Failed to create Clipboard")?;
+    clipboard
+        .request(session)
+        .await
+        .context("Failed to request the clipboard")?;
+    Ok(clipboard)
+}
+
+/// Send the text we put on the clipboard to every application that pastes it.
+async fn serve_clipboard(text: Arc<Mutex<Option<String>>>) {
+    let result = async {
+        let clipboard = Clipboard::new()
+            .await
+            .context("Failed to create Clipboard")?;
+        let mut transfers = clipboard
+            .receive_selection_transfer()
+            .await
+            .context("Failed to listen for clipboard transfers")?;
+
+        while let Some((session, mime_type, serial)) = transfers.next().await {
+            let text = text.lock().unwrap().clone();
+            let success = match text {
+                Some(text) => {
+                    let result = async {
+                        let fd = clipboard.selection_write(&session, serial).await?;
+                        let mut file = fs::File::from_std(std::fs::File::from(fd));
+                        file.write_all(text.as_bytes()).await?;
+                        file.flush().await?;
+                        anyhow::Ok(())
+                    }
+                    .await;
+                    if let Err(err) = &result {
+                        println!("Failed to send clipboard as {mime_type}: {:#}", err);
+                    }
+                    result.is_ok()
+                }
+                None => false,
+            };
+            clipboard
+                .selection_write_done(&session, serial, success)
                 .await
-                .with_context(|| format!("Failed to write {:?} to tok file {:?}", tok, tok_file))?;
+                .context("Failed to finish clipboard transfer")?;
         }
+        anyhow::Ok(())
+    }
+    .await;
+
+    if let Err(err) = result {
+        println!("Stopped sharing the clipboard: {:#}", err);
+    }
+}
+
+impl Robot {
+    /// Start a remote desktop session sharing all monitors.
+    ///
+    /// Without a restore token, or if the token is no longer valid, this shows the portal's
+    /// permission dialog on the media PC and waits until somebody answers it there.
+    /// The restore token covers both the input devices and the shared monitors,
+    /// and is replaced with the new one the portal hands out every time.
+    pub async fn new() -> anyhow::Result<Self> {
+        let restore_token = read_restore_token().await?;
 
         let rd_proxy = RemoteDesktop::new()
             .await
@@ -276,145 +606,295 @@ impl Robot {
             .select_devices(
                 &session,
                 DeviceType::Keyboard | DeviceType::Pointer,
-                None,
-                PersistMode::DoNot,
+                restore_token.as_deref(),
+                PersistMode::ExplicitlyRevoked,
             )
             .await
             .context("Failed to select input devices to remote control session")?;
 
+        // The clipboard has to be requested before the session starts
+        let clipboard = match request_clipboard(&session).await {
+            Ok(clipboard) => Some(clipboard),
+            Err(err) => {
+                println!("Clipboard sharing is not available: {:#}", err);
+                None
+            }
+        };
+
         let sc_proxy = Screencast::new()
             .await
             .context("Failed to create Screencast")?;
         sc_proxy
             .select_sources(
                 &session,
-                CursorMode::Embedded,
+                CursorMode::Metadata,
                 SourceType::Monitor.into(),
-                false, // multiple
-                None,  // restore_token
+                true, // multiple
+                None, // restore_token, which is handled by the remote desktop session
                 PersistMode::DoNot,
             )
             .await
             .context("Failed to configure screen cast session")?;
 
-        // This is a hack to work around the fact that you're not supposed to
-        // be able to persist the screencast permission...
-        // Nobody tell GNOME, this should probably be considered an exploit
-        tokio::spawn({
-            let proxy = rd_proxy_1;
-            let session = rd_session_1;
-
-            // Try to press a key. If an error occurred, print it and return. Sleeps after key is pressed
-            macro_rules! try_press_sleep {
-                ($key:expr) => {
-                    async {
-                        eprintln!("Pressing {}...", $key);
-                        if let Err(err) = press_key(&proxy, &session, $key).await {
-                            eprintln!("{}", err);
-                            return;
-                        }
-                        tokio::time::sleep(Duration::from_millis(50)).await;
-                    }
-                };
-                ($($modifier:expr),+; $key:expr) => {
-                    async {
-                        eprintln!("Pressing {:?} + {}...", &[$($modifier),+], $key);
-                        if let Err(err) = press_key_with_modifiers(&proxy, &session, $key, &[$($modifier),+]).await {
-                            eprintln!("{}", err);
-                            return;
-                        }
-                        tokio::time::sleep(Duration::from_millis(50)).await;
-                    }
-                };
-            }
-
-            async move {
-                // Space to activate remote desktop
-                try_press_sleep!(Key::Space).await;
-
-                // Tab + space to select (first) screen
-                try_press_sleep!(Key::Tab).await;
-                try_press_sleep!(Key::Space).await;
-
-                // Shift-Tab twice to focus the share button
-                try_press_sleep!(Key::LeftShift; Key::Tab).await;
-                try_press_sleep!(Key::LeftShift; Key::Tab).await;
-
-                // Space to allow sharing
-                try_press_sleep!(Key::Space).await;
-            }
-        });
-
         let response = rd_proxy
             .start(&session, None)
             .await
             .context("Failed to start remote desktop session")?
             .response()
-            .context("Failed to get remote desktop session response")?;
+            .context("Permission to control the desktop was not granted")?;
+
+        if let Some(token) = response.restore_token()
+            && restore_token.as_deref() != Some(token)
+        {
+            write_restore_token(token).await?;
+        }
 
-        let stream = match response.streams() {
-            Some(streams) => streams.first(),
-            None => None,
+        let streams = match response.streams() {
+            Some(streams) => streams.to_vec(),
+            None => Vec::new(),
         };
-        let Some(stream) = stream else {
+        if streams.is_empty() {
             return Err(anyhow::anyhow!("Missing stream"));
-        };
-        let stream = stream.clone();
+        }
+        println!("Got {} streams", streams.len());
+
+        let closed = session
+            .receive_closed()
+            .await
+            .context("Failed to listen for the session closing")?;
+
+        let clipboard_text = Arc::new(Mutex::new(None));
+        let clipboard_task = clipboard
+            .is_some()
+            .then(|| tokio::spawn(serve_clipboard(clipboard_text.clone())));
 
         Ok(Self {
             session,
             remote: rd_proxy,
             screencast: sc_proxy,
             response,
-            stream,
+            streams,
+            selected: 0,
+            outputs: None,
             streaming_thread: None,
+            closed: Mutex::new(Some(Box::pin(closed))),
+            clipboard,
+            clipboard_text,
+            clipboard_task,
         })
     }
 
-    pub async fn start_streaming(&mut self) -> anyhow::Result<mpsc::Receiver<Frame>> {
-        if self.streaming_thread.is_some() {
-            return Err(anyhow::anyhow!("Already streaming"));
-        }
+    /// Start a thread streaming the selected monitor to `self.outputs`,
+    /// stopping the previous one if there is any.
+    async fn spawn_streaming_thread(&mut self) -> anyhow::Result<()> {
+        let Some(outputs) = self.outputs.clone() else {
+            return Err(anyhow::anyhow!("Not streaming"));
+        };
+
+        // Dropping the previous thread stops it
+        self.streaming_thread = None;
 
         let fd = self
             .screencast
             .open_pipe_wire_remote(&self.session)
             .await
             .context("Failed to open pipewire remote")?;
-        let (tx, rx) = mpsc::sync_channel::<Frame>(0);
+        let (quit, quit_rx) = pw::channel::channel();
 
-        self.streaming_thread = Some(thread::spawn({
-            let stream = self.stream.clone();
+        let handle = thread::spawn({
+            let stream = self.selected_stream().clone();
             move || {
-                if let Err(err) = streaming_thread(fd, stream, tx) {
-                    println!("Streaming failed: {}", err);
+                let failure_tx = outputs.failure_tx.clone();
+                if let Err(err) = streaming_thread(fd, stream, outputs, quit_rx) {
+                    println!("Streaming failed: {:#}", err);
+                    let _ = failure_tx.send(format!("{err:#}"));
                 }
             }
-        }));
+        });
+        self.streaming_thread = Some(StreamingThread {
+            _handle: handle,
+            quit,
+        });
 
-        Ok(rx)
+        Ok(())
     }
 
-    pub async fn press_key(&self, sym: i32) -> anyhow::Result<()> {
-        self.remote
-            .notify_keyboard_keysym(&self.session, sym, KeyState::Pressed)
-            .await
-            .with_context(|| format!("Failed to press key {sym}"))?;
+    /// The monitor that is streamed and controlled.
+    fn selected_stream(&self) -> &Stream {
+        &self.streams[self.selected]
+    }
+}
 
-        tokio::time::sleep(Duration::from_millis(10)).await;
+impl RemoteBackend for Robot {
+    fn start_streaming(
+        &mut self,
+        cursor_tx: watch::Sender<CursorState>,
+    ) -> BoxFuture<'_, anyhow::Result<StreamReceivers>> {
+        Box::pin(async move {
+            if self.outputs.is_some() {
+                return Err(anyhow::anyhow!("Already streaming"));
+            }
 
-        self.remote
-            .notify_keyboard_keysym(&self.session, sym, KeyState::Released)
-            .await
-            .with_context(|| format!("Failed to release key {sym}"))?;
-        Ok(())
+            let (frame_tx, frames) = mpsc::channel(1);
+            let (failure_tx, failures) = mpsc::unbounded_channel();
+            self.outputs = Some(StreamOutputs {
+                frame_tx,
+                cursor_tx,
+                failure_tx,
+            });
+            self.spawn_streaming_thread().await?;
+
+            Ok(StreamReceivers { frames, failures })
+        })
     }
 
-    pub async fn move_mouse_absolute(&self, x: f64, y: f64) -> anyhow::Result<()> {
-        self.remote
-            .notify_pointer_motion_absolute(&self.session, self.stream.pipe_wire_node_id(), x, y)
-            .await
-            .with_context(|| format!("Failed to move pointer to ({x}, {y})"))?;
-        Ok(())
+    fn closed(&self) -> BoxFuture<'static, ()> {
+        let closed = self.closed.lock().unwrap().take();
+        Box::pin(async move {
+            match closed {
+                Some(mut closed) => {
+                    closed.next().await;
+                }
+                None => futures::future::pending().await,
+            }
+        })
+    }
+
+    fn close(mut self: Box<Self>) -> BoxFuture<'static, anyhow::Result<()>> {
+        Box::pin(async move {
+            self.streaming_thread = None;
+            self.session
+                .close()
+                .await
+                .context("Failed to close remote desktop session")
+        })
+    }
+
+    fn monitors(&self) -> Vec<Monitor> {
+        let selected = self.selected_stream().pipe_wire_node_id();
+        self.streams
+            .iter()
+            .map(|stream| Monitor {
+                id: stream.pipe_wire_node_id(),
+                position: stream.position(),
+                size: stream.size(),
+                selected: stream.pipe_wire_node_id() == selected,
+            })
+            .collect()
+    }
+
+    /// Monitors are identified by the PipeWire node id of their stream.
+    fn select_monitor(&mut self, id: u32) -> BoxFuture<'_, anyhow::Result<()>> {
+        Box::pin(async move {
+            let Some(index) = self
+                .streams
+                .iter()
+                .position(|stream| stream.pipe_wire_node_id() == id)
+            else {
+                return Err(anyhow::anyhow!("There is no stream with node id {id}"));
+            };
+            if index == self.selected {
+                return Ok(());
+            }
+
+            println!("Switching to stream {id}");
+            self.selected = index;
+            if self.outputs.is_some() {
+                self.spawn_streaming_thread().await?;
+            }
+            Ok(())
+        })
+    }
+
+    /// The logical size of the streamed monitor.
+    fn monitor_size(&self) -> Option<(i32, i32)> {
+        self.selected_stream().size()
+    }
+
+    fn move_pointer(&self, x: f64, y: f64) -> BoxFuture<'_, anyhow::Result<()>> {
+        Box::pin(async move {
+            let node_id = self.selected_stream().pipe_wire_node_id();
+            self.remote
+                .notify_pointer_motion_absolute(&self.session, node_id, x, y)
+                .await
+                .with_context(|| format!("Failed to move pointer to ({x}, {y})"))
+        })
+    }
+
+    fn pointer_button(
+        &self,
+        button: MouseButton,
+        pressed: bool,
+    ) -> BoxFuture<'_, anyhow::Result<()>> {
+        Box::pin(async move {
+            let code = match button {
+                MouseButton::Left => BTN_LEFT,
+                MouseButton::Right => BTN_RIGHT,
+                MouseButton::Middle => BTN_MIDDLE,
+            };
+            self.remote
+                .notify_pointer_button(&self.session, code, key_state(pressed))
+                .await
+                .with_context(|| format!("Failed to press or release {button:?} mouse button"))
+        })
+    }
+
+    fn scroll(&self, pixels: f64) -> BoxFuture<'_, anyhow::Result<()>> {
+        Box::pin(async move {
+            self.remote
+                .notify_pointer_axis(&self.session, 0.0, pixels, true)
+                .await
+                .with_context(|| format!("Failed to scroll by {pixels}"))
+        })
+    }
+
+    fn key(&self, keysym: i32, pressed: bool) -> BoxFuture<'_, anyhow::Result<()>> {
+        Box::pin(async move {
+            self.remote
+                .notify_keyboard_keysym(&self.session, keysym, key_state(pressed))
+                .await
+                .with_context(|| format!("Failed to press or release key {keysym}"))
+        })
+    }
+
+    fn set_clipboard(&self, text: String) -> BoxFuture<'_, anyhow::Result<()>> {
+        Box::pin(async move {
+            let Some(clipboard) = &self.clipboard else {
+                anyhow::bail!("The portal does not support sharing the clipboard");
+            };
+
+            *self.clipboard_text.lock().unwrap() = Some(text);
+            clipboard
+                .set_selection(&self.session, &TEXT_MIME_TYPES)
+                .await
+                .context("Failed to set the clipboard")
+        })
+    }
+
+    fn clipboard(&self) -> BoxFuture<'_, anyhow::Result<String>> {
+        Box::pin(async move {
+            let Some(clipboard) = &self.clipboard else {
+                anyhow::bail!("The portal does not support sharing the clipboard");
+            };
+
+            let fd = clipboard
+                .selection_read(&self.session, TEXT_MIME_TYPES[0])
+                .await
+                .context("Failed to read the clipboard")?;
+            let mut text = String::new();
+            fs::File::from_std(std::fs::File::from(fd))
+                .read_to_string(&mut text)
+                .await
+                .context("Failed to read the clipboard")?;
+            Ok(text)
+        })
+    }
+}
+
+fn key_state(pressed: bool) -> KeyState {
+    if pressed {
+        KeyState::Pressed
+    } else {
+        KeyState::Released
     }
 }
@@ -0,0 +1,219 @@
+//! Starts the remote desktop session once somebody needs it, stops it again when nobody used it
+//! for a while, and restarts it when its backend goes away.
+//!
+//! If the user has to allow controlling the desktop first, the session waits until somebody
+//! asks for permission from the remote page, instead of popping up a dialog on the TV by itself.
+
+use std::{
+    pin::pin,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context as _;
+use dioxus::prelude::*;
+use tokio::{
+    sync::{Mutex, Notify, watch},
+    time::{interval, sleep},
+};
+
+use super::backend::{self, RemoteBackend, StreamReceivers};
+use crate::{
+    backend::remote::{CursorState, convert::RgbFrame},
+    frontend::remote::SessionStatus,
+};
+
+/// How long the session keeps running without anybody using it.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// How often we check whether somebody is using the session.
+const VIEWER_CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+const MIN_RETRY_DELAY: Duration = Duration::from_secs(1);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// Wakes up the session waiting in [`SessionStatus::PermissionRequired`].
+static PERMISSION_REQUESTED: Notify = Notify::const_new();
+
+/// Stops the running session, so it starts again with the current permission.
+static RESTART: Notify = Notify::const_new();
+
+/// Ask the user for permission, if the session is waiting for it.
+pub fn request_permission() {
+    PERMISSION_REQUESTED.notify_waiters();
+}
+
+/// Stop the running session, if there is one.
+pub fn restart() {
+    RESTART.notify_waiters();
+}
+
+/// Everything the session shares with the rest of the backend.
+pub struct Outputs {
+    pub frame_tx: watch::Sender<Option<Arc<RgbFrame>>>,
+    pub cursor_tx: watch::Sender<CursorState>,
+    pub status_tx: watch::Sender<SessionStatus>,
+    pub backend: Arc<Mutex<Option<Box<dyn RemoteBackend>>>>,
+}
+
+impl Outputs {
+    /// Whether anybody is watching the screen or waiting for pointer updates.
+    ///
+    /// The context keeps one receiver of each channel itself, which does not count.
+    fn has_viewers(&self) -> bool {
+        self.frame_tx.receiver_count() > 1 || self.cursor_tx.receiver_count() > 1
+    }
+}
+
+/// Manage the session forever.
+pub async fn run(outputs: Outputs) {
+    let mut retry_delay = MIN_RETRY_DELAY;
+
+    loop {
+        if !outputs.has_viewers() {
+            outputs.status_tx.send_replace(SessionStatus::Idle);
+            while !outputs.has_viewers() {
+                sleep(VIEWER_CHECK_INTERVAL).await;
+            }
+        }
+
+        if backend::needs_permission().await {
+            if !wait_for_permission_request(&outputs).await {
+                continue;
+            }
+            info!("Asking for permission to control the desktop");
+            outputs
+                .status_tx
+                .send_replace(SessionStatus::WaitingForPermission);
+        } else {
+            info!("Starting remote desktop session");
+            outputs.status_tx.send_replace(SessionStatus::Starting);
+        }
+        let started = Instant::now();
+        let result = run_session(&outputs).await;
+
+        let backend = outputs.backend.lock().await.take();
+        if let Some(backend) = backend
+            && let Err(err) = backend.close().await
+        {
+            warn!("{:#}", err);
+        }
+        outputs.frame_tx.send_replace(None);
+        outputs.cursor_tx.send_replace(CursorState::default());
+
+        match result {
+            Ok(()) => {
+                info!("Stopped remote desktop session");
+                retry_delay = MIN_RETRY_DELAY;
+            }
+            Err(err) => {
+                warn!("Remote desktop session failed: {:#}", err);
+
+                // Only back off further if the session keeps failing right away
+                if started.elapsed() > MAX_RETRY_DELAY {
+                    retry_delay = MIN_RETRY_DELAY;
+                }
+                outputs.status_tx.send_replace(SessionStatus::Reconnecting {
+                    error: format!("{err:#}"),
+                    retry_in_secs: retry_delay.as_secs(),
+                });
+                sleep(retry_delay).await;
+                retry_delay = (retry_delay * 2).min(MAX_RETRY_DELAY);
+            }
+        }
+    }
+}
+
+/// Wait until somebody asks for permission to control the desktop,
+/// returning `false` if everybody left before that.
+async fn wait_for_permission_request(outputs: &Outputs) -> bool {
+    let mut requested = pin!(PERMISSION_REQUESTED.notified());
+    // Make sure a request right after reporting the status is not missed
+    requested.as_mut().enable();
+    outputs
+        .status_tx
+        .send_replace(SessionStatus::PermissionRequired);
+
+    let mut viewer_check = interval(VIEWER_CHECK_INTERVAL);
+    loop {
+        tokio::select! {
+            () = &mut requested => return true,
+            _ = viewer_check.tick() => {
+                if !outputs.has_viewers() {
+                    return false;
+                }
+            }
+        }
+    }
+}
+
+/// Run a single session, returning `Ok` once it was idle for [`IDLE_TIMEOUT`] or restarted
+/// on purpose, or an error once it broke.
+async fn run_session(outputs: &Outputs) -> anyhow::Result<()> {
+    let mut backend = backend::connect()
+        .await
+        .context("Failed to start the remote desktop session")?;
+    let StreamReceivers {
+        mut frames,
+        mut failures,
+    } = backend
+        .start_streaming(outputs.cursor_tx.clone())
+        .await
+        .context("Failed to start streaming")?;
+    let mut closed = backend.closed();
+    *outputs.backend.lock().await = Some(backend);
+
+    outputs.status_tx.send_replace(SessionStatus::Running);
+    info!("Remote desktop session is running");
+
+    let mut viewer_check = interval(VIEWER_CHECK_INTERVAL);
+    let mut idle_since = Option::<Instant>::None;
+    let mut previous_hash = None;
+    let mut restart = pin!(RESTART.notified());
+
+    loop {
+        tokio::select! {
+            frame = frames.recv() => {
+                let Some(frame) = frame else {
+                    anyhow::bail!("The screencast stopped");
+                };
+
+                // Nobody is watching, so there is no need to convert the frame
+                if outputs.frame_tx.receiver_count() == 1 {
+                    previous_hash = None;
+                    continue;
+                }
+
+                // Nothing changed on the screen, so there is no need to convert and encode the frame
+                let hash = frame.content_hash();
+                if previous_hash == Some(hash) {
+                    continue;
+                }
+                previous_hash = Some(hash);
+
+                match frame.to_rgb() {
+                    Ok(rgb) => {
+                        outputs.frame_tx.send_replace(Some(Arc::new(rgb)));
+                    }
+                    Err(err) => warn!("Failed to convert {:?} frame: {}", frame.format, err),
+                }
+            }
+            failure = failures.recv() => {
+                anyhow::bail!(failure.unwrap_or_else(|| "The screencast stopped".to_owned()));
+            }
+            () = &mut closed => {
+                anyhow::bail!("The desktop closed the remote desktop session");
+            }
+            () = &mut restart => {
+                return Ok(());
+            }
+            _ = viewer_check.tick() => {
+                if outputs.has_viewers() {
+                    idle_since = None;
+                } else if idle_since.get_or_insert_with(Instant::now).elapsed() >= IDLE_TIMEOUT {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,205 @@
+//! Drives the remote desktop endpoints against the fake backend, over real HTTP.
+
+use std::{net::SocketAddr, time::Duration};
+
+use axum::{extract::Request, middleware::Next, response::Response};
+use futures::{SinkExt, StreamExt};
+use tokio::{net::TcpListener, time::timeout};
+use tokio_tungstenite::tungstenite::Message;
+
+use super::{fake, key};
+use crate::{
+    backend::auth::Device,
+    frontend::remote::{
+        ClipboardRequest, Interaction, Key, MouseButton, RelativePosition, RemoteEvent,
+        SessionStatus,
+    },
+};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Every request comes from the media PC, so nothing has to be paired.
+async fn as_media_pc(mut request: Request, next: Next) -> Response {
+    request.extensions_mut().insert(Device::media_pc());
+    next.run(request).await
+}
+
+/// Serve EMPC on a free port.
+async fn serve() -> SocketAddr {
+    let router =
+        dioxus::server::router(crate::frontend::App).layer(axum::middleware::from_fn(as_media_pc));
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, router).await.unwrap();
+    });
+    addr
+}
+
+#[tokio::test]
+async fn interaction_is_injected() {
+    let addr = serve().await;
+    let (mut socket, _) =
+        tokio_tungstenite::connect_async(format!("ws://{addr}/api/remote/interaction"))
+            .await
+            .unwrap();
+
+    // Connecting starts the session
+    timeout(TIMEOUT, async {
+        while let Some(message) = socket.next().await {
+            if let Message::Binary(bytes) = message.unwrap() {
+                let event: RemoteEvent = postcard::from_bytes(&bytes).unwrap();
+                if event == RemoteEvent::Status(SessionStatus::Running) {
+                    return;
+                }
+            }
+        }
+        panic!("The socket closed before the session was running");
+    })
+    .await
+    .expect("The session didn't start");
+    fake::take_recorded_inputs();
+
+    let interactions = [
+        Interaction::Position(RelativePosition { x: 0.5, y: 0.25 }),
+        Interaction::MouseDown(MouseButton::Left),
+        Interaction::MouseUp(MouseButton::Left),
+        Interaction::Scroll(120.0),
+        Interaction::Text("a".to_owned()),
+        Interaction::Key(Key::Enter),
+        Interaction::Clipboard(ClipboardRequest::Set {
+            text: "secret".to_owned(),
+            paste: false,
+        }),
+    ];
+    for interaction in interactions {
+        let message = postcard::to_allocvec(&interaction).unwrap();
+        socket.send(Message::binary(message)).await.unwrap();
+    }
+
+    let enter = key::Key::from(Key::Enter).symbol();
+    let a = key::char_symbol('a');
+    let expected = vec![
+        fake::FakeInput::PointerMotion {
+            monitor: 1,
+            x: 640.0,
+            y: 180.0,
+        },
+        fake::FakeInput::PointerButton {
+            button: MouseButton::Left,
+            pressed: true,
+        },
+        fake::FakeInput::PointerButton {
+            button: MouseButton::Left,
+            pressed: false,
+        },
+        fake::FakeInput::Scroll { pixels: 120.0 },
+        fake::FakeInput::Key {
+            keysym: a,
+            pressed: true,
+        },
+        fake::FakeInput::Key {
+            keysym: a,
+            pressed: false,
+        },
+        fake::FakeInput::Key {
+            keysym: enter,
+            pressed: true,
+        },
+        fake::FakeInput::Key {
+            keysym: enter,
+            pressed: false,
+        },
+        fake::FakeInput::Clipboard {
+            text: "secret".to_owned(),
+        },
+    ];
+
+    let mut inputs = Vec::new();
+    timeout(TIMEOUT, async {
+        while inputs.len() < expected.len() {
+            inputs.extend(fake::take_recorded_inputs());
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .unwrap_or_else(|_| panic!("Only got {inputs:?}"));
+    assert_eq!(inputs, expected);
+}
+
+/// Whether a pixel of a lossy JPEG is about one of the test pattern colors.
+fn is_pattern_color(pixel: &[u8]) -> bool {
+    fake::BARS
+        .iter()
+        .chain([&fake::SQUARE_COLOR])
+        .any(|color| color.iter().zip(pixel).all(|(a, b)| a.abs_diff(*b) <= 48))
+}
+
+#[tokio::test]
+async fn screencast_is_the_test_pattern() {
+    let addr = serve().await;
+    let client = dioxus::fullstack::reqwest::Client::new();
+    let mut response = client
+        .get(format!(
+            "http://{addr}/api/remote/screencast?quality=90&adaptive=false"
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(
+        response.headers()["content-type"],
+        "multipart/x-mixed-replace;boundary=EMPC_FRAME_BOUNDARY"
+    );
+
+    // Read until the first part is complete
+    let mut body = Vec::new();
+    let jpeg = timeout(TIMEOUT, async {
+        loop {
+            body.extend_from_slice(&response.chunk().await.unwrap().unwrap());
+            let Some(header_end) = body.windows(4).position(|window| window == b"\r\n\r\n") else {
+                continue;
+            };
+            let headers = std::str::from_utf8(&body[..header_end]).unwrap();
+            assert!(headers.starts_with("--EMPC_FRAME_BOUNDARY\r\n"));
+            assert!(headers.contains("Content-Type: image/jpeg"));
+            let len: usize = headers
+                .lines()
+                .find_map(|line| line.strip_prefix("Content-Length: "))
+                .unwrap()
+                .parse()
+                .unwrap();
+
+            let start = header_end + 4;
+            if body.len() >= start + len {
+                return body[start..start + len].to_vec();
+            }
+        }
+    })
+    .await
+    .expect("No frame was streamed");
+
+    let mut decoder = jpeg_decoder::Decoder::new(jpeg.as_slice());
+    let pixels = decoder.decode().unwrap();
+    let info = decoder.info().unwrap();
+    assert_eq!((info.width, info.height), (1280, 720));
+
+    // Edges between colors are blurred by the compression, so only most pixels have to match
+    let samples: Vec<_> = pixels.chunks_exact(3).step_by(97).collect();
+    let matching = samples
+        .iter()
+        .filter(|pixel| is_pattern_color(pixel))
+        .count();
+    assert!(
+        matching * 10 >= samples.len() * 9,
+        "Only {matching} of {} pixels look like the test pattern",
+        samples.len()
+    );
+    for bar in fake::BARS {
+        assert!(
+            samples
+                .iter()
+                .any(|pixel| pixel.iter().zip(bar).all(|(a, b)| a.abs_diff(b) <= 48)),
+            "The bar {bar:?} is missing"
+        );
+    }
+}
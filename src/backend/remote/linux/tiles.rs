@@ -0,0 +1,139 @@
+//! A screencast that only sends the parts of the screen that changed.
+//!
+//! Every frame is split into square tiles, and only the tiles that differ from the previous frame
+//! are encoded and sent to the client as one binary websocket message.
+//! All numbers in a message are little endian:
+//!
+//! - `u16` frame width, `u16` frame height, `u16` number of tiles
+//! - for every tile: `u16` x, `u16` y, `u16` width, `u16` height, `u32` length, followed by
+//!   `length` bytes of JPEG
+
+use dioxus::fullstack::{Message, PostcardEncoding, WebSocketOptions, Websocket};
+use dioxus::prelude::*;
+use jpeg_encoder::ColorType;
+use tokio::task;
+
+use super::{CONTEXT, pacer::Pacer};
+use crate::{backend::remote::convert::RgbFrame, frontend::remote::ScreencastSettings};
+
+/// The width and height of a tile in pixels.
+const TILE_SIZE: usize = 64;
+
+pub async fn tiles(
+    options: WebSocketOptions,
+    settings: ScreencastSettings,
+) -> Result<Websocket<(), (), PostcardEncoding>, HttpError> {
+    let settings = settings.normalized();
+    let mut pacer = Pacer::new(settings, CONTEXT.frame_receiver.clone());
+
+    Ok(options.on_upgrade(move |mut socket| async move {
+        let mut previous = Option::<RgbFrame>::None;
+
+        while let Some(frame) = pacer.next_frame(&mut socket).await {
+            let previous_frame = previous.take();
+            let result = task::spawn_blocking(move || {
+                let frame = frame
+                    .fit_width(settings.max_width.unwrap_or_default() as usize)
+                    .into_owned();
+                let message =
+                    encode_changed_tiles(&frame, previous_frame.as_ref(), settings.quality);
+                (message, frame)
+            })
+            .await;
+
+            let message = match result {
+                Ok((message, frame)) => {
+                    previous = Some(frame);
+                    message
+                }
+                Err(err) => {
+                    warn!("Tile encoding task failed: {err}");
+                    continue;
+                }
+            };
+
+            match message {
+                Ok(Some(message)) => {
+                    if let Err(err) = socket.send_raw(Message::Binary(message.into())).await {
+                        warn!("Failed to send tiles: {}", err);
+                        return;
+                    }
+                }
+                Ok(None) => {}
+                Err(err) => warn!("Encoding tiles failed: {err}"),
+            }
+        }
+    }))
+}
+
+/// Encode the tiles of `frame` that differ from `previous` into a single message,
+/// or return `None` if nothing changed.
+///
+/// If `previous` is missing or has a different size, every tile is included.
+fn encode_changed_tiles(
+    frame: &RgbFrame,
+    previous: Option<&RgbFrame>,
+    quality: u8,
+) -> anyhow::Result<Option<Vec<u8>>> {
+    let previous = previous
+        .filter(|previous| previous.width == frame.width && previous.height == frame.height);
+
+    let mut tile_count = 0u16;
+    let mut body = Vec::new();
+    let mut pixels = Vec::with_capacity(TILE_SIZE * TILE_SIZE * 3);
+
+    for y in (0..frame.height).step_by(TILE_SIZE) {
+        for x in (0..frame.width).step_by(TILE_SIZE) {
+            let width = TILE_SIZE.min(frame.width - x);
+            let height = TILE_SIZE.min(frame.height - y);
+            let tile = (x, y, width, height);
+
+            if let Some(previous) = previous
+                && tile_rows(frame, tile).eq(tile_rows(previous, tile))
+            {
+                continue;
+            }
+
+            pixels.clear();
+            tile_rows(frame, tile).for_each(|row| pixels.extend_from_slice(row));
+
+            let mut jpeg = Vec::new();
+            jpeg_encoder::Encoder::new(&mut jpeg, quality).encode(
+                &pixels,
+                width as u16,
+                height as u16,
+                ColorType::Rgb,
+            )?;
+
+            for value in [x, y, width, height] {
+                body.extend_from_slice(&(value as u16).to_le_bytes());
+            }
+            body.extend_from_slice(&(jpeg.len() as u32).to_le_bytes());
+            body.extend_from_slice(&jpeg);
+            tile_count += 1;
+        }
+    }
+
+    if tile_count == 0 {
+        return Ok(None);
+    }
+
+    let mut message = Vec::with_capacity(6 + body.len());
+    message.extend_from_slice(&(frame.width as u16).to_le_bytes());
+    message.extend_from_slice(&(frame.height as u16).to_le_bytes());
+    message.extend_from_slice(&tile_count.to_le_bytes());
+    message.extend_from_slice(&body);
+
+    Ok(Some(message))
+}
+
+/// Get the rows of pixels of the tile at `(x, y, width, height)`.
+fn tile_rows(
+    frame: &RgbFrame,
+    (x, y, width, height): (usize, usize, usize, usize),
+) -> impl Iterator<Item = &[u8]> {
+    (y..y + height).map(move |row| {
+        let start = (row * frame.width + x) * 3;
+        &frame.data[start..start + width * 3]
+    })
+}
@@ -0,0 +1,775 @@
+//! Remote control of an X11 desktop, capturing the screen with MIT-SHM and injecting input with XTEST.
+//!
+//! Unlike the portals, this needs no permission from the user,
+//! so it also works on headless X servers like Xvfb.
+
+use std::{
+    fs::File,
+    future,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use anyhow::Context as _;
+use dioxus::prelude::*;
+use futures::future::BoxFuture;
+use memmap2::MmapOptions;
+use tokio::sync::{mpsc, watch};
+use x11rb::{
+    CURRENT_TIME,
+    connection::Connection,
+    protocol::{
+        randr::ConnectionExt as _,
+        shm::ConnectionExt as _,
+        xfixes::{ConnectionExt as _, GetCursorImageReply},
+        xproto::{self, ConnectionExt as _, ImageFormat, ImageOrder},
+        xtest::ConnectionExt as _,
+    },
+    rust_connection::RustConnection,
+};
+
+use super::{
+    backend::{RemoteBackend, StreamReceivers},
+    cursor::encode_png,
+};
+use crate::{
+    backend::remote::{
+        CursorImage, CursorState,
+        convert::{PixelFormat, Plane, RawFrame},
+    },
+    frontend::remote::{CursorShape, Monitor, MouseButton, RelativePosition, RelativeSize},
+};
+
+/// How often the screen is captured, as X11 can't tell us when it changed.
+const CAPTURE_FPS: u32 = 30;
+
+/// How many pixels one step of the scroll wheel scrolls.
+const SCROLL_STEP_PIXELS: f64 = 50.0;
+
+const SHIFT_KEYSYM: u32 = 0xffe1;
+
+/// The buttons X11 uses for the scroll wheel.
+const SCROLL_UP_BUTTON: u8 = 4;
+const SCROLL_DOWN_BUTTON: u8 = 5;
+
+/// A part of the X screen that is shown on one monitor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct X11Monitor {
+    /// The atom of the monitor's name, or 0 if RandR is not available
+    id: u32,
+    x: i16,
+    y: i16,
+    width: u16,
+    height: u16,
+}
+
+pub struct X11Backend {
+    connection: RustConnection,
+    /// The display connected to, or `None` for the one in `$DISPLAY`
+    display: Option<String>,
+    root: xproto::Window,
+    monitors: Vec<X11Monitor>,
+    /// Index into `monitors` of the monitor that is streamed and controlled
+    selected: usize,
+    keyboard: Mutex<KeyboardMapping>,
+    outputs: Option<CaptureOutputs>,
+    capture_thread: Option<CaptureThread>,
+}
+
+/// Where the capture thread sends what it captures.
+#[derive(Clone)]
+struct CaptureOutputs {
+    frame_tx: mpsc::Sender<RawFrame>,
+    cursor_tx: watch::Sender<CursorState>,
+    failure_tx: mpsc::UnboundedSender<String>,
+}
+
+struct CaptureThread {
+    _handle: JoinHandle<()>,
+    stop: Arc<AtomicBool>,
+}
+
+impl Drop for CaptureThread {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Which keysyms the keycodes of the keyboard produce.
+struct KeyboardMapping {
+    min_keycode: u8,
+    keysyms_per_keycode: u8,
+    keysyms: Vec<u32>,
+    /// A keycode without any keysyms, which is remapped to type keysyms no key produces
+    spare_keycode: Option<u8>,
+}
+
+impl KeyboardMapping {
+    fn query(connection: &RustConnection) -> anyhow::Result<Self> {
+        let setup = connection.setup();
+        let reply = connection
+            .get_keyboard_mapping(setup.min_keycode, setup.max_keycode - setup.min_keycode + 1)?
+            .reply()
+            .context("Failed to get the keyboard mapping")?;
+
+        Ok(Self::new(
+            setup.min_keycode,
+            reply.keysyms_per_keycode,
+            reply.keysyms,
+        ))
+    }
+
+    fn new(min_keycode: u8, keysyms_per_keycode: u8, keysyms: Vec<u32>) -> Self {
+        let per_keycode = usize::from(keysyms_per_keycode).max(1);
+        let spare_keycode = keysyms
+            .chunks(per_keycode)
+            .rposition(|syms| syms.iter().all(|&sym| sym == 0))
+            .and_then(|index| u8::try_from(index).ok())
+            .and_then(|index| min_keycode.checked_add(index));
+
+        Self {
+            min_keycode,
+            keysyms_per_keycode,
+            keysyms,
+            spare_keycode,
+        }
+    }
+
+    /// Find the keycode producing `keysym`, and whether shift needs to be held for it.
+    ///
+    /// Only the unshifted and shifted columns are used, since the others need modifiers like
+    /// AltGr that we would have to find first.
+    fn keycode(&self, keysym: u32) -> Option<(u8, bool)> {
+        let per_keycode = usize::from(self.keysyms_per_keycode).max(1);
+        self.keysyms
+            .chunks(per_keycode)
+            .enumerate()
+            .find_map(|(index, syms)| {
+                let column = syms.iter().take(2).position(|&sym| sym == keysym)?;
+                let keycode = self.min_keycode.checked_add(u8::try_from(index).ok()?)?;
+                Some((keycode, column == 1))
+            })
+    }
+
+    /// Map the spare keycode to `keysym`, so it can be typed without any modifiers.
+    fn remap(&mut self, connection: &RustConnection, keysym: u32) -> anyhow::Result<u8> {
+        let keycode = self
+            .spare_keycode
+            .context("There is no unused keycode to map it to")?;
+        let per_keycode = usize::from(self.keysyms_per_keycode).max(1);
+        let keysyms = vec![keysym; per_keycode];
+        connection
+            .change_keyboard_mapping(1, keycode, self.keysyms_per_keycode, &keysyms)?
+            .check()
+            .context("Failed to change the keyboard mapping")?;
+
+        let start = usize::from(keycode - self.min_keycode) * per_keycode;
+        self.keysyms[start..start + per_keycode].copy_from_slice(&keysyms);
+        Ok(keycode)
+    }
+}
+
+/// List the monitors of the screen, or the whole screen if RandR can't tell us.
+fn query_monitors(connection: &RustConnection, screen: &xproto::Screen) -> Vec<X11Monitor> {
+    let result = (|| -> anyhow::Result<_> {
+        connection.randr_query_version(1, 5)?.reply()?;
+        Ok(connection.randr_get_monitors(screen.root, true)?.reply()?)
+    })();
+
+    let mut monitors = match result {
+        Ok(reply) => reply
+            .monitors
+            .iter()
+            .map(|monitor| X11Monitor {
+                id: monitor.name,
+                x: monitor.x,
+                y: monitor.y,
+                width: monitor.width,
+                height: monitor.height,
+            })
+            .collect(),
+        Err(err) => {
+            warn!("Failed to list monitors with RandR: {:#}", err);
+            Vec::new()
+        }
+    };
+
+    if monitors.is_empty() {
+        monitors.push(X11Monitor {
+            id: 0,
+            x: 0,
+            y: 0,
+            width: screen.width_in_pixels,
+            height: screen.height_in_pixels,
+        });
+    }
+    monitors
+}
+
+fn capture_thread(
+    display: Option<&str>,
+    monitor: X11Monitor,
+    outputs: CaptureOutputs,
+    stop: &AtomicBool,
+) -> anyhow::Result<()> {
+    let (connection, screen) = RustConnection::connect(display)?;
+    let setup = connection.setup();
+    let root = setup
+        .roots
+        .get(screen)
+        .context("The X server has no default screen")?;
+
+    // We only know how to read 32-bit pixels, which all common setups use
+    let bits_per_pixel = setup
+        .pixmap_formats
+        .iter()
+        .find(|format| format.depth == root.root_depth)
+        .map(|format| format.bits_per_pixel);
+    if bits_per_pixel != Some(32) {
+        anyhow::bail!(
+            "Unsupported pixel format with depth {} and {:?} bits per pixel",
+            root.root_depth,
+            bits_per_pixel
+        );
+    }
+    let format = match setup.image_byte_order {
+        ImageOrder::LSB_FIRST => PixelFormat::Bgrx,
+        _ => PixelFormat::Xrgb,
+    };
+
+    let stride = usize::from(monitor.width) * 4;
+    let size = stride * usize::from(monitor.height);
+    let segment = connection.generate_id()?;
+    let reply = connection
+        .shm_create_segment(segment, u32::try_from(size)?, false)?
+        .reply()
+        .context("The X server does not support MIT-SHM")?;
+    let file = File::from(reply.shm_fd);
+    // SAFETY: Only the X server writes to the segment, while handling our requests
+    let memory = unsafe { MmapOptions::new().len(size).map(&file) }
+        .context("Failed to map the shared memory segment")?;
+
+    let has_xfixes = connection
+        .xfixes_query_version(4, 0)
+        .ok()
+        .and_then(|cookie| cookie.reply().ok())
+        .is_some();
+    if !has_xfixes {
+        warn!("The X server does not support XFIXES, so the cursor is not shown");
+    }
+    let mut cursor = CursorTracker::default();
+
+    info!(
+        "Capturing {}x{} at ({}, {})",
+        monitor.width, monitor.height, monitor.x, monitor.y
+    );
+    let interval = Duration::from_secs(1) / CAPTURE_FPS;
+    let mut next_frame = Instant::now();
+
+    while !stop.load(Ordering::Relaxed) {
+        thread::sleep(next_frame.saturating_duration_since(Instant::now()));
+        next_frame = (next_frame + interval).max(Instant::now());
+
+        connection
+            .shm_get_image(
+                root.root,
+                monitor.x,
+                monitor.y,
+                monitor.width,
+                monitor.height,
+                !0,
+                ImageFormat::Z_PIXMAP.into(),
+                segment,
+                0,
+            )?
+            .reply()
+            .context("Failed to capture the screen")?;
+        // The server is done writing to the segment once it replied
+        let frame = RawFrame {
+            format,
+            width: usize::from(monitor.width),
+            height: usize::from(monitor.height),
+            planes: vec![Plane {
+                data: memory[..size].to_vec(),
+                stride,
+            }],
+        };
+
+        if has_xfixes {
+            let image = connection.xfixes_get_cursor_image()?.reply()?;
+            if let Some(state) = cursor.update(&image, monitor) {
+                outputs.cursor_tx.send_replace(state);
+            }
+        }
+
+        // Blocking here applies backpressure until the frame is taken
+        if outputs.frame_tx.blocking_send(frame).is_err() {
+            break;
+        }
+    }
+
+    connection.shm_detach(segment)?;
+    connection.flush()?;
+    Ok(())
+}
+
+/// Keeps track of the cursor reported by XFIXES.
+#[derive(Default)]
+struct CursorTracker {
+    state: CursorState,
+    serial: Option<u32>,
+}
+
+impl CursorTracker {
+    /// Returns the new cursor state if it changed.
+    fn update(&mut self, image: &GetCursorImageReply, monitor: X11Monitor) -> Option<CursorState> {
+        if monitor.width == 0 || monitor.height == 0 {
+            return None;
+        }
+        let monitor_width = f32::from(monitor.width);
+        let monitor_height = f32::from(monitor.height);
+
+        let mut state = self.state.clone();
+        let position = RelativePosition {
+            x: (i32::from(image.x) - i32::from(monitor.x)) as f32 / monitor_width,
+            y: (i32::from(image.y) - i32::from(monitor.y)) as f32 / monitor_height,
+        };
+        state.position = ((0.0..1.0).contains(&position.x) && (0.0..1.0).contains(&position.y))
+            .then_some(position);
+
+        if self.serial != Some(image.cursor_serial) {
+            self.serial = Some(image.cursor_serial);
+
+            let width = usize::from(image.width);
+            let height = usize::from(image.height);
+            match encode_png(width, height, &unpremultiply(&image.cursor_image)) {
+                Ok(png) => {
+                    state.image = Some(CursorImage {
+                        shape: CursorShape {
+                            serial: u64::from(image.cursor_serial),
+                            size: RelativeSize {
+                                width: f32::from(image.width) / monitor_width,
+                                height: f32::from(image.height) / monitor_height,
+                            },
+                            hotspot: RelativePosition {
+                                x: f32::from(image.xhot) / monitor_width,
+                                y: f32::from(image.yhot) / monitor_height,
+                            },
+                        },
+                        png: Arc::new(png),
+                    });
+                }
+                Err(err) => warn!("Failed to encode cursor image: {}", err),
+            }
+        }
+
+        if state == self.state {
+            return None;
+        }
+        self.state = state.clone();
+        Some(state)
+    }
+}
+
+/// Convert premultiplied ARGB pixels, as XFIXES sends them, to RGBA.
+fn unpremultiply(pixels: &[u32]) -> Vec<u8> {
+    let mut rgba = Vec::with_capacity(pixels.len() * 4);
+    for pixel in pixels {
+        let [a, r, g, b] = pixel.to_be_bytes();
+        let channel = |value: u8| match a {
+            0 => 0,
+            a => (u16::from(value) * 255 / u16::from(a)).min(255) as u8,
+        };
+        rgba.extend_from_slice(&[channel(r), channel(g), channel(b), a]);
+    }
+    rgba
+}
+
+impl X11Backend {
+    /// Connect to the display in `$DISPLAY`.
+    pub fn connect() -> anyhow::Result<Self> {
+        Self::connect_to(None)
+    }
+
+    /// Connect to the given display, like `:1`.
+    fn connect_to(display: Option<&str>) -> anyhow::Result<Self> {
+        let (connection, screen) = RustConnection::connect(display)?;
+        let root = connection
+            .setup()
+            .roots
+            .get(screen)
+            .context("The X server has no default screen")?
+            .clone();
+
+        connection
+            .xtest_get_version(2, 2)?
+            .reply()
+            .context("The X server does not support XTEST")?;
+        let monitors = query_monitors(&connection, &root);
+        let keyboard = KeyboardMapping::query(&connection)?;
+        info!("Found {} X11 monitors", monitors.len());
+
+        Ok(Self {
+            connection,
+            display: display.map(str::to_owned),
+            root: root.root,
+            monitors,
+            selected: 0,
+            keyboard: Mutex::new(keyboard),
+            outputs: None,
+            capture_thread: None,
+        })
+    }
+
+    fn selected_monitor(&self) -> X11Monitor {
+        self.monitors[self.selected]
+    }
+
+    /// Start a thread capturing the selected monitor to `self.outputs`,
+    /// stopping the previous one if there is any.
+    fn spawn_capture_thread(&mut self) -> anyhow::Result<()> {
+        let Some(outputs) = self.outputs.clone() else {
+            anyhow::bail!("Not streaming");
+        };
+
+        // Dropping the previous thread stops it
+        self.capture_thread = None;
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let handle = thread::spawn({
+            let display = self.display.clone();
+            let monitor = self.selected_monitor();
+            let stop = stop.clone();
+            move || {
+                let failure_tx = outputs.failure_tx.clone();
+                if let Err(err) = capture_thread(display.as_deref(), monitor, outputs, &stop) {
+                    warn!("Capturing the X11 screen failed: {:#}", err);
+                    let _ = failure_tx.send(format!("{err:#}"));
+                }
+            }
+        });
+        self.capture_thread = Some(CaptureThread {
+            _handle: handle,
+            stop,
+        });
+
+        Ok(())
+    }
+
+    /// Send a fake input event to the root window.
+    fn fake_input(&self, type_: u8, detail: u8, x: i16, y: i16) -> anyhow::Result<()> {
+        self.connection
+            .xtest_fake_input(type_, detail, CURRENT_TIME, self.root, x, y, 0)?;
+        self.connection.flush()?;
+        Ok(())
+    }
+
+    fn click(&self, button: u8) -> anyhow::Result<()> {
+        self.fake_input(xproto::BUTTON_PRESS_EVENT, button, 0, 0)?;
+        self.fake_input(xproto::BUTTON_RELEASE_EVENT, button, 0, 0)
+    }
+
+    fn press_keycode(&self, keycode: u8, pressed: bool) -> anyhow::Result<()> {
+        let type_ = if pressed {
+            xproto::KEY_PRESS_EVENT
+        } else {
+            xproto::KEY_RELEASE_EVENT
+        };
+        self.fake_input(type_, keycode, 0, 0)
+    }
+
+    fn key_sync(&self, keysym: i32, pressed: bool) -> anyhow::Result<()> {
+        let mut keyboard = self.keyboard.lock().unwrap();
+        let (keycode, shifted) = match keyboard.keycode(keysym as u32) {
+            Some(found) => found,
+            // Releasing finds the keycode we remapped when it was pressed
+            None if pressed => {
+                let keycode = keyboard
+                    .remap(&self.connection, keysym as u32)
+                    .with_context(|| format!("No key produces keysym {keysym:#x}"))?;
+                (keycode, false)
+            }
+            None => anyhow::bail!("No key produces keysym {keysym:#x}"),
+        };
+        let shift = if shifted {
+            keyboard.keycode(SHIFT_KEYSYM).map(|(keycode, _)| keycode)
+        } else {
+            None
+        };
+
+        if pressed {
+            if let Some(shift) = shift {
+                self.press_keycode(shift, true)?;
+            }
+            self.press_keycode(keycode, true)
+        } else {
+            self.press_keycode(keycode, false)?;
+            if let Some(shift) = shift {
+                self.press_keycode(shift, false)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+impl RemoteBackend for X11Backend {
+    fn start_streaming(
+        &mut self,
+        cursor_tx: watch::Sender<CursorState>,
+    ) -> BoxFuture<'_, anyhow::Result<StreamReceivers>> {
+        Box::pin(async move {
+            if self.outputs.is_some() {
+                anyhow::bail!("Already streaming");
+            }
+
+            let (frame_tx, frames) = mpsc::channel(1);
+            let (failure_tx, failures) = mpsc::unbounded_channel();
+            self.outputs = Some(CaptureOutputs {
+                frame_tx,
+                cursor_tx,
+                failure_tx,
+            });
+            self.spawn_capture_thread()?;
+
+            Ok(StreamReceivers { frames, failures })
+        })
+    }
+
+    /// X11 has no session that could be closed, losing the connection fails the capture instead.
+    fn closed(&self) -> BoxFuture<'static, ()> {
+        Box::pin(future::pending())
+    }
+
+    fn close(mut self: Box<Self>) -> BoxFuture<'static, anyhow::Result<()>> {
+        self.capture_thread = None;
+        Box::pin(future::ready(Ok(())))
+    }
+
+    fn monitors(&self) -> Vec<Monitor> {
+        self.monitors
+            .iter()
+            .enumerate()
+            .map(|(index, monitor)| Monitor {
+                id: monitor.id,
+                position: Some((i32::from(monitor.x), i32::from(monitor.y))),
+                size: Some((i32::from(monitor.width), i32::from(monitor.height))),
+                selected: index == self.selected,
+            })
+            .collect()
+    }
+
+    /// Monitors are identified by the atom of their RandR name.
+    fn select_monitor(&mut self, id: u32) -> BoxFuture<'_, anyhow::Result<()>> {
+        Box::pin(async move {
+            let Some(index) = self.monitors.iter().position(|monitor| monitor.id == id) else {
+                anyhow::bail!("There is no monitor with id {id}");
+            };
+            if index == self.selected {
+                return Ok(());
+            }
+
+            info!("Switching to monitor {id}");
+            self.selected = index;
+            if self.outputs.is_some() {
+                self.spawn_capture_thread()?;
+            }
+            Ok(())
+        })
+    }
+
+    fn monitor_size(&self) -> Option<(i32, i32)> {
+        let monitor = self.selected_monitor();
+        Some((i32::from(monitor.width), i32::from(monitor.height)))
+    }
+
+    fn move_pointer(&self, x: f64, y: f64) -> BoxFuture<'_, anyhow::Result<()>> {
+        let monitor = self.selected_monitor();
+        let x = monitor.x.saturating_add(x.round() as i16);
+        let y = monitor.y.saturating_add(y.round() as i16);
+        Box::pin(future::ready(self.fake_input(
+            xproto::MOTION_NOTIFY_EVENT,
+            0,
+            x,
+            y,
+        )))
+    }
+
+    fn pointer_button(
+        &self,
+        button: MouseButton,
+        pressed: bool,
+    ) -> BoxFuture<'_, anyhow::Result<()>> {
+        let button = match button {
+            MouseButton::Left => 1,
+            MouseButton::Middle => 2,
+            MouseButton::Right => 3,
+        };
+        let type_ = if pressed {
+            xproto::BUTTON_PRESS_EVENT
+        } else {
+            xproto::BUTTON_RELEASE_EVENT
+        };
+        Box::pin(future::ready(self.fake_input(type_, button, 0, 0)))
+    }
+
+    fn scroll(&self, pixels: f64) -> BoxFuture<'_, anyhow::Result<()>> {
+        if pixels == 0.0 {
+            return Box::pin(future::ready(Ok(())));
+        }
+        // Scroll at least one step, so slow touchpad scrolling is not lost
+        let steps = (pixels.abs() / SCROLL_STEP_PIXELS).round().max(1.0) as u32;
+        let button = if pixels > 0.0 {
+            SCROLL_DOWN_BUTTON
+        } else {
+            SCROLL_UP_BUTTON
+        };
+        let result = (0..steps).try_for_each(|_| self.click(button));
+        Box::pin(future::ready(result))
+    }
+
+    fn key(&self, keysym: i32, pressed: bool) -> BoxFuture<'_, anyhow::Result<()>> {
+        Box::pin(future::ready(self.key_sync(keysym, pressed)))
+    }
+
+    fn set_clipboard(&self, _text: String) -> BoxFuture<'_, anyhow::Result<()>> {
+        Box::pin(future::ready(Err(anyhow::anyhow!(
+            "Sharing the clipboard is not supported on X11"
+        ))))
+    }
+
+    fn clipboard(&self) -> BoxFuture<'_, anyhow::Result<String>> {
+        Box::pin(future::ready(Err(anyhow::anyhow!(
+            "Sharing the clipboard is not supported on X11"
+        ))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{self, BufRead, BufReader},
+        process::{Child, Command, Stdio},
+    };
+
+    use super::*;
+
+    const A_KEYSYM: i32 = 0x61;
+    const EURO_KEYSYM: i32 = 0x20ac;
+
+    /// A headless X server, stopped when dropped.
+    struct Xvfb {
+        child: Child,
+        display: String,
+    }
+
+    impl Drop for Xvfb {
+        fn drop(&mut self) {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+
+    /// Start Xvfb on a free display, or return `None` if it is not installed.
+    fn xvfb() -> Option<Xvfb> {
+        let spawned = Command::new("Xvfb")
+            .args([
+                "-displayfd",
+                "1",
+                "-screen",
+                "0",
+                "640x480x24",
+                "-nolisten",
+                "tcp",
+            ])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn();
+        let mut child = match spawned {
+            Ok(child) => child,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                eprintln!("Skipping the X11 test, as Xvfb is not installed");
+                return None;
+            }
+            Err(err) => panic!("Failed to start Xvfb: {err}"),
+        };
+
+        // The display number is written once the server accepts connections
+        let mut display = String::new();
+        BufReader::new(child.stdout.take().unwrap())
+            .read_line(&mut display)
+            .unwrap();
+        let display = format!(":{}", display.trim());
+        Some(Xvfb { child, display })
+    }
+
+    fn key_is_down(backend: &X11Backend, keycode: u8) -> bool {
+        let keymap = backend.connection.query_keymap().unwrap().reply().unwrap();
+        keymap.keys[usize::from(keycode / 8)] & (1 << (keycode % 8)) != 0
+    }
+
+    #[tokio::test]
+    async fn monitors_capture_and_keys() {
+        let Some(xvfb) = xvfb() else {
+            return;
+        };
+        let mut backend = X11Backend::connect_to(Some(&xvfb.display)).unwrap();
+
+        let monitors = backend.monitors();
+        assert_eq!(monitors.len(), 1);
+        assert_eq!(monitors[0].size, Some((640, 480)));
+        assert!(monitors[0].selected);
+
+        let (cursor_tx, _) = watch::channel(CursorState::default());
+        let mut receivers = backend.start_streaming(cursor_tx).await.unwrap();
+        let frame = tokio::time::timeout(Duration::from_secs(5), receivers.frames.recv())
+            .await
+            .expect("No frame was captured")
+            .unwrap();
+        let rgb = frame.to_rgb().unwrap();
+        assert_eq!((rgb.width, rgb.height), (640, 480));
+
+        let keycode = |keysym: i32| backend.keyboard.lock().unwrap().keycode(keysym as u32);
+        let (a_keycode, _) = keycode(A_KEYSYM).unwrap();
+        backend.key(A_KEYSYM, true).await.unwrap();
+        assert!(key_is_down(&backend, a_keycode));
+        backend.key(A_KEYSYM, false).await.unwrap();
+        assert!(!key_is_down(&backend, a_keycode));
+
+        // The US layout has no key for the euro sign, so a spare key is mapped to it
+        assert_eq!(keycode(EURO_KEYSYM), None);
+        backend.key(EURO_KEYSYM, true).await.unwrap();
+        let (euro_keycode, shifted) = keycode(EURO_KEYSYM).unwrap();
+        assert!(!shifted);
+        assert!(key_is_down(&backend, euro_keycode));
+        backend.key(EURO_KEYSYM, false).await.unwrap();
+        assert!(!key_is_down(&backend, euro_keycode));
+
+        backend.scroll(0.0).await.unwrap();
+        Box::new(backend).close().await.unwrap();
+    }
+
+    #[test]
+    fn keycodes_only_use_the_first_two_columns() {
+        const AE_KEYSYM: u32 = 0xe6;
+        let mapping = KeyboardMapping::new(
+            8,
+            4,
+            [
+                [A_KEYSYM as u32, 0x41, AE_KEYSYM, 0xc6],
+                [0, 0, 0, 0],
+                [0x31, 0x21, 0, 0],
+            ]
+            .concat(),
+        );
+
+        assert_eq!(mapping.keycode(A_KEYSYM as u32), Some((8, false)));
+        assert_eq!(mapping.keycode(0x21), Some((10, true)));
+        // Only reachable with AltGr
+        assert_eq!(mapping.keycode(AE_KEYSYM), None);
+        assert_eq!(mapping.spare_keycode, Some(9));
+    }
+}
@@ -1,44 +1,281 @@
 use dioxus::fullstack::{PostcardEncoding, WebSocketOptions, Websocket};
 use dioxus::prelude::*;
+use dioxus_fullstack::response::Response;
 
 #[cfg(all(target_os = "linux", feature = "server"))]
 pub mod linux;
 #[cfg(all(target_os = "linux", feature = "server"))]
 pub use linux as implementation;
 
+#[cfg(all(target_os = "linux", feature = "server"))]
+pub mod convert;
+
 #[cfg(all(not(target_os = "linux"), feature = "server"))]
 pub mod dummy;
 #[cfg(all(not(target_os = "linux"), feature = "server"))]
 pub use dummy as implementation;
 
-use crate::frontend::remote::{Interaction, RelativePosition};
+#[cfg(feature = "server")]
+use crate::frontend::remote::{
+    ClipboardRequest, CursorShape, RelativePosition, ScreencastSettings,
+};
+use crate::frontend::remote::{Interaction, Monitor, RemoteEvent, SessionStatus};
+#[cfg(feature = "server")]
+use crate::{backend::auth::require_role, frontend::devices::Role};
+#[cfg(feature = "server")]
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// The state of the remote pointer, as reported by the screencast.
+#[cfg(feature = "server")]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CursorState {
+    /// Position of the pointer hotspot, or `None` if the pointer is not on the screen
+    pub position: Option<RelativePosition>,
+    pub image: Option<CursorImage>,
+}
+
+#[cfg(feature = "server")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct CursorImage {
+    pub shape: CursorShape,
+    pub png: std::sync::Arc<Vec<u8>>,
+}
+
+#[cfg(feature = "server")]
+impl CursorState {
+    /// Get the events a client that knows about `self` needs to get up to date with `new`.
+    fn events_until(&self, new: &CursorState) -> Vec<RemoteEvent> {
+        let mut events = Vec::new();
+
+        let old_shape = self.image.as_ref().map(|image| image.shape);
+        if let Some(image) = &new.image
+            && old_shape != Some(image.shape)
+        {
+            events.push(RemoteEvent::CursorShape(image.shape));
+        }
+
+        if self.position != new.position {
+            events.push(match new.position {
+                Some(position) => RemoteEvent::CursorPosition(position),
+                None => RemoteEvent::CursorHidden,
+            });
+        }
+
+        events
+    }
+}
+
+#[cfg(feature = "server")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageFormat {
+    #[default]
+    Png,
+    Jpeg,
+}
+
+#[cfg(feature = "server")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScreenshotOptions {
+    pub format: ImageFormat,
+}
+
+#[cfg(feature = "server")]
+#[get("/api/remote/screencast?:settings")]
+pub async fn screencast(
+    settings: ScreencastSettings,
+) -> Result<implementation::ScreencastResponse, HttpError> {
+    require_role(Role::Household)?;
+    if let Err(err) = settings.check() {
+        return HttpError::bad_request(err);
+    }
+    implementation::screencast(settings).await
+}
+
+/// Stream the screen as tiles of JPEG images, only sending the tiles that changed.
+#[cfg(feature = "server")]
+#[get("/api/remote/tiles?:settings")]
+pub async fn tiles(
+    options: WebSocketOptions,
+    settings: ScreencastSettings,
+) -> Result<Websocket<(), (), PostcardEncoding>, HttpError> {
+    require_role(Role::Household)?;
+    if let Err(err) = settings.check() {
+        return HttpError::bad_request(err);
+    }
+    implementation::tiles(options, settings).await
+}
+
+/// Stream the screen as H.264 over a websocket.
+#[cfg(feature = "server")]
+#[get("/api/remote/h264?:settings")]
+pub async fn h264(
+    options: WebSocketOptions,
+    settings: ScreencastSettings,
+) -> Result<Websocket<(), (), PostcardEncoding>, HttpError> {
+    require_role(Role::Household)?;
+    if let Err(err) = settings.check() {
+        return HttpError::bad_request(err);
+    }
+    implementation::h264(options, settings).await
+}
+
+/// Get the current frame at full resolution, as `?format=png` (the default) or `?format=jpeg`.
+#[cfg(feature = "server")]
+#[get("/api/remote/screenshot?:options")]
+pub async fn screenshot(options: ScreenshotOptions) -> Result<Response, HttpError> {
+    require_role(Role::Household)?;
+    implementation::screenshot(options).await
+}
+
+/// Start recording the screen to a new file, returning its path.
+#[post("/api/remote/recording/start")]
+pub async fn start_recording() -> Result<PathBuf, HttpError> {
+    require_role(Role::Admin)?;
+    implementation::start_recording().await
+}
+
+/// Stop the current recording, returning the path of the finished file.
+#[post("/api/remote/recording/stop")]
+pub async fn stop_recording() -> Result<PathBuf, HttpError> {
+    require_role(Role::Admin)?;
+    implementation::stop_recording().await
+}
+
+/// List the monitors that can be streamed.
+#[get("/api/remote/monitors")]
+pub async fn monitors() -> Result<Vec<Monitor>, HttpError> {
+    require_role(Role::Household)?;
+    implementation::monitors().await
+}
+
+/// Stream and control the monitor with the given id.
+#[post("/api/remote/monitors/select")]
+pub async fn select_monitor(id: u32) -> Result<(), HttpError> {
+    require_role(Role::Household)?;
+    implementation::select_monitor(id).await
+}
+
+#[get("/api/remote/cursor")]
+pub async fn cursor_image() -> Result<Response, HttpError> {
+    use axum::{
+        body::Body,
+        http::header::{CACHE_CONTROL, CONTENT_TYPE},
+    };
+
+    require_role(Role::Household)?;
+
+    let Some(image) = implementation::cursor().borrow().image.clone() else {
+        return Err(HttpError::new(
+            StatusCode::NOT_FOUND,
+            "The cursor image is not known yet",
+        ));
+    };
+
+    Response::builder()
+        .header(CONTENT_TYPE, "image/png")
+        .header(CACHE_CONTROL, "no-store")
+        .body(Body::from(image.png.to_vec()))
+        .map_err(|err| HttpError::new(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))
+}
+
+/// Get the state of the remote desktop session.
+#[get("/api/remote/status")]
+pub async fn status() -> Result<SessionStatus, HttpError> {
+    require_role(Role::Household)?;
+    Ok(implementation::status().borrow().clone())
+}
+
+/// Take the input the fake backend recorded since the last request.
+#[cfg(all(target_os = "linux", feature = "fake-remote"))]
+#[get("/api/remote/fake/inputs")]
+pub async fn fake_inputs() -> Result<Vec<linux::fake::FakeInput>, HttpError> {
+    require_role(Role::Admin)?;
+    Ok(linux::fake::take_recorded_inputs())
+}
+
+/// Show the dialog asking for permission to control the desktop on the media PC.
+#[post("/api/remote/permission/request")]
+pub async fn request_permission() -> Result<(), HttpError> {
+    require_role(Role::Household)?;
+    implementation::request_permission().await
+}
+
+/// Forget that the desktop may be controlled, so permission has to be given again.
+#[post("/api/remote/permission/revoke")]
+pub async fn revoke_permission() -> Result<(), HttpError> {
+    require_role(Role::Admin)?;
+    implementation::revoke_permission().await
+}
 
+/// Describe an interaction for the log, leaving out what was typed or copied.
 #[cfg(feature = "server")]
-#[get("/api/remote/screencast")]
-pub async fn screencast() -> Result<implementation::ScreencastResponse, HttpError> {
-    implementation::screencast().await
+fn redacted(interaction: &Interaction) -> String {
+    match interaction {
+        Interaction::Text(text) => format!("Text({} characters)", text.chars().count()),
+        Interaction::Clipboard(ClipboardRequest::Set { paste, .. }) => {
+            format!("Clipboard(Set {{ paste: {paste} }})")
+        }
+        other => format!("{other:?}"),
+    }
 }
 
 #[get("/api/remote/interaction")]
 pub async fn interaction(
     options: WebSocketOptions,
-) -> Result<Websocket<Interaction, RelativePosition, PostcardEncoding>, HttpError> {
+) -> Result<Websocket<Interaction, RemoteEvent, PostcardEncoding>, HttpError> {
+    require_role(Role::Household)?;
+
     Ok(options.on_upgrade(|mut socket| async move {
+        let mut cursor = implementation::cursor();
+        let mut sent_cursor = CursorState::default();
+        let mut status = implementation::status();
+
+        // Make sure the client learns about the current cursor and status right away
+        cursor.mark_changed();
+        status.mark_changed();
+
         loop {
-            let message = match socket.recv().await {
-                Ok(message) => message,
-                Err(err) => {
-                    error!("socket.recv() returned an error: {err}");
-                    return;
-                }
-            };
+            tokio::select! {
+                message = socket.recv() => {
+                    let message = match message {
+                        Ok(message) => message,
+                        Err(err) => {
+                            error!("socket.recv() returned an error: {err}");
+                            return;
+                        }
+                    };
 
-            info!("Got message: {:?}", message);
+                    info!("Got message: {}", redacted(&message));
 
-            if let Interaction::Position(position) = message
-                && let Err(err) = socket.send(position).await
-            {
-                warn!("Failed to send message: {}", err);
+                    if message == Interaction::Clipboard(ClipboardRequest::Get) {
+                        let text = implementation::clipboard()
+                            .await
+                            .map_err(|err| format!("{err:#}"));
+                        if let Err(err) = socket.send(RemoteEvent::Clipboard(text)).await {
+                            warn!("Failed to send message: {}", err);
+                        }
+                    } else if let Err(err) = implementation::interact(message).await {
+                        warn!("Failed to interact with the remote desktop: {}", err);
+                    }
+                }
+                Ok(()) = cursor.changed() => {
+                    let new_cursor = cursor.borrow_and_update().clone();
+                    for event in sent_cursor.events_until(&new_cursor) {
+                        if let Err(err) = socket.send(event).await {
+                            warn!("Failed to send message: {}", err);
+                        }
+                    }
+                    sent_cursor = new_cursor;
+                }
+                Ok(()) = status.changed() => {
+                    let new_status = status.borrow_and_update().clone();
+                    if let Err(err) = socket.send(RemoteEvent::Status(new_status)).await {
+                        warn!("Failed to send message: {}", err);
+                    }
+                }
             }
         }
     }))
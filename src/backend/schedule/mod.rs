@@ -0,0 +1,55 @@
+//! Runs actions later, like a sleep timer or powering off every night.
+
+use dioxus::fullstack::{PostcardEncoding, WebSocketOptions, Websocket};
+use dioxus::prelude::*;
+
+#[cfg(feature = "server")]
+mod scheduler;
+#[cfg(feature = "server")]
+pub use scheduler::start;
+
+use crate::frontend::schedule::{ScheduleCommand, ScheduleEvent};
+#[cfg(feature = "server")]
+use crate::{backend::auth::require_role, frontend::devices::Role};
+
+/// Follow the scheduled actions and their countdowns, and schedule or cancel actions.
+#[get("/api/schedule")]
+pub async fn schedule(
+    options: WebSocketOptions,
+) -> Result<Websocket<ScheduleCommand, ScheduleEvent, PostcardEncoding>, HttpError> {
+    use std::time::Duration;
+
+    let device = require_role(Role::Guest)?;
+
+    Ok(options.on_upgrade(move |mut socket| async move {
+        let mut ticks = tokio::time::interval(Duration::from_secs(1));
+
+        loop {
+            tokio::select! {
+                command = socket.recv() => {
+                    let command = match command {
+                        Ok(command) => command,
+                        Err(err) => {
+                            error!("socket.recv() returned an error: {err}");
+                            return;
+                        }
+                    };
+
+                    if let Err(err) = scheduler::command(command, device.role) {
+                        let message = ScheduleEvent::Error(format!("{err:#}"));
+                        if let Err(err) = socket.send(message).await {
+                            warn!("Failed to send message: {}", err);
+                        }
+                    }
+                    ticks.reset_immediately();
+                }
+                _ = ticks.tick() => {
+                    let entries = ScheduleEvent::Entries(scheduler::entries());
+                    if let Err(err) = socket.send(entries).await {
+                        warn!("Failed to send message: {}", err);
+                    }
+                }
+            }
+        }
+    }))
+}
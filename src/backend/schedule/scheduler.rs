@@ -0,0 +1,416 @@
+//! Keeps the scheduled actions in the state directory, and runs them when they are due.
+
+use std::{
+    collections::HashSet,
+    io,
+    path::PathBuf,
+    sync::{
+        LazyLock,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context as _, bail};
+use chrono::{DateTime, Local, NaiveTime};
+use dioxus::prelude::*;
+use tokio::{fs, sync::watch, time::interval};
+
+use crate::{
+    backend::{playback, shutdown},
+    frontend::{
+        devices::Role,
+        playback::{MediaPlayer, PlaybackStatus},
+        schedule::{Schedule, ScheduleCommand, ScheduleEntry, ScheduledAction, Trigger, When},
+    },
+};
+
+/// Actions that were missed by more than this while EMPC was not running are dropped,
+/// so the media PC doesn't power off right after it was turned on again.
+const MISSED_GRACE_SECS: u64 = 60;
+
+static SCHEDULES: LazyLock<watch::Sender<Vec<Schedule>>> = LazyLock::new(|| {
+    // Read before anybody can add to them, so nothing added is replaced by the saved ones
+    let schedules = read_schedules().unwrap_or_else(|err| {
+        warn!("Failed to read schedules: {:#}", err);
+        Vec::new()
+    });
+    let schedules = restore(schedules, Local::now());
+    let next_id = schedules.iter().map(|schedule| schedule.id + 1).max();
+    NEXT_ID.store(next_id.unwrap_or_default(), Ordering::Relaxed);
+
+    let (tx, _) = watch::channel(schedules);
+    tokio::spawn(run(tx.clone()));
+    tx
+});
+
+/// Ids are never reused, so nothing kept about a removed schedule applies to a new one.
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Start running the saved schedules, which needs to happen at startup instead of
+/// when somebody first looks at them.
+pub fn start() {
+    LazyLock::force(&SCHEDULES);
+}
+
+pub fn entries() -> Vec<ScheduleEntry> {
+    let now = now();
+    SCHEDULES
+        .borrow()
+        .iter()
+        .map(|schedule| ScheduleEntry {
+            secs_left: match schedule.trigger {
+                Trigger::At(at) | Trigger::Daily { next: at, .. } => Some(at.saturating_sub(now)),
+                Trigger::EndOfTrack { .. } => None,
+            },
+            schedule: schedule.clone(),
+        })
+        .collect()
+}
+
+/// Add or cancel a schedule for a device with the given role.
+pub fn command(command: ScheduleCommand, role: Role) -> anyhow::Result<()> {
+    match command {
+        ScheduleCommand::Add { action, when } => {
+            if role < action.role() {
+                bail!("A {role} device can't schedule {action}");
+            }
+            let trigger = match when {
+                When::InMinutes(minutes) => Trigger::At(now() + u64::from(minutes) * 60),
+                When::EndOfTrack => {
+                    let players = playback::watch_players().borrow().clone();
+                    let Some(player) = players
+                        .into_iter()
+                        .find(|player| player.status == PlaybackStatus::Playing)
+                    else {
+                        bail!("Nothing is playing");
+                    };
+                    Trigger::EndOfTrack {
+                        player: player.id,
+                        title: player.title,
+                    }
+                }
+                When::Daily { hour, minute } => Trigger::Daily {
+                    hour,
+                    minute,
+                    next: next_daily(hour, minute, Local::now())?,
+                },
+            };
+
+            SCHEDULES.send_modify(|schedules| {
+                schedules.push(Schedule {
+                    id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+                    action,
+                    trigger,
+                });
+            });
+        }
+        ScheduleCommand::Cancel(id) => {
+            let mut result = Ok(());
+            SCHEDULES.send_if_modified(|schedules| {
+                let Some(index) = schedules.iter().position(|schedule| schedule.id == id) else {
+                    result = Err(anyhow::anyhow!("Nothing is scheduled with id {id}"));
+                    return false;
+                };
+                if role < schedules[index].action.role() {
+                    result = Err(anyhow::anyhow!(
+                        "A {role} device can't cancel {}",
+                        schedules[index].action
+                    ));
+                    return false;
+                }
+                schedules.remove(index);
+                true
+            });
+            result?;
+        }
+    }
+    Ok(())
+}
+
+async fn run(tx: watch::Sender<Vec<Schedule>>) {
+    // Save the schedules whenever they change
+    let mut saved = tx.subscribe();
+    tokio::spawn(async move {
+        while saved.changed().await.is_ok() {
+            let schedules = saved.borrow_and_update().clone();
+            if let Err(err) = write_schedules(&schedules).await {
+                warn!("Failed to save schedules: {:#}", err);
+            }
+        }
+    });
+
+    // Players are only trusted to have stopped after they were seen, since they are
+    // not known yet right after starting
+    let mut seen_players = HashSet::new();
+    let mut ticks = interval(Duration::from_secs(1));
+    loop {
+        ticks.tick().await;
+        let now = now();
+        let players = playback::watch_players().borrow().clone();
+
+        let mut due = Vec::new();
+        tx.send_if_modified(|schedules| {
+            let count = schedules.len();
+            schedules.retain_mut(|schedule| {
+                if !is_due(schedule, now, &players, &mut seen_players) {
+                    return true;
+                }
+                due.push(schedule.action);
+                match &mut schedule.trigger {
+                    Trigger::Daily { hour, minute, next } => {
+                        match next_daily(*hour, *minute, Local::now()) {
+                            Ok(new_next) => *next = new_next,
+                            Err(err) => {
+                                warn!("Failed to schedule the next {}: {:#}", schedule.action, err);
+                                return false;
+                            }
+                        }
+                        true
+                    }
+                    _ => false,
+                }
+            });
+            // Forget the players of schedules that ran or were cancelled
+            seen_players.retain(|id| schedules.iter().any(|schedule| schedule.id == *id));
+            schedules.len() != count || !due.is_empty()
+        });
+
+        for action in due {
+            println!("Running scheduled action: {action}");
+            match action {
+                ScheduledAction::StopPlayback => {
+                    if let Err(err) = playback::pause_all().await {
+                        warn!("Failed to stop playback: {:#}", err);
+                    }
+                }
+                // Powering off counts down as usual, so it can still be cancelled
+                ScheduledAction::Power(action) => {
+                    if let Err(err) = shutdown::start_countdown(action) {
+                        warn!("Skipped {}: {}", action, err.message.unwrap_or_default());
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn is_due(
+    schedule: &Schedule,
+    now: u64,
+    players: &[MediaPlayer],
+    seen_players: &mut HashSet<u64>,
+) -> bool {
+    match &schedule.trigger {
+        Trigger::At(at) | Trigger::Daily { next: at, .. } => *at <= now,
+        Trigger::EndOfTrack { player, title } => {
+            match players.iter().find(|other| &other.id == player) {
+                Some(player) => {
+                    seen_players.insert(schedule.id);
+                    player.status == PlaybackStatus::Stopped || &player.title != title
+                }
+                None => seen_players.contains(&schedule.id),
+            }
+        }
+    }
+}
+
+/// Drop what was missed while EMPC was not running, and move daily actions to their next time.
+fn restore(schedules: Vec<Schedule>, local_now: DateTime<Local>) -> Vec<Schedule> {
+    let now = local_now.timestamp() as u64;
+    schedules
+        .into_iter()
+        .filter_map(|mut schedule| {
+            match &mut schedule.trigger {
+                Trigger::At(at) if *at + MISSED_GRACE_SECS < now => {
+                    warn!("Missed scheduled action: {}", schedule.action);
+                    return None;
+                }
+                Trigger::Daily { hour, minute, next } if *next + MISSED_GRACE_SECS < now => {
+                    *next = next_daily(*hour, *minute, local_now).ok()?;
+                }
+                _ => {}
+            }
+            Some(schedule)
+        })
+        .collect()
+}
+
+/// The next time after `now` it is the given local time, in seconds since the unix epoch.
+fn next_daily(hour: u8, minute: u8, now: DateTime<Local>) -> anyhow::Result<u64> {
+    let time = NaiveTime::from_hms_opt(hour.into(), minute.into(), 0)
+        .with_context(|| format!("{hour}:{minute} is not a time"))?;
+
+    let mut date = now.date_naive();
+    // A day can be skipped when the clocks change, and the time may not exist on it
+    for _ in 0..3 {
+        if let Some(at) = date.and_time(time).and_local_timezone(Local).earliest()
+            && at > now
+        {
+            return Ok(at.timestamp() as u64);
+        }
+        date = date.succ_opt().context("Ran out of dates")?;
+    }
+    bail!("{hour:02}:{minute:02} does not happen in the next days")
+}
+
+fn schedules_path() -> anyhow::Result<PathBuf> {
+    Ok(dirs::state_dir()
+        .or_else(dirs::data_local_dir)
+        .context("Failed to find the state directory")?
+        .join("empc")
+        .join("schedule.json"))
+}
+
+fn read_schedules() -> anyhow::Result<Vec<Schedule>> {
+    let path = schedules_path()?;
+    match std::fs::read(&path) {
+        Ok(json) => serde_json::from_slice(&json)
+            .with_context(|| format!("Failed to parse schedules {path:?}")),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(err).with_context(|| format!("Failed to read schedules {path:?}")),
+    }
+}
+
+async fn write_schedules(schedules: &[Schedule]) -> anyhow::Result<()> {
+    let path = schedules_path()?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .await
+            .with_context(|| format!("Failed to create state directory {dir:?}"))?;
+    }
+    let json = serde_json::to_vec_pretty(schedules)?;
+
+    // Write to a temporary file first, so a crash can't lose every schedule
+    let temporary = path.with_extension("json.tmp");
+    fs::write(&temporary, json)
+        .await
+        .with_context(|| format!("Failed to write schedules {temporary:?}"))?;
+    fs::rename(&temporary, &path)
+        .await
+        .with_context(|| format!("Failed to replace schedules {path:?}"))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone as _;
+
+    use super::*;
+    use crate::frontend::shutdown::PowerAction;
+
+    fn schedule(id: u64, trigger: Trigger) -> Schedule {
+        Schedule {
+            id,
+            action: ScheduledAction::Power(PowerAction::PowerOff),
+            trigger,
+        }
+    }
+
+    fn player(status: PlaybackStatus, title: &str) -> MediaPlayer {
+        MediaPlayer {
+            id: "org.mpris.MediaPlayer2.test".to_owned(),
+            name: "Test".to_owned(),
+            status,
+            title: Some(title.to_owned()),
+            artists: Vec::new(),
+            album: None,
+            art_url: None,
+            length_us: None,
+            position_us: None,
+            volume: None,
+        }
+    }
+
+    fn local(day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        Local
+            .with_ymd_and_hms(2026, 1, day, hour, minute, 0)
+            .single()
+            .unwrap()
+    }
+
+    #[test]
+    fn daily_times_roll_forward() {
+        let now = local(15, 12, 0);
+        let today = next_daily(13, 30, now).unwrap();
+        assert_eq!(today, local(15, 13, 30).timestamp() as u64);
+        let tomorrow = next_daily(11, 30, now).unwrap();
+        assert_eq!(tomorrow, local(16, 11, 30).timestamp() as u64);
+        // Right now is already too late
+        let now_again = next_daily(12, 0, now).unwrap();
+        assert_eq!(now_again, local(16, 12, 0).timestamp() as u64);
+
+        assert!(next_daily(24, 0, now).is_err());
+    }
+
+    #[test]
+    fn restore_drops_missed_actions() {
+        let local_now = local(15, 12, 0);
+        let now = local_now.timestamp() as u64;
+        let restored = restore(
+            vec![
+                schedule(0, Trigger::At(now - MISSED_GRACE_SECS - 1)),
+                schedule(1, Trigger::At(now - MISSED_GRACE_SECS)),
+                schedule(2, Trigger::At(now + 60)),
+                schedule(
+                    3,
+                    Trigger::Daily {
+                        hour: 8,
+                        minute: 0,
+                        next: local(14, 8, 0).timestamp() as u64,
+                    },
+                ),
+                schedule(
+                    4,
+                    Trigger::EndOfTrack {
+                        player: "org.mpris.MediaPlayer2.test".to_owned(),
+                        title: None,
+                    },
+                ),
+            ],
+            local_now,
+        );
+
+        let ids: Vec<_> = restored.iter().map(|schedule| schedule.id).collect();
+        assert_eq!(ids, [1, 2, 3, 4]);
+        assert_eq!(
+            restored[2].trigger,
+            Trigger::Daily {
+                hour: 8,
+                minute: 0,
+                next: local(16, 8, 0).timestamp() as u64,
+            }
+        );
+    }
+
+    #[test]
+    fn end_of_track_waits_for_the_player() {
+        let end_of_track = schedule(
+            0,
+            Trigger::EndOfTrack {
+                player: "org.mpris.MediaPlayer2.test".to_owned(),
+                title: Some("First".to_owned()),
+            },
+        );
+        let mut seen = HashSet::new();
+
+        // Players are not known yet right after starting, so a missing one isn't done yet
+        assert!(!is_due(&end_of_track, 0, &[], &mut seen));
+        let playing = [player(PlaybackStatus::Playing, "First")];
+        assert!(!is_due(&end_of_track, 0, &playing, &mut seen));
+        // Once it was seen, it going away means the track is over
+        assert!(is_due(&end_of_track, 0, &[], &mut seen));
+
+        let mut seen = HashSet::new();
+        let next_track = [player(PlaybackStatus::Playing, "Second")];
+        assert!(is_due(&end_of_track, 0, &next_track, &mut seen));
+        let stopped = [player(PlaybackStatus::Stopped, "First")];
+        assert!(is_due(&end_of_track, 0, &stopped, &mut seen));
+    }
+}
@@ -0,0 +1,84 @@
+//! Counts down to power actions, which can be cancelled until they run.
+
+use std::{
+    sync::{LazyLock, Mutex},
+    time::Duration,
+};
+
+use dioxus::prelude::*;
+use tokio::{sync::watch, task::AbortHandle, time::sleep};
+
+use super::implementation;
+use crate::{
+    backend::config,
+    frontend::shutdown::{PowerAction, PowerState},
+};
+
+pub static STATE: LazyLock<watch::Sender<PowerState>> =
+    LazyLock::new(|| watch::channel(PowerState::Idle).0);
+
+/// The countdown that is running, if any.
+static COUNTDOWN: Mutex<Option<AbortHandle>> = Mutex::new(None);
+
+/// Start counting down to an action, replacing the countdown that is running.
+pub fn start(action: PowerAction) {
+    // Held until the handle is stored, so the task can't look for it before it is there
+    let mut countdown = COUNTDOWN.lock().unwrap();
+    if let Some(previous) = countdown.take() {
+        previous.abort();
+    }
+    *countdown = Some(tokio::spawn(count_down(action)).abort_handle());
+}
+
+/// Stop the countdown, returning whether there was one.
+pub fn cancel() -> bool {
+    let Some(countdown) = COUNTDOWN.lock().unwrap().take() else {
+        return false;
+    };
+    countdown.abort();
+    STATE.send_replace(PowerState::Idle);
+    true
+}
+
+async fn count_down(action: PowerAction) {
+    let power = config::get().power.clone();
+
+    for secs_left in (1..=power.countdown_secs).rev() {
+        STATE.send_replace(PowerState::Pending { action, secs_left });
+        sleep(Duration::from_secs(1)).await;
+    }
+    {
+        // An aborted countdown can still get here if it was past its last sleep
+        let mut countdown = COUNTDOWN.lock().unwrap();
+        if countdown
+            .as_ref()
+            .is_none_or(|handle| handle.id() != tokio::task::id())
+        {
+            return;
+        }
+        countdown.take();
+    }
+    STATE.send_replace(PowerState::Running(action));
+    println!("{action}");
+
+    // Sleep a bit so browsers have enough time to be redirected
+    if action.leaves() {
+        sleep(Duration::from_millis(power.delay_ms)).await;
+    }
+
+    let result = match action {
+        PowerAction::Quit => std::process::exit(0),
+        action => implementation::run(action).await,
+    };
+    match result {
+        // Suspending returns once the media PC wakes up again
+        Ok(()) if matches!(action, PowerAction::ScreenOff | PowerAction::Suspend) => {
+            STATE.send_replace(PowerState::Idle);
+        }
+        Ok(()) => {}
+        Err(err) => {
+            error!("Failed to {}: {:#}", action, err);
+            STATE.send_replace(PowerState::Failed(format!("{action} failed: {err:#}")));
+        }
+    }
+}
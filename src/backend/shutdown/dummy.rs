@@ -0,0 +1,5 @@
+use crate::frontend::shutdown::PowerAction;
+
+pub async fn run(action: PowerAction) -> anyhow::Result<()> {
+    anyhow::bail!("{action} is only supported on linux")
+}
@@ -0,0 +1,147 @@
+//! Power actions through logind on the system bus, and the screen saver on the session bus.
+
+use anyhow::Context as _;
+use zbus::Connection;
+
+use crate::frontend::shutdown::PowerAction;
+
+#[zbus::proxy(
+    interface = "org.freedesktop.login1.Manager",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1"
+)]
+trait Manager {
+    fn power_off(&self, interactive: bool) -> zbus::Result<()>;
+    fn reboot(&self, interactive: bool) -> zbus::Result<()>;
+    fn suspend(&self, interactive: bool) -> zbus::Result<()>;
+    fn hibernate(&self, interactive: bool) -> zbus::Result<()>;
+}
+
+#[zbus::proxy(
+    interface = "org.freedesktop.ScreenSaver",
+    default_service = "org.freedesktop.ScreenSaver",
+    default_path = "/org/freedesktop/ScreenSaver"
+)]
+trait ScreenSaver {
+    fn set_active(&self, active: bool) -> zbus::Result<bool>;
+}
+
+pub async fn run(action: PowerAction) -> anyhow::Result<()> {
+    if action == PowerAction::ScreenOff {
+        let connection = Connection::session()
+            .await
+            .context("Failed to connect to the session bus")?;
+        ScreenSaverProxy::new(&connection)
+            .await?
+            .set_active(true)
+            .await?;
+        return Ok(());
+    }
+
+    let connection = Connection::system()
+        .await
+        .context("Failed to connect to the system bus")?;
+    call_logind(&connection, action).await
+}
+
+/// Ask logind on the given bus to do a power action.
+async fn call_logind(connection: &Connection, action: PowerAction) -> anyhow::Result<()> {
+    let manager = ManagerProxy::new(connection).await?;
+
+    // Not interactive, since nobody is at the media PC to enter a password
+    match action {
+        PowerAction::PowerOff => manager.power_off(false).await?,
+        PowerAction::Reboot => manager.reboot(false).await?,
+        PowerAction::Suspend => manager.suspend(false).await?,
+        PowerAction::Hibernate => manager.hibernate(false).await?,
+        PowerAction::ScreenOff | PowerAction::Quit => {
+            unreachable!("{action} is not done by logind")
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::backend::test_bus::TestBus;
+
+    /// Records what it was asked to do, instead of doing it.
+    struct MockManager {
+        calls: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[zbus::interface(name = "org.freedesktop.login1.Manager")]
+    impl MockManager {
+        fn power_off(&self, interactive: bool) {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("PowerOff({interactive})"));
+        }
+
+        fn reboot(&self, interactive: bool) {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("Reboot({interactive})"));
+        }
+
+        fn suspend(&self, interactive: bool) {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("Suspend({interactive})"));
+        }
+
+        fn hibernate(&self, interactive: bool) {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("Hibernate({interactive})"));
+        }
+    }
+
+    #[tokio::test]
+    async fn power_actions_call_logind() {
+        let Some(bus) = TestBus::start() else {
+            return;
+        };
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let _logind = bus
+            .builder()
+            .name("org.freedesktop.login1")
+            .unwrap()
+            .serve_at(
+                "/org/freedesktop/login1",
+                MockManager {
+                    calls: calls.clone(),
+                },
+            )
+            .unwrap()
+            .build()
+            .await
+            .unwrap();
+        let connection = bus.connect().await;
+
+        for action in [
+            PowerAction::PowerOff,
+            PowerAction::Reboot,
+            PowerAction::Suspend,
+            PowerAction::Hibernate,
+        ] {
+            call_logind(&connection, action).await.unwrap();
+        }
+        assert_eq!(
+            *calls.lock().unwrap(),
+            [
+                "PowerOff(false)",
+                "Reboot(false)",
+                "Suspend(false)",
+                "Hibernate(false)"
+            ]
+        );
+    }
+}
@@ -0,0 +1,96 @@
+//! Powers off, reboots or suspends the media PC through logind, after a countdown.
+
+use dioxus::fullstack::{PostcardEncoding, WebSocketOptions, Websocket};
+use dioxus::prelude::*;
+
+#[cfg(all(target_os = "linux", feature = "server"))]
+mod logind;
+#[cfg(all(target_os = "linux", feature = "server"))]
+use logind as implementation;
+
+#[cfg(all(not(target_os = "linux"), feature = "server"))]
+mod dummy;
+#[cfg(all(not(target_os = "linux"), feature = "server"))]
+use dummy as implementation;
+
+#[cfg(feature = "server")]
+mod countdown;
+
+use crate::frontend::shutdown::{PowerAction, PowerState};
+#[cfg(feature = "server")]
+use crate::{
+    backend::{auth::require_role, config},
+    frontend::devices::Role,
+};
+
+/// Start counting down to a power action, which has to be confirmed.
+#[post("/api/shutdown/start")]
+pub async fn start_power_action(action: PowerAction, confirmed: bool) -> Result<(), HttpError> {
+    require_role(Role::Admin)?;
+    if !confirmed {
+        return HttpError::bad_request(format!("{action} has to be confirmed"));
+    }
+
+    start_countdown(action)
+}
+
+/// Start counting down to a power action, unless another one is already running.
+#[cfg(feature = "server")]
+pub fn start_countdown(action: PowerAction) -> Result<(), HttpError> {
+    if matches!(*countdown::STATE.borrow(), PowerState::Running(_)) {
+        return HttpError::conflict("Another power action is already running");
+    }
+
+    countdown::start(action);
+    Ok(())
+}
+
+/// Cancel the power action that is counting down.
+#[post("/api/shutdown/cancel")]
+pub async fn cancel_power_action() -> Result<(), HttpError> {
+    require_role(Role::Admin)?;
+
+    if !countdown::cancel() {
+        return HttpError::conflict("No power action is counting down");
+    }
+    println!("Cancelled the power action");
+    Ok(())
+}
+
+/// Follow the countdown to power actions.
+#[get("/api/shutdown/state")]
+pub async fn power_state(
+    options: WebSocketOptions,
+) -> Result<Websocket<(), PowerState, PostcardEncoding>, HttpError> {
+    require_role(Role::Guest)?;
+
+    Ok(options.on_upgrade(|mut socket| async move {
+        let mut state = countdown::STATE.subscribe();
+        state.mark_changed();
+
+        loop {
+            tokio::select! {
+                message = socket.recv() => {
+                    if let Err(err) = message {
+                        error!("socket.recv() returned an error: {err}");
+                        return;
+                    }
+                }
+                Ok(()) = state.changed() => {
+                    let new_state = state.borrow_and_update().clone();
+                    if let Err(err) = socket.send(new_state).await {
+                        warn!("Failed to send message: {}", err);
+                    }
+                }
+            }
+        }
+    }))
+}
+
+/// Where the browser is sent once the media PC is powered off.
+#[get("/api/shutdown/redirect")]
+pub async fn power_redirect() -> Result<Option<String>, HttpError> {
+    require_role(Role::Guest)?;
+
+    Ok(config::get().power.redirect.clone())
+}
@@ -0,0 +1,60 @@
+//! A private D-Bus bus for tests that talk to services on the session or system bus.
+
+use std::{
+    io::{self, BufRead, BufReader},
+    process::{Child, Command, Stdio},
+};
+
+/// A `dbus-daemon` of its own, stopped when dropped.
+pub struct TestBus {
+    child: Child,
+    pub address: String,
+}
+
+impl Drop for TestBus {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+impl TestBus {
+    /// Start a bus, or return `None` if `dbus-daemon` is not installed.
+    pub fn start() -> Option<TestBus> {
+        let spawned = Command::new("dbus-daemon")
+            .args([
+                "--session",
+                "--nofork",
+                "--print-address",
+                "--address=unix:tmpdir=/tmp",
+            ])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn();
+        let mut child = match spawned {
+            Ok(child) => child,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                eprintln!("Skipping the D-Bus test, as dbus-daemon is not installed");
+                return None;
+            }
+            Err(err) => panic!("Failed to start dbus-daemon: {err}"),
+        };
+
+        // The address is printed once the bus accepts connections
+        let mut address = String::new();
+        BufReader::new(child.stdout.take().unwrap())
+            .read_line(&mut address)
+            .unwrap();
+        let address = address.trim().to_owned();
+        Some(TestBus { child, address })
+    }
+
+    /// Start building a connection to the bus, e.g. to serve a mock service on it.
+    pub fn builder(&self) -> zbus::connection::Builder<'_> {
+        zbus::connection::Builder::address(self.address.as_str()).unwrap()
+    }
+
+    pub async fn connect(&self) -> zbus::Connection {
+        self.builder().build().await.unwrap()
+    }
+}
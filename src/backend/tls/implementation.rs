@@ -0,0 +1,191 @@
+use std::{
+    io,
+    net::{IpAddr, SocketAddr},
+    path::{Path, PathBuf},
+    sync::{Arc, OnceLock},
+};
+
+use anyhow::Context as _;
+use axum::{Router, extract::Request, http::header::HOST, response::Redirect};
+use axum_server::tls_rustls::RustlsConfig;
+use dioxus::prelude::*;
+use rustls::{
+    ServerConfig,
+    pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject},
+};
+use sha2::{Digest, Sha256};
+use tokio::fs;
+
+use crate::backend::{config, discovery};
+
+static FINGERPRINT: OnceLock<String> = OnceLock::new();
+
+/// The SHA-256 fingerprint of the certificate being served, like browsers show it.
+pub fn fingerprint() -> Option<String> {
+    FINGERPRINT.get().cloned()
+}
+
+/// Whether EMPC is serving HTTPS, which only changes with a restart, unlike `tls.enabled`.
+pub fn is_serving() -> bool {
+    FINGERPRINT.get().is_some()
+}
+
+/// Serve over HTTPS, instead of `dioxus::serve`, which only serves plain HTTP.
+pub fn serve(router: impl FnOnce() -> Router) {
+    let result = tokio::runtime::Runtime::new()
+        .context("Failed to start the async runtime")
+        .and_then(|runtime| runtime.block_on(serve_tls(router)));
+    if let Err(err) = result {
+        eprintln!("{err:#}");
+        std::process::exit(1);
+    }
+}
+
+async fn serve_tls(router: impl FnOnce() -> Router) -> anyhow::Result<()> {
+    let config = config::get();
+    let (cert, key) = match (&config.tls.cert, &config.tls.key) {
+        (Some(cert), Some(key)) => (read(cert).await?, read(key).await?),
+        _ => self_signed().await?,
+    };
+
+    let certs = CertificateDer::pem_slice_iter(&cert)
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to parse the certificate")?;
+    let fingerprint = certs
+        .first()
+        .map(|cert| {
+            let hash = Sha256::digest(cert);
+            let hex: Vec<_> = hash.iter().map(|byte| format!("{byte:02X}")).collect();
+            hex.join(":")
+        })
+        .context("There is no certificate in the PEM file")?;
+    println!("Certificate fingerprint (SHA-256): {fingerprint}");
+    let _ = FINGERPRINT.set(fingerprint);
+    let key = PrivateKeyDer::from_pem_slice(&key).context("Failed to parse the private key")?;
+
+    // Picked explicitly, since other dependencies may bring in more crypto providers
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let mut tls = ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .context("The certificate doesn't match the private key")?;
+    tls.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+    let addr = dioxus::cli_config::fullstack_address_or_localhost();
+    if let Some(port) = config.tls.redirect_port {
+        let redirect_addr = SocketAddr::new(addr.ip(), port);
+        let redirect =
+            Router::new().fallback(move |request| redirect_to_https(request, addr.port()));
+        tokio::spawn(async move {
+            let served = axum_server::bind(redirect_addr)
+                .serve(redirect.into_make_service())
+                .await;
+            if let Err(err) = served {
+                warn!(
+                    "Failed to redirect plain HTTP at {}: {}",
+                    redirect_addr, err
+                );
+            }
+        });
+        println!("Redirecting plain HTTP at {redirect_addr}");
+    }
+
+    println!("Serving HTTPS at {addr}");
+    axum_server::bind_rustls(addr, RustlsConfig::from_config(Arc::new(tls)))
+        .serve(router().into_make_service_with_connect_info::<SocketAddr>())
+        .await
+        .with_context(|| format!("Failed to serve HTTPS at {addr}"))
+}
+
+/// Send plain HTTP requests to the same host and path over HTTPS.
+///
+/// The redirect is temporary, so browsers don't remember it if HTTPS is turned off again.
+async fn redirect_to_https(request: Request, port: u16) -> Redirect {
+    let host = request
+        .headers()
+        .get(HOST)
+        .and_then(|host| host.to_str().ok())
+        .unwrap_or("localhost");
+    // Without the port of the plain HTTP listener, which may also be an IPv6 address
+    let host = host
+        .rsplit_once(':')
+        .filter(|(_, port)| port.bytes().all(|byte| byte.is_ascii_digit()))
+        .map_or(host, |(host, _)| host);
+    let path = request
+        .uri()
+        .path_and_query()
+        .map_or("/", |path| path.as_str());
+
+    Redirect::temporary(&format!("https://{host}:{port}{path}"))
+}
+
+/// The self-signed certificate and its private key, generated on first run.
+async fn self_signed() -> anyhow::Result<(Vec<u8>, Vec<u8>)> {
+    let dir = tls_dir()?;
+    let (cert_path, key_path) = (dir.join("cert.pem"), dir.join("key.pem"));
+    match (fs::read(&cert_path).await, fs::read(&key_path).await) {
+        (Ok(cert), Ok(key)) => return Ok((cert, key)),
+        (Err(err), _) | (_, Err(err)) if err.kind() != io::ErrorKind::NotFound => {
+            return Err(err).with_context(|| format!("Failed to read the certificate in {dir:?}"));
+        }
+        _ => {}
+    }
+
+    // The addresses at the time, since they are only a convenience over the fingerprint
+    let hostname = &config::get().discovery.hostname;
+    let mut names = vec!["localhost".to_owned(), format!("{hostname}.local")];
+    names.extend(
+        discovery::lan_ips()
+            .unwrap_or_default()
+            .iter()
+            .map(IpAddr::to_string),
+    );
+    let generated =
+        rcgen::generate_simple_self_signed(names).context("Failed to generate a certificate")?;
+    let cert = generated.cert.pem().into_bytes();
+    let key = generated.signing_key.serialize_pem().into_bytes();
+
+    fs::create_dir_all(&dir)
+        .await
+        .with_context(|| format!("Failed to create state directory {dir:?}"))?;
+    write_key(&key_path, &key).await?;
+    fs::write(&cert_path, &cert)
+        .await
+        .with_context(|| format!("Failed to write certificate {cert_path:?}"))?;
+    println!("Generated a self-signed certificate in {dir:?}");
+    Ok((cert, key))
+}
+
+fn tls_dir() -> anyhow::Result<PathBuf> {
+    Ok(dirs::state_dir()
+        .or_else(dirs::data_local_dir)
+        .context("Failed to find the state directory")?
+        .join("empc")
+        .join("tls"))
+}
+
+async fn read(path: &Path) -> anyhow::Result<Vec<u8>> {
+    fs::read(path)
+        .await
+        .with_context(|| format!("Failed to read {path:?}"))
+}
+
+/// Write the private key, so only EMPC's user can read it.
+async fn write_key(path: &Path, key: &[u8]) -> anyhow::Result<()> {
+    // Restricted before it is moved into place, like the paired devices
+    let temporary = path.with_extension("pem.tmp");
+    fs::write(&temporary, key)
+        .await
+        .with_context(|| format!("Failed to write private key {temporary:?}"))?;
+    #[cfg(unix)]
+    {
+        use std::{fs::Permissions, os::unix::fs::PermissionsExt};
+        fs::set_permissions(&temporary, Permissions::from_mode(0o600))
+            .await
+            .with_context(|| format!("Failed to restrict permissions of {temporary:?}"))?;
+    }
+    fs::rename(&temporary, path)
+        .await
+        .with_context(|| format!("Failed to replace private key {path:?}"))
+}
@@ -0,0 +1,18 @@
+//! Serves HTTPS, with a configured certificate or a self-signed one that is generated on
+//! first run and kept in the state directory.
+
+#[cfg(feature = "server")]
+mod implementation;
+#[cfg(feature = "server")]
+pub use implementation::{is_serving, serve};
+
+use dioxus::prelude::*;
+
+/// The fingerprint of the certificate, so it can be compared with what the browser shows.
+///
+/// This is `None` when serving plain HTTP. Anybody may get it, since it matters most
+/// before pairing.
+#[get("/api/tls/fingerprint")]
+pub async fn certificate_fingerprint() -> Result<Option<String>, HttpError> {
+    Ok(implementation::fingerprint())
+}
@@ -0,0 +1,143 @@
+use dioxus::{
+    fullstack::{WebSocketOptions, use_websocket},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::backend::audio::audio;
+
+/// An audio output of the media PC, like HDMI or headphones.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Sink {
+    /// The PipeWire node id of the sink
+    pub id: u32,
+    pub name: String,
+    /// Volume from 0 to 1, on the same scale as the volume sliders of the desktop
+    pub volume: f32,
+    pub muted: bool,
+}
+
+/// The audio outputs of the media PC, sent whenever anything about them changes.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AudioState {
+    pub sinks: Vec<Sink>,
+    /// The id of the sink audio is played on
+    pub default_sink: Option<u32>,
+}
+
+impl AudioState {
+    pub fn default_sink(&self) -> Option<&Sink> {
+        let id = self.default_sink?;
+        self.sinks.iter().find(|sink| sink.id == id)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum AudioCommand {
+    /// Set the volume of a sink, from 0 to 1
+    SetVolume {
+        sink: u32,
+        volume: f32,
+    },
+    SetMuted {
+        sink: u32,
+        muted: bool,
+    },
+    /// Play audio on the given sink from now on
+    SetDefaultSink(u32),
+}
+
+/// Changes the volume of the media PC and the output audio is played on.
+#[component]
+pub fn VolumeControl() -> Element {
+    let socket = use_websocket(|| audio(WebSocketOptions::new()));
+    let mut state = use_signal(|| Option::<AudioState>::None);
+
+    use_future(move || async move {
+        loop {
+            match socket.recv().await {
+                Ok(new_state) => *state.write() = Some(new_state),
+                Err(err) => {
+                    warn!("socket.recv() returned an error: {err}");
+                    return;
+                }
+            }
+        }
+    });
+
+    let send = move |command: AudioCommand| async move {
+        if let Err(err) = socket.send(command).await {
+            warn!("Failed to send audio command to socket: {}", err);
+        }
+    };
+
+    let Some(state) = state() else {
+        return rsx! {};
+    };
+    let Some(sink) = state.default_sink().cloned() else {
+        return rsx! {
+            div { id: "group-volume", "No audio output" }
+        };
+    };
+    let percent = (sink.volume * 100.0).round();
+    let (sink_id, muted) = (sink.id, sink.muted);
+
+    rsx! {
+        div { id: "group-volume",
+            div { class: "title",
+                "Volume "
+                span { id: "volume-text", "{percent}" }
+                "%"
+                button {
+                    class: "small",
+                    id: "volume-mute",
+                    onclick: move |_| send(AudioCommand::SetMuted {
+                        sink: sink_id,
+                        muted: !muted,
+                    }),
+                    if muted {
+                        "Unmute"
+                    } else {
+                        "Mute"
+                    }
+                }
+            }
+            input {
+                id: "volume",
+                r#type: "range",
+                min: 0,
+                max: 100,
+                value: percent,
+                // Sent while dragging, so the volume can be judged by ear
+                oninput: move |event| async move {
+                    if let Ok(volume) = event.value().parse::<f32>() {
+                        send(AudioCommand::SetVolume {
+                                sink: sink_id,
+                                volume: volume / 100.0,
+                            })
+                            .await;
+                    }
+                },
+            }
+            if state.sinks.len() > 1 {
+                label {
+                    "Output "
+                    select {
+                        onchange: move |event| async move {
+                            if let Ok(id) = event.value().parse() {
+                                send(AudioCommand::SetDefaultSink(id)).await;
+                            }
+                        },
+                        for other in state.sinks.iter() {
+                            option {
+                                value: "{other.id}",
+                                selected: other.id == sink_id,
+                                "{other.name}"
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
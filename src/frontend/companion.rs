@@ -0,0 +1,95 @@
+use dioxus::{
+    fullstack::{WebSocketOptions, use_websocket},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::backend::companion::{machines, wake};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum WakeStatus {
+    /// Magic packets are being sent, but the media PC doesn't answer yet
+    Waking {
+        secs: u64,
+    },
+    /// The media PC answers at the given URL
+    Awake(String),
+    TimedOut,
+    Failed(String),
+}
+
+/// Wakes the media PCs, for a companion running on an always-on device.
+#[component]
+pub fn Companion() -> Element {
+    let machines = use_resource(machines);
+    let mut waking = use_signal(|| Option::<String>::None);
+
+    rsx! {
+        div { id: "parts",
+            match machines.value().read_unchecked().clone() {
+                Some(Ok(list)) if list.is_empty() => rsx! {
+                    div { class: "part", "No media PCs are configured in [[companion.machines]]" }
+                },
+                Some(Ok(list)) => rsx! {
+                    for name in list {
+                        div { class: "part",
+                            if waking().as_ref() == Some(&name) {
+                                Waking { name: name.clone() }
+                            } else {
+                                button {
+                                    class: "link",
+                                    disabled: waking().is_some(),
+                                    onclick: move |_| waking.set(Some(name.clone())),
+                                    "Wake {name}"
+                                }
+                            }
+                        }
+                    }
+                },
+                Some(Err(err)) => rsx! {
+                    div { class: "part error", "Failed to list media PCs: {err}" }
+                },
+                None => rsx! {
+                    div { class: "part", "Loading ..." }
+                },
+            }
+        }
+    }
+}
+
+#[component]
+fn Waking(name: String) -> Element {
+    let socket = use_websocket(move || wake(WebSocketOptions::new(), name.clone()));
+    let mut status = use_signal(|| Option::<WakeStatus>::None);
+
+    use_future(move || async move {
+        loop {
+            match socket.recv().await {
+                Ok(new_status) => *status.write() = Some(new_status),
+                Err(err) => {
+                    warn!("socket.recv() returned an error: {err}");
+                    return;
+                }
+            }
+        }
+    });
+
+    match status() {
+        None => rsx! {
+            div { class: "name", "Waking up..." }
+        },
+        Some(WakeStatus::Waking { secs }) => rsx! {
+            div { class: "name", "Waking up... {secs} s" }
+        },
+        Some(WakeStatus::Awake(url)) => rsx! {
+            meta { http_equiv: "refresh", content: "0;url={url}" }
+            div { class: "name", "Awake, taking you there" }
+        },
+        Some(WakeStatus::TimedOut) => rsx! {
+            div { class: "name error", "The media PC didn't wake up" }
+        },
+        Some(WakeStatus::Failed(err)) => rsx! {
+            div { class: "name error", "Failed to wake the media PC: {err}" }
+        },
+    }
+}
@@ -0,0 +1,163 @@
+use std::{fmt, str::FromStr};
+
+use dioxus::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    backend::auth::{current_role, paired_devices, revoke_device, set_device_role},
+    frontend::Route,
+};
+
+/// What a paired device may do. Every role may also do everything the roles before it may.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub enum Role {
+    /// Control playback and the volume
+    Guest,
+    /// Browse local media and use the remote
+    #[default]
+    Household,
+    /// Manage files, paired devices, power and settings
+    Admin,
+}
+
+impl Role {
+    pub const ALL: [Role; 3] = [Role::Guest, Role::Household, Role::Admin];
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Role::Guest => "guest",
+            Role::Household => "household",
+            Role::Admin => "admin",
+        })
+    }
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Role::ALL
+            .into_iter()
+            .find(|role| role.to_string() == s)
+            .ok_or_else(|| format!("Unknown role {s:?}"))
+    }
+}
+
+/// A phone or other device paired with the media PC.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PairedDevice {
+    pub id: String,
+    pub name: String,
+    pub role: Role,
+    pub paired_secs_ago: u64,
+    pub last_seen_secs_ago: u64,
+    /// Whether this is the device asking
+    pub current: bool,
+}
+
+/// Lists the paired devices, so lost phones can be unpaired and roles changed.
+///
+/// Devices that aren't admins only see themselves, so they can unpair.
+#[component]
+pub fn Devices() -> Element {
+    let mut devices = use_resource(paired_devices);
+    let result = devices.value().read_unchecked().cloned();
+    let role = use_resource(current_role);
+    let is_admin = matches!(*role.read(), Some(Ok(Role::Admin)));
+
+    let revoke = move |device: PairedDevice| async move {
+        if let Err(err) = revoke_device(device.id.clone()).await {
+            warn!("Failed to revoke device {}: {}", device.id, err);
+            return;
+        }
+        if device.current {
+            navigator().replace(Route::Pair {});
+        } else {
+            devices.restart();
+        }
+    };
+
+    let change_role = move |id: String, role: Role| async move {
+        if let Err(err) = set_device_role(id.clone(), role).await {
+            warn!("Failed to change the role of device {}: {}", id, err);
+        }
+        devices.restart();
+    };
+
+    rsx! {
+        div { id: "parts",
+            div { class: "part",
+                div { class: "name", "Paired devices" }
+            }
+            match result {
+                Some(Ok(list)) if list.is_empty() => rsx! {
+                    div { class: "part", "No devices are paired" }
+                },
+                Some(Ok(list)) => rsx! {
+                    for device in list {
+                        div { class: "part device", key: "{device.id}",
+                            div { class: "name",
+                                "{device.name}"
+                                if device.current {
+                                    " (this device)"
+                                }
+                            }
+                            select {
+                                class: "device-role",
+                                disabled: !is_admin,
+                                onchange: {
+                                    let id = device.id.clone();
+                                    move |event: FormEvent| {
+                                        let id = id.clone();
+                                        async move {
+                                            if let Ok(role) = event.value().parse() {
+                                                change_role(id, role).await;
+                                            }
+                                        }
+                                    }
+                                },
+                                for role in Role::ALL {
+                                    option {
+                                        value: "{role}",
+                                        selected: device.role == role,
+                                        "{role}"
+                                    }
+                                }
+                            }
+                            div { class: "device-times",
+                                "Paired {ago(device.paired_secs_ago)}, last seen {ago(device.last_seen_secs_ago)}"
+                            }
+                            button {
+                                class: "link",
+                                onclick: {
+                                    let device = device.clone();
+                                    move |_| revoke(device.clone())
+                                },
+                                "Revoke"
+                            }
+                        }
+                    }
+                },
+                Some(Err(err)) => rsx! {
+                    div { class: "part error", "Failed to list devices: {err}" }
+                },
+                None => rsx! {
+                    div { class: "part", "Loading ..." }
+                },
+            }
+        }
+    }
+}
+
+fn ago(secs: u64) -> String {
+    match secs {
+        0..60 => "just now".to_owned(),
+        60..3600 => format!("{} min ago", secs / 60),
+        3600..86400 => format!("{} h ago", secs / 3600),
+        _ => format!("{} days ago", secs / 86400),
+    }
+}
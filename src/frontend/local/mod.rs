@@ -144,28 +144,20 @@ fn Directory(directory: Signal<String>, file_name: String, file_path: PathBuf) -
 
 #[component]
 fn File(file_name: String, file_path: PathBuf) -> Element {
-    /*
-    var form = document.createElement("form");
-    form.className = "entry";
-    form.action = "/local/play/"+
-        encodeURIComponent(location.href)+"/"+
-        encodeURIComponent(file.path);
-    form.method = "post";
-
-    var el = document.createElement("a");
-    el.className = "file";
-    el.innerText = file.name;
-    el.href = "javascript:void";
-    el.onclick = form.submit.bind(form);
-
-    form.appendChild(el);
-
-    return form;
-    */
     rsx! {
-        form { class: "entry", method: "post",
-            // action: "/local/play/"+ encodeURIComponent(location.href)+"/"+ encodeURIComponent(file.path);
-            a { class: "file", "{file_name}" }
+        div { class: "entry",
+            a {
+                class: "file",
+                onclick: move |_| {
+                    let path = file_path.clone();
+                    async move {
+                        if let Err(err) = backend::local::play_file(path.clone()).await {
+                            warn!("Failed to play {:?}: {}", path, err);
+                        }
+                    }
+                },
+                "{file_name}"
+            }
         }
     }
 }
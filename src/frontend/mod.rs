@@ -1,21 +1,64 @@
+pub mod audio;
+pub mod companion;
+pub mod devices;
 pub mod local;
+pub mod pair;
 pub mod playback;
 pub mod remote;
+pub mod schedule;
+pub mod settings;
 pub mod shutdown;
 
-use {local::Local, playback::Playback, remote::Remote, shutdown::Shutdown};
+use {
+    companion::Companion,
+    devices::Devices,
+    local::Local,
+    pair::{Pair, PairingScreen},
+    playback::Playback,
+    remote::{Dpad, Remote},
+    schedule::SleepTimer,
+    settings::Settings,
+    shutdown::Shutdown,
+};
 
 use dioxus::prelude::*;
 
+use crate::{backend::auth::current_role, frontend::devices::Role};
+
 const STYLE_CSS: Asset = asset!("/assets/style.css");
 const SAKURA_CSS: Asset = asset!("/assets/sakura.css");
 
 pub fn run() {
+    #[cfg(feature = "server")]
+    {
+        if crate::backend::config::get().tls.enabled {
+            crate::backend::tls::serve(router);
+            return;
+        }
+        dioxus::serve(|| async move { Ok(router()) });
+    }
+
+    #[cfg(not(feature = "server"))]
     dioxus::launch(App);
 }
 
+#[cfg(feature = "server")]
+fn router() -> axum::Router {
+    if crate::backend::config::get().companion.enabled {
+        println!("Running as a companion, to wake the media PC");
+        return dioxus::server::router(App).layer(axum::middleware::from_fn(
+            crate::backend::companion::companion_only,
+        ));
+    }
+    crate::backend::schedule::start();
+
+    dioxus::server::router(App).layer(axum::middleware::from_fn(
+        crate::backend::auth::require_device,
+    ))
+}
+
 #[component]
-fn App() -> Element {
+pub(crate) fn App() -> Element {
     rsx! {
         document::Link { rel: "stylesheet", href: SAKURA_CSS }
         document::Link { rel: "stylesheet", href: STYLE_CSS }
@@ -29,17 +72,34 @@ enum Route {
     Home {},
     #[route("/play")]
     Playback {},
-    #[route("/remote")]
-    Remote {},
+    #[route("/remote?:transport")]
+    Remote { transport: remote::Transport },
+    #[route("/remote/dpad")]
+    Dpad {},
     #[route("/local?:directory")]
     Local { directory: String },
     #[route("/shutdown")]
     Shutdown {},
+    #[route("/pair")]
+    Pair {},
+    #[route("/pair/screen?:token")]
+    PairingScreen { token: String },
+    #[route("/devices")]
+    Devices {},
+    #[route("/settings")]
+    Settings {},
+    #[route("/companion")]
+    Companion {},
 }
 
+/// Lists what this device can do, hiding what its role doesn't allow.
 #[component]
 pub fn Home() -> Element {
+    let role = use_server_future(|| async { current_role().await.ok() })?;
+    let role = role().flatten().unwrap_or(Role::Guest);
+
     rsx! {
+        SleepTimer {}
         div { id: "parts",
             form { class: "part", method: "post", action: "/play/url",
                 div { class: "name", "URL or Magnet Link:" }
@@ -55,14 +115,34 @@ pub fn Home() -> Element {
                 input { autocomplete: "off", name: "file", r#type: "file" }
                 button { "Play" }
             }
-            form { class: "part", method: "get", action: "/remote/",
-                button { class: "link", "Remote" }
+            if role >= Role::Household {
+                form { class: "part", method: "get", action: "/remote/",
+                    button { class: "link", "Remote" }
+                }
+                form { class: "part", method: "get", action: "/remote/dpad",
+                    button { class: "link", "D-pad" }
+                }
+                form { class: "part", method: "get", action: "/local/",
+                    button { class: "link", "Local Media" }
+                }
             }
-            form { class: "part", method: "get", action: "/local/",
-                button { class: "link", "Local Media" }
+            // Other devices only see themselves there, so they can unpair
+            form { class: "part", method: "get", action: "/devices",
+                button { class: "link",
+                    if role >= Role::Admin {
+                        "Paired Devices"
+                    } else {
+                        "This Device"
+                    }
+                }
             }
-            form { class: "part", method: "get", action: "/api/shutdown/",
-                button { class: "link", "Power Off" }
+            if role >= Role::Admin {
+                form { class: "part", method: "get", action: "/settings",
+                    button { class: "link", "Settings" }
+                }
+                form { class: "part", method: "get", action: "/shutdown",
+                    button { class: "link", "Power" }
+                }
             }
         }
     }
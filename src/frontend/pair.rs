@@ -0,0 +1,135 @@
+use dioxus::{
+    fullstack::{WebSocketOptions, use_websocket},
+    prelude::*,
+};
+
+use crate::{
+    backend::{
+        auth::{pair, pin_screen, request_pin},
+        discovery::url_qr_code,
+        tls::certificate_fingerprint,
+    },
+    frontend::Route,
+};
+
+/// Pairs this device with the media PC, using the PIN shown on the TV.
+#[component]
+pub fn Pair() -> Element {
+    let fingerprint = use_server_future(|| async { certificate_fingerprint().await.ok() })?;
+    let mut requested = use_signal(|| false);
+    let mut name = use_signal(String::new);
+    let mut pin = use_signal(String::new);
+    let mut error = use_signal(|| Option::<String>::None);
+
+    let show_pin = move |_| async move {
+        match request_pin().await {
+            Ok(()) => requested.set(true),
+            Err(err) => error.set(Some(format!("Failed to show a PIN: {err}"))),
+        }
+    };
+    let submit = move |_| async move {
+        match pair(pin(), name()).await {
+            Ok(_) => {
+                navigator().replace(Route::Home {});
+            }
+            Err(err) => {
+                pin.set(String::new());
+                error.set(Some(err.to_string()));
+            }
+        }
+    };
+
+    rsx! {
+        div { id: "parts",
+            div { class: "part",
+                div { class: "name", "Pair this device with the media PC" }
+                button { class: "link", onclick: show_pin,
+                    if requested() {
+                        "Show a new PIN"
+                    } else {
+                        "Show a PIN on the TV"
+                    }
+                }
+            }
+            div { class: "part",
+                div { class: "name", "Device name:" }
+                input {
+                    autocomplete: "off",
+                    r#type: "text",
+                    placeholder: "Living room phone",
+                    value: name,
+                    oninput: move |event| name.set(event.value()),
+                }
+            }
+            div { class: "part",
+                div { class: "name", "PIN:" }
+                input {
+                    autocomplete: "one-time-code",
+                    inputmode: "numeric",
+                    r#type: "text",
+                    value: pin,
+                    oninput: move |event| pin.set(event.value()),
+                }
+                button { disabled: pin().trim().is_empty(), onclick: submit, "Pair" }
+            }
+            if let Some(error) = error() {
+                div { class: "part error", "{error}" }
+            }
+            if let Some(fingerprint) = fingerprint().flatten().flatten() {
+                div { class: "part fingerprint",
+                    div { class: "name", "Certificate fingerprint (SHA-256), as shown on the TV:" }
+                    code { "{fingerprint}" }
+                }
+            }
+        }
+    }
+}
+
+/// Shows the PIN to pair with in big letters, for a browser running on the TV itself,
+/// with a QR code phones can scan to open EMPC and the fingerprint of the certificate to
+/// check the browser's warning against.
+///
+/// The `token` query parameter lets it show the PIN without pairing, and is printed at startup.
+#[component]
+pub fn PairingScreen(token: String) -> Element {
+    let qr_code = use_server_future(|| async { url_qr_code().await.ok() })?;
+    let fingerprint = use_server_future(|| async { certificate_fingerprint().await.ok() })?;
+    let socket = use_websocket(move || pin_screen(WebSocketOptions::new(), token.clone()));
+    let mut pin = use_signal(|| Option::<String>::None);
+
+    use_future(move || async move {
+        loop {
+            match socket.recv().await {
+                Ok(new_pin) => *pin.write() = new_pin,
+                Err(err) => {
+                    warn!("socket.recv() returned an error: {err}");
+                    return;
+                }
+            }
+        }
+    });
+
+    rsx! {
+        div { id: "pairing-screen",
+            match pin() {
+                Some(pin) => rsx! {
+                    div { "Enter this PIN on your phone" }
+                    div { id: "pin", "{pin}" }
+                },
+                None => rsx! {
+                    div { "Open EMPC on your phone to pair it" }
+                },
+            }
+            if let Some(svg) = qr_code().flatten() {
+                div { id: "qr-code", dangerous_inner_html: svg }
+            }
+            if let Some(fingerprint) = fingerprint().flatten().flatten() {
+                div { id: "fingerprint",
+                    "Certificate fingerprint (SHA-256):"
+                    br {}
+                    code { "{fingerprint}" }
+                }
+            }
+        }
+    }
+}
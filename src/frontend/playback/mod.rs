@@ -1,8 +1,64 @@
+mod now_playing;
+
 use dioxus::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::frontend::{audio::VolumeControl, schedule::SleepTimer};
+use now_playing::NowPlaying;
+
+/// A media player on the media PC that can be controlled through MPRIS, like a browser or Spotify.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MediaPlayer {
+    /// The D-Bus name of the player, like `org.mpris.MediaPlayer2.spotify`
+    pub id: String,
+    /// The name of the player as shown to the user
+    pub name: String,
+    pub status: PlaybackStatus,
+    pub title: Option<String>,
+    pub artists: Vec<String>,
+    pub album: Option<String>,
+    /// Where the artwork of the current track is, which is only reachable through
+    /// `/api/playback/art` if it is a `file://` URL
+    pub art_url: Option<String>,
+    /// Length of the current track in microseconds
+    pub length_us: Option<i64>,
+    /// Position in the current track in microseconds
+    pub position_us: Option<i64>,
+    /// Volume from 0 to 1, if the player has its own volume
+    pub volume: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PlaybackStatus {
+    Playing,
+    Paused,
+    Stopped,
+}
+
+/// Something to do with the media player with the given id.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlayerCommand {
+    pub player: String,
+    pub action: PlayerAction,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum PlayerAction {
+    PlayPause,
+    Pause,
+    Next,
+    Previous,
+    /// Move forward by the given number of microseconds, or backward if it is negative
+    Seek(i64),
+    /// Set the volume, from 0 to 1
+    SetVolume(f64),
+}
 
 #[component]
 pub fn Playback() -> Element {
     rsx! {
+        NowPlaying {}
+        SleepTimer {}
         div { id: "group-info",
             div { id: "is-playing", "Not Playing" }
             div { id: "progress-text" }
@@ -17,14 +73,7 @@ pub fn Playback() -> Element {
             button { class: "small", id: "skip-forward", ">" }
             button { class: "small", id: "exit", "Exit" }
         }
-        div { id: "group-volume",
-            div { class: "title",
-                "Volume "
-                span { id: "volume-text", "0" }
-                "%"
-            }
-            input { id: "volume", r#type: "range" }
-        }
+        VolumeControl {}
         div { id: "group-sub-delay",
             div { class: "title",
                 "\r\n                Subtitle Delay "
@@ -0,0 +1,167 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use dioxus::{
+    fullstack::{WebSocketOptions, use_websocket},
+    prelude::*,
+};
+
+use super::{MediaPlayer, PlaybackStatus, PlayerAction, PlayerCommand};
+use crate::backend::playback::players;
+
+/// How far the seek buttons jump, in microseconds.
+const SEEK_STEP_US: i64 = 10_000_000;
+
+/// Shows what the media players on the media PC are playing, and lets the user control them.
+#[component]
+pub fn NowPlaying() -> Element {
+    let socket = use_websocket(|| players(WebSocketOptions::new()));
+    let mut list = use_signal(Vec::<MediaPlayer>::new);
+
+    use_future(move || async move {
+        loop {
+            match socket.recv().await {
+                Ok(new_list) => *list.write() = new_list,
+                Err(err) => {
+                    warn!("socket.recv() returned an error: {err}");
+                    return;
+                }
+            }
+        }
+    });
+
+    let send = move |player: String, action: PlayerAction| async move {
+        if let Err(err) = socket.send(PlayerCommand { player, action }).await {
+            warn!("Failed to send player command to socket: {}", err);
+        }
+    };
+
+    if list.read().is_empty() {
+        return rsx! {};
+    }
+
+    rsx! {
+        div { id: "now-playing",
+            div { class: "title", "Now playing" }
+            for player in list() {
+                div { class: "player", key: "{player.id}",
+                    if let Some(url) = artwork_url(&player) {
+                        img { class: "artwork", src: url }
+                    }
+                    div { class: "player-info",
+                        div { class: "player-name", "{player.name}" }
+                        div { class: "track-title",
+                            {player.title.clone().unwrap_or_else(|| "Unknown track".to_owned())}
+                        }
+                        if !player.artists.is_empty() {
+                            div { class: "track-artist", {player.artists.join(", ")} }
+                        }
+                        if let Some(album) = &player.album {
+                            div { class: "track-album", "{album}" }
+                        }
+                        if let Some(position) = player.position_us {
+                            div { class: "track-time", {format_time(position, player.length_us)} }
+                        }
+                    }
+                    div { class: "player-buttons",
+                        button {
+                            class: "small",
+                            onclick: {
+                                let id = player.id.clone();
+                                move |_| send(id.clone(), PlayerAction::Previous)
+                            },
+                            "⏮"
+                        }
+                        button {
+                            class: "small",
+                            onclick: {
+                                let id = player.id.clone();
+                                move |_| send(id.clone(), PlayerAction::Seek(-SEEK_STEP_US))
+                            },
+                            "-10s"
+                        }
+                        button {
+                            class: "small",
+                            onclick: {
+                                let id = player.id.clone();
+                                move |_| send(id.clone(), PlayerAction::PlayPause)
+                            },
+                            if player.status == PlaybackStatus::Playing {
+                                "||"
+                            } else {
+                                "▶"
+                            }
+                        }
+                        button {
+                            class: "small",
+                            onclick: {
+                                let id = player.id.clone();
+                                move |_| send(id.clone(), PlayerAction::Seek(SEEK_STEP_US))
+                            },
+                            "+10s"
+                        }
+                        button {
+                            class: "small",
+                            onclick: {
+                                let id = player.id.clone();
+                                move |_| send(id.clone(), PlayerAction::Next)
+                            },
+                            "⏭"
+                        }
+                    }
+                    if let Some(volume) = player.volume {
+                        input {
+                            class: "player-volume",
+                            r#type: "range",
+                            min: 0,
+                            max: 100,
+                            value: (volume * 100.0).round(),
+                            onchange: {
+                                let id = player.id.clone();
+                                move |event: Event<FormData>| {
+                                    let volume = event.value().parse::<f64>().ok();
+                                    let id = id.clone();
+                                    async move {
+                                        if let Some(volume) = volume {
+                                            send(id, PlayerAction::SetVolume(volume / 100.0)).await;
+                                        }
+                                    }
+                                }
+                            },
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Get a URL the browser can load the artwork of a player from.
+fn artwork_url(player: &MediaPlayer) -> Option<String> {
+    let url = player.art_url.as_deref()?;
+    if url.starts_with("file://") {
+        // The URL of the endpoint stays the same, so make sure new artwork is not taken from the cache
+        let mut hasher = DefaultHasher::new();
+        url.hash(&mut hasher);
+        let version = hasher.finish();
+        Some(format!(
+            "/api/playback/art?player={}&version={version:x}",
+            player.id
+        ))
+    } else if url.starts_with("http://") || url.starts_with("https://") {
+        Some(url.to_owned())
+    } else {
+        None
+    }
+}
+
+/// Format a position like `1:23 / 4:56`.
+fn format_time(position_us: i64, length_us: Option<i64>) -> String {
+    let format = |us: i64| {
+        let secs = us.max(0) / 1_000_000;
+        format!("{}:{:02}", secs / 60, secs % 60)
+    };
+    match length_us {
+        Some(length) => format!("{} / {}", format(position_us), format(length)),
+        None => format(position_us),
+    }
+}
@@ -0,0 +1,77 @@
+use dioxus::prelude::*;
+
+use super::{ClipboardRequest, EqWebsocket, Interaction};
+
+/// Copies the text of `#remote-clipboard` to the clipboard of the phone.
+///
+/// The clipboard API is only available over HTTPS or on localhost,
+/// so we fall back to the deprecated `execCommand` elsewhere.
+const COPY_JS: &str = r#"
+const text = document.getElementById("remote-clipboard");
+if (navigator.clipboard) {
+    navigator.clipboard.writeText(text.value);
+} else {
+    text.select();
+    document.execCommand("copy");
+}
+"#;
+
+/// Moves text between the clipboard of the phone and the media PC.
+#[component]
+pub fn Clipboard(
+    socket: EqWebsocket,
+    clipboard: Signal<Option<Result<String, String>>>,
+) -> Element {
+    let mut input = use_signal(String::new);
+
+    let send = move |paste: bool| async move {
+        let request = ClipboardRequest::Set {
+            text: input(),
+            paste,
+        };
+        if let Err(err) = socket.send(Interaction::Clipboard(request)).await {
+            warn!("Failed to send clipboard to socket: {}", err);
+        }
+    };
+
+    rsx! {
+        details { id: "clipboard",
+            summary { "Clipboard" }
+            div {
+                textarea {
+                    placeholder: "Text to send to the media PC",
+                    oninput: move |event| *input.write() = event.value(),
+                    value: input,
+                }
+                button { onclick: move |_| send(false), "Send" }
+                button { onclick: move |_| send(true), "Send and paste" }
+            }
+            div {
+                button {
+                    onclick: move |_| async move {
+                        let request = Interaction::Clipboard(ClipboardRequest::Get);
+                        if let Err(err) = socket.send(request).await {
+                            warn!("Failed to send clipboard request to socket: {}", err);
+                        }
+                    },
+                    "Get from media PC"
+                }
+                match clipboard() {
+                    Some(Ok(text)) => rsx! {
+                        textarea { id: "remote-clipboard", readonly: true, value: text }
+                        button {
+                            onclick: move |_| {
+                                document::eval(COPY_JS);
+                            },
+                            "Copy"
+                        }
+                    },
+                    Some(Err(err)) => rsx! {
+                        p { "Failed to read the clipboard: {err}" }
+                    },
+                    None => rsx! {},
+                }
+            }
+        }
+    }
+}
@@ -1,9 +1,21 @@
 use dioxus::prelude::*;
 
-use crate::frontend::remote::Key;
+use crate::frontend::{Route, remote::Key};
 
 use super::{EqWebsocket, Interaction};
 
+/// Media keys, which control whatever is playing on the media PC, like videos in a browser.
+const MEDIA_KEYS: [(&str, Key); 8] = [
+    ("⏮", Key::AudioPrev),
+    ("⏯", Key::AudioPlay),
+    ("⏭", Key::AudioNext),
+    ("Vol −", Key::AudioLowerVolume),
+    ("Vol +", Key::AudioRaiseVolume),
+    ("Mute", Key::AudioMute),
+    ("Back", Key::Back),
+    ("Forward", Key::Forward),
+];
+
 #[component]
 pub fn Controls(socket: EqWebsocket) -> Element {
     let mut input = use_signal(String::new);
@@ -60,6 +72,22 @@ pub fn Controls(socket: EqWebsocket) -> Element {
                 },
                 "Escape"
             }
+            Link { id: "dpad-link", to: Route::Dpad {}, "D-pad" }
+            div { id: "media-keys",
+                for (label , key) in MEDIA_KEYS {
+                    button {
+                        onclick: move |_| {
+                            let key = key.clone();
+                            async move {
+                                if let Err(err) = socket.send(Interaction::Key(key.clone())).await {
+                                    warn!("Failed to send {:?} to socket: {}", key, err);
+                                }
+                            }
+                        },
+                        "{label}"
+                    }
+                }
+            }
         }
     }
 }
@@ -0,0 +1,90 @@
+use dioxus::{
+    fullstack::{WebSocketOptions, use_websocket},
+    prelude::*,
+};
+
+use super::{CSS, EqWebsocket, Interaction, Key, RemoteEvent, SessionStatus, Status};
+use crate::{backend::remote::interaction, frontend::Route};
+
+/// Gives a short buzz on phones that support it, so presses can be felt without looking.
+const VIBRATE_JS: &str = "if (navigator.vibrate) { navigator.vibrate(15); }";
+
+/// A TV remote for media centers, which sends keys without streaming the screen.
+#[component]
+pub fn Dpad() -> Element {
+    let socket = use_websocket(|| interaction(WebSocketOptions::new()));
+    let mut status = use_signal(|| Option::<SessionStatus>::None);
+
+    use_future(move || async move {
+        loop {
+            match socket.recv().await {
+                Ok(RemoteEvent::Status(new_status)) => *status.write() = Some(new_status),
+                // There is no pointer or clipboard to show here
+                Ok(_) => {}
+                Err(err) => {
+                    warn!("socket.recv() returned an error: {err}");
+                    return;
+                }
+            }
+        }
+    });
+
+    let socket = EqWebsocket::new(socket);
+    rsx! {
+        document::Stylesheet { href: CSS }
+        Status { status }
+        div { id: "dpad",
+            div { class: "dpad-row",
+                KeyButton { socket, key: Key::HomePage, label: "Home" }
+                KeyButton { socket, key: Key::ArrowUp, label: "▲" }
+                KeyButton { socket, key: Key::Escape, label: "Back" }
+            }
+            div { class: "dpad-row",
+                KeyButton { socket, key: Key::ArrowLeft, label: "◀" }
+                KeyButton { socket, key: Key::Enter, label: "OK", class: "dpad-ok" }
+                KeyButton { socket, key: Key::ArrowRight, label: "▶" }
+            }
+            div { class: "dpad-row",
+                KeyButton { socket, key: Key::AudioLowerVolume, label: "Vol −" }
+                KeyButton { socket, key: Key::ArrowDown, label: "▼" }
+                KeyButton { socket, key: Key::AudioRaiseVolume, label: "Vol +" }
+            }
+            div { class: "dpad-row",
+                KeyButton { socket, key: Key::AudioPrev, label: "⏮" }
+                KeyButton { socket, key: Key::AudioPlay, label: "⏯" }
+                KeyButton { socket, key: Key::AudioNext, label: "⏭" }
+                KeyButton { socket, key: Key::AudioMute, label: "Mute" }
+            }
+            Link {
+                to: Route::Remote {
+                    transport: Default::default(),
+                },
+                "Show the screen"
+            }
+        }
+    }
+}
+
+#[component]
+fn KeyButton(
+    socket: EqWebsocket,
+    key: Key,
+    label: &'static str,
+    #[props(default)] class: &'static str,
+) -> Element {
+    rsx! {
+        button {
+            class: "dpad-key {class}",
+            onclick: move |_| {
+                let key = key.clone();
+                async move {
+                    document::eval(VIBRATE_JS);
+                    if let Err(err) = socket.send(Interaction::Key(key.clone())).await {
+                        warn!("Failed to send {:?} to socket: {}", key, err);
+                    }
+                }
+            },
+            "{label}"
+        }
+    }
+}
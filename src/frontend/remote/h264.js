@@ -0,0 +1,57 @@
+// Expects `url` to be defined, see `START_H264_JS` in screen.rs.
+// The message format is described in backend/remote/linux/h264.rs.
+window.empcScreenSocket?.close();
+window.empcScreenSocket = null;
+
+const canvas = document.getElementById("screen-canvas");
+const context = canvas.getContext("2d");
+
+if (typeof VideoDecoder === "undefined") {
+    // WebCodecs is only available over HTTPS or on localhost
+    console.warn("WebCodecs is not available, can't decode H.264");
+    canvas.width = 640;
+    canvas.height = 360;
+    context.fillStyle = "white";
+    context.font = "20px sans-serif";
+    context.textAlign = "center";
+    context.fillText("H.264 is not supported by this browser or connection", 320, 180);
+} else {
+    const protocol = location.protocol === "https:" ? "wss:" : "ws:";
+    const socket = new WebSocket(`${protocol}//${location.host}${url}`);
+    socket.binaryType = "arraybuffer";
+    window.empcScreenSocket = socket;
+
+    const decoder = new VideoDecoder({
+        output: (frame) => {
+            if (canvas.width !== frame.displayWidth || canvas.height !== frame.displayHeight) {
+                canvas.width = frame.displayWidth;
+                canvas.height = frame.displayHeight;
+            }
+            context.drawImage(frame, 0, 0);
+            frame.close();
+        },
+        error: (err) => {
+            console.warn("Decoding H.264 failed", err);
+            socket.close();
+        },
+    });
+    // Without a description, the decoder expects Annex-B NAL units
+    decoder.configure({ codec: "avc1.42E034", optimizeForLatency: true });
+
+    let timestamp = 0;
+    socket.onmessage = (event) => {
+        const data = new Uint8Array(event.data);
+        decoder.decode(
+            new EncodedVideoChunk({
+                type: data[0] === 1 ? "key" : "delta",
+                timestamp: timestamp++,
+                data: data.subarray(1),
+            }),
+        );
+    };
+    socket.onclose = () => {
+        if (decoder.state !== "closed") {
+            decoder.close();
+        }
+    };
+}
@@ -1,5 +1,9 @@
+mod clipboard;
 mod controls;
+mod dpad;
 mod screen;
+mod settings;
+mod status;
 mod utils;
 
 use dioxus::{
@@ -12,10 +16,17 @@ use dioxus::{
 };
 use serde::{Deserialize, Serialize};
 
-use crate::backend::remote::interaction;
+use crate::{
+    backend::{config::screencast_defaults, remote::interaction},
+    frontend::audio::VolumeControl,
+};
 
+use clipboard::Clipboard;
 use controls::Controls;
+pub use dpad::Dpad;
 use screen::Screen;
+use settings::Settings;
+use status::Status;
 use utils::EqWebsocket;
 
 static CSS: Asset = asset!("/assets/remote.css");
@@ -28,6 +39,16 @@ pub enum Interaction {
     Scroll(f32),
     Text(String),
     Key(Key),
+    Clipboard(ClipboardRequest),
+}
+
+/// What to do with the clipboard of the media PC.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ClipboardRequest {
+    /// Put text on the clipboard, and paste it with Ctrl+V if `paste` is set.
+    Set { text: String, paste: bool },
+    /// Send the text on the clipboard back as [`RemoteEvent::Clipboard`].
+    Get,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
@@ -72,22 +93,244 @@ impl RelativePosition {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RelativeSize {
+    pub width: f32,
+    pub height: f32,
+}
+
+impl RelativeSize {
+    pub fn into_absolute_size(self, size: Size2D<f64, Pixels>) -> Size2D<f64, Pixels> {
+        Size2D::new(
+            f64::from(self.width) * size.width,
+            f64::from(self.height) * size.height,
+        )
+    }
+}
+
+/// Messages sent from the backend to the remote page.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum RemoteEvent {
+    /// The remote pointer moved to the given position.
+    CursorPosition(RelativePosition),
+    /// The remote pointer is not visible on the screen.
+    CursorHidden,
+    /// The remote pointer changed its appearance.
+    CursorShape(CursorShape),
+    /// The remote desktop session changed its state.
+    Status(SessionStatus),
+    /// The text on the clipboard, as asked for with [`ClipboardRequest::Get`],
+    /// or why it could not be read.
+    Clipboard(Result<String, String>),
+}
+
+/// The state of the remote desktop session on the media PC.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SessionStatus {
+    /// Nobody uses the remote, so the session is stopped
+    Idle,
+    /// The user has to allow controlling the desktop before the session can start
+    PermissionRequired,
+    /// The media PC shows a dialog asking whether the desktop may be controlled
+    WaitingForPermission,
+    /// The session is being set up, which can take a few seconds
+    Starting,
+    Running,
+    /// The session broke and will be started again
+    Reconnecting {
+        error: String,
+        retry_in_secs: u64,
+    },
+    /// The media PC has no way to share its screen
+    Unsupported,
+}
+
+/// Describes the image of the remote pointer, which can be fetched from `/api/remote/cursor`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CursorShape {
+    /// Changes every time the cursor image changes
+    pub serial: u64,
+    /// Size of the cursor image relative to the screen
+    pub size: RelativeSize,
+    /// Offset from the top left corner of the image to the pointer position, relative to the screen
+    pub hotspot: RelativePosition,
+}
+
+/// A monitor shared by the remote desktop session, which can be streamed and controlled.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Monitor {
+    /// Identifies the monitor within the session, like the PipeWire node id of its stream
+    pub id: u32,
+    /// Position of the monitor in the compositor's logical coordinate space
+    pub position: Option<(i32, i32)>,
+    /// Size of the monitor in logical pixels
+    pub size: Option<(i32, i32)>,
+    /// Whether this is the monitor currently being streamed
+    pub selected: bool,
+}
+
+/// How the screencast should be encoded. Clients with the same settings share the encoded frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScreencastSettings {
+    /// JPEG quality, from 1 to 100
+    pub quality: u8,
+    /// Frames wider than this are scaled down
+    pub max_width: Option<u32>,
+    /// The maximum number of frames per second
+    pub max_fps: Option<u32>,
+    /// Lower the quality and frame rate when the connection can't keep up
+    pub adaptive: bool,
+}
+
+impl ScreencastSettings {
+    /// Check settings that came from a query string or the configuration file.
+    #[cfg(feature = "server")]
+    pub fn check(&self) -> Result<(), String> {
+        if !(1..=100).contains(&self.quality) {
+            return Err(format!(
+                "quality must be from 1 to 100, not {}",
+                self.quality
+            ));
+        }
+        if self.max_fps == Some(0) {
+            return Err("max_fps must be at least 1".to_owned());
+        }
+        Ok(())
+    }
+}
+
+impl Default for ScreencastSettings {
+    fn default() -> Self {
+        Self {
+            quality: 70,
+            max_width: None,
+            max_fps: None,
+            adaptive: true,
+        }
+    }
+}
+
+/// How the screen is sent to the browser, chosen with the `transport` query parameter of the Remote page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Transport {
+    /// A multipart stream of JPEG images, shown in an `img`
+    #[default]
+    Mjpeg,
+    /// Only the changed tiles of each frame over a websocket, drawn on a `canvas`
+    Tiles,
+    /// H.264 over a websocket, decoded with WebCodecs and drawn on a `canvas`
+    H264,
+}
+
+impl std::fmt::Display for Transport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Mjpeg => "mjpeg",
+            Self::Tiles => "tiles",
+            Self::H264 => "h264",
+        })
+    }
+}
+
+impl std::str::FromStr for Transport {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mjpeg" => Ok(Self::Mjpeg),
+            "tiles" => Ok(Self::Tiles),
+            "h264" => Ok(Self::H264),
+            _ => anyhow::bail!("Unknown transport: {s}"),
+        }
+    }
+}
+
+impl ScreencastSettings {
+    /// Get the URL of a screencast with these settings.
+    pub fn url(&self) -> String {
+        format!("/api/remote/screencast?{}", self.query())
+    }
+
+    /// Get the URL of a tile stream with these settings.
+    pub fn tiles_url(&self) -> String {
+        format!("/api/remote/tiles?{}", self.query())
+    }
+
+    /// Get the URL of an H.264 stream with these settings.
+    pub fn h264_url(&self) -> String {
+        format!("/api/remote/h264?{}", self.query())
+    }
+
+    fn query(&self) -> String {
+        let mut query = format!("quality={}&adaptive={}", self.quality, self.adaptive);
+        if let Some(max_width) = self.max_width {
+            query += &format!("&max_width={max_width}");
+        }
+        if let Some(max_fps) = self.max_fps {
+            query += &format!("&max_fps={max_fps}");
+        }
+        query
+    }
+
+    /// Clamp the settings to sensible values, so equivalent settings compare equal.
+    #[cfg(feature = "server")]
+    pub fn normalized(self) -> Self {
+        Self {
+            quality: self.quality.clamp(1, 100),
+            max_width: self.max_width.filter(|&max_width| max_width != 0),
+            max_fps: self.max_fps.filter(|&max_fps| max_fps != 0),
+            adaptive: self.adaptive,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Key {
     Backspace,
     Escape,
     Space,
+    Enter,
+    ArrowUp,
+    ArrowDown,
+    ArrowLeft,
+    ArrowRight,
+    /// The XF86 home page key, which opens the home screen of media centers
+    HomePage,
+    AudioPlay,
+    AudioPrev,
+    AudioNext,
+    AudioRaiseVolume,
+    AudioLowerVolume,
+    AudioMute,
+    /// The XF86 back key, which browsers and file managers understand
+    Back,
+    Forward,
 }
 
 #[component]
-pub fn Remote() -> Element {
+pub fn Remote(transport: Transport) -> Element {
     let socket = use_websocket(|| interaction(WebSocketOptions::new()));
+    let defaults = use_server_future(|| async { screencast_defaults().await.ok() })?;
+    let settings = use_signal(move || defaults().flatten().unwrap_or_default());
+    let status = use_signal(|| Option::<SessionStatus>::None);
+    let clipboard = use_signal(|| Option::<Result<String, String>>::None);
 
     rsx! {
         document::Stylesheet { href: CSS }
         div { id: "content",
-            Screen { socket: EqWebsocket::new(socket) }
+            Status { status }
+            Screen {
+                socket: EqWebsocket::new(socket),
+                settings,
+                transport,
+                status,
+                clipboard,
+            }
             Controls { socket: EqWebsocket::new(socket) }
+            Clipboard { socket: EqWebsocket::new(socket), clipboard }
+            VolumeControl {}
+            Settings { settings, transport }
         }
     }
 }
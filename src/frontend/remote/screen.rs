@@ -3,17 +3,54 @@ use dioxus::{
     prelude::*,
 };
 
-use super::{EqWebsocket, Interaction, MouseButton, RelativePosition, WheelDelta};
+use super::{
+    CursorShape, EqWebsocket, Interaction, MouseButton, RelativePosition, RemoteEvent,
+    ScreencastSettings, SessionStatus, Transport, WheelDelta,
+};
 
 static CURSOR: Asset = asset!("/assets/cursor.png");
 
+/// Draws the tile stream at `url` onto `#screen-canvas`, replacing any previous websocket stream.
+const START_TILES_JS: &str = include_str!("tiles.js");
+
+/// Draws the H.264 stream at `url` onto `#screen-canvas`, replacing any previous websocket stream.
+const START_H264_JS: &str = include_str!("h264.js");
+
+/// Closes the websocket stream started by [`START_TILES_JS`] or [`START_H264_JS`], if any.
+const STOP_STREAM_JS: &str = "window.empcScreenSocket?.close(); window.empcScreenSocket = null;";
+
+/// Height of [`CURSOR`] in pixels, used until the backend tells us what the real cursor looks like.
+const DEFAULT_CURSOR_HEIGHT: f64 = 24.0;
+
 #[component]
-pub fn Screen(socket: EqWebsocket) -> Element {
+pub fn Screen(
+    socket: EqWebsocket,
+    settings: Signal<ScreencastSettings>,
+    transport: ReadSignal<Transport>,
+    mut status: Signal<Option<SessionStatus>>,
+    mut clipboard: Signal<Option<Result<String, String>>>,
+) -> Element {
     let mut screen_size = use_signal(|| Option::<Size2D<f64, Pixels>>::None);
 
-    // update cursor position based on data we get from the backend
-    let mut cursor_top = use_signal(|| "0px".to_owned());
-    let mut cursor_left = use_signal(|| "0px".to_owned());
+    // websocket streams are drawn by javascript, as the canvas API is not available from rust
+    use_effect(move || {
+        let (url, script) = match transport() {
+            Transport::Mjpeg => {
+                document::eval(STOP_STREAM_JS);
+                return;
+            }
+            Transport::Tiles => (settings().tiles_url(), START_TILES_JS),
+            Transport::H264 => (settings().h264_url(), START_H264_JS),
+        };
+        document::eval(&format!("const url = {url:?};\n{script}"));
+    });
+    use_drop(|| {
+        document::eval(STOP_STREAM_JS);
+    });
+
+    // update cursor based on data we get from the backend
+    let mut cursor_position = use_signal(|| Option::<RelativePosition>::None);
+    let mut cursor_shape = use_signal(|| Option::<CursorShape>::None);
     use_future(move || async move {
         loop {
             let message = match socket.recv().await {
@@ -24,16 +61,42 @@ pub fn Screen(socket: EqWebsocket) -> Element {
                 }
             };
 
-            let Some(size) = screen_size() else {
-                warn!("Screen size not set, ignoring mouse message from backend");
-                continue;
-            };
-            let absolute_position = message.into_absolute_position(size);
-            *cursor_left.write() = format!("{}px", absolute_position.x);
-            *cursor_top.write() = format!("{}px", absolute_position.y);
+            match message {
+                RemoteEvent::CursorPosition(position) => *cursor_position.write() = Some(position),
+                RemoteEvent::CursorHidden => *cursor_position.write() = None,
+                RemoteEvent::CursorShape(shape) => *cursor_shape.write() = Some(shape),
+                RemoteEvent::Status(new_status) => *status.write() = Some(new_status),
+                RemoteEvent::Clipboard(text) => *clipboard.write() = Some(text),
+            }
         }
     });
 
+    let cursor = match (screen_size(), cursor_position()) {
+        (Some(size), Some(position)) => {
+            let position = position.into_absolute_position(size);
+            Some(match cursor_shape() {
+                Some(shape) => {
+                    // The backend reports the position of the hotspot, so we offset the image by it
+                    let hotspot = shape.hotspot.into_absolute_position(size);
+                    let height = shape.size.into_absolute_size(size).height;
+                    (
+                        position.x - hotspot.x,
+                        position.y - hotspot.y,
+                        height,
+                        format!("/api/remote/cursor?serial={}", shape.serial),
+                    )
+                }
+                None => (
+                    position.x,
+                    position.y,
+                    DEFAULT_CURSOR_HEIGHT,
+                    CURSOR.to_string(),
+                ),
+            })
+        }
+        _ => None,
+    };
+
     rsx! {
         div {
             id: "screen",
@@ -83,7 +146,7 @@ pub fn Screen(socket: EqWebsocket) -> Element {
             onmouseup: move |event| async move {
                 if let Some(button) = event.data.trigger_button()
                     && let Ok(button) = MouseButton::try_from(button)
-                    && let Err(err) = socket.send(Interaction::MouseDown(button)).await
+                    && let Err(err) = socket.send(Interaction::MouseUp(button)).await
                 {
                     warn!("Failed to send {:?} to socket: {}", event, err);
                 }
@@ -108,12 +171,22 @@ pub fn Screen(socket: EqWebsocket) -> Element {
             // ontouchmove: |event| info!("{:?}", event.data),
             // ontouchstart: |event| info!("{:?}", event.data),
             // ontouchend: |event| info!("{:?}", event.data),
-            img { id: "screen-img", src: "/api/remote/screencast" }
-            img {
-                id: "screen-cursor",
-                src: CURSOR,
-                top: cursor_top,
-                left: cursor_left,
+            match transport() {
+                Transport::Mjpeg => rsx! {
+                    img { id: "screen-img", src: settings().url() }
+                },
+                Transport::Tiles | Transport::H264 => rsx! {
+                    canvas { id: "screen-canvas" }
+                },
+            }
+            if let Some((left, top, height, src)) = cursor {
+                img {
+                    id: "screen-cursor",
+                    src,
+                    top: "{top}px",
+                    left: "{left}px",
+                    height: "{height:.0}",
+                }
             }
         }
     }
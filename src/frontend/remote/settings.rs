@@ -0,0 +1,174 @@
+use dioxus::prelude::*;
+
+use super::{Monitor, ScreencastSettings, Transport};
+use crate::{
+    backend::remote::{monitors, revoke_permission, select_monitor},
+    frontend::Route,
+};
+
+const TRANSPORTS: [(&str, Transport); 3] = [
+    ("MJPEG", Transport::Mjpeg),
+    ("Changed tiles", Transport::Tiles),
+    ("H.264", Transport::H264),
+];
+
+const QUALITIES: [(&str, u8); 4] = [("Low", 30), ("Medium", 50), ("High", 70), ("Best", 90)];
+const MAX_WIDTHS: [(&str, u32); 5] = [
+    ("Full", 0),
+    ("1920px", 1920),
+    ("1280px", 1280),
+    ("960px", 960),
+    ("640px", 640),
+];
+const MAX_FPS: [(&str, u32); 5] = [
+    ("Unlimited", 0),
+    ("30 fps", 30),
+    ("20 fps", 20),
+    ("10 fps", 10),
+    ("5 fps", 5),
+];
+
+#[component]
+pub fn Settings(settings: Signal<ScreencastSettings>, transport: Transport) -> Element {
+    let current = settings();
+
+    rsx! {
+        details { id: "settings",
+            summary { "Stream settings" }
+            MonitorSelect {}
+            label {
+                "Transport "
+                select {
+                    onchange: move |event| {
+                        if let Ok(transport) = event.value().parse() {
+                            navigator().replace(Route::Remote { transport });
+                        }
+                    },
+                    for (name , option_transport) in TRANSPORTS {
+                        option {
+                            value: "{option_transport}",
+                            selected: transport == option_transport,
+                            "{name}"
+                        }
+                    }
+                }
+            }
+            label {
+                "Quality "
+                select {
+                    onchange: move |event| {
+                        if let Ok(quality) = event.value().parse() {
+                            settings.write().quality = quality;
+                        }
+                    },
+                    for (name , quality) in QUALITIES {
+                        option {
+                            value: "{quality}",
+                            selected: current.quality == quality,
+                            "{name}"
+                        }
+                    }
+                }
+            }
+            label {
+                "Resolution "
+                select {
+                    onchange: move |event| {
+                        if let Ok(max_width) = event.value().parse::<u32>() {
+                            settings.write().max_width = Some(max_width).filter(|&width| width != 0);
+                        }
+                    },
+                    for (name , max_width) in MAX_WIDTHS {
+                        option {
+                            value: "{max_width}",
+                            selected: current.max_width.unwrap_or_default() == max_width,
+                            "{name}"
+                        }
+                    }
+                }
+            }
+            label {
+                "Frame rate "
+                select {
+                    onchange: move |event| {
+                        if let Ok(max_fps) = event.value().parse::<u32>() {
+                            settings.write().max_fps = Some(max_fps).filter(|&fps| fps != 0);
+                        }
+                    },
+                    for (name , max_fps) in MAX_FPS {
+                        option {
+                            value: "{max_fps}",
+                            selected: current.max_fps.unwrap_or_default() == max_fps,
+                            "{name}"
+                        }
+                    }
+                }
+            }
+            label {
+                input {
+                    r#type: "checkbox",
+                    checked: current.adaptive,
+                    onchange: move |event| {
+                        settings.write().adaptive = event.checked();
+                    },
+                }
+                " Adapt to connection"
+            }
+            button {
+                onclick: move |_| async move {
+                    if let Err(err) = revoke_permission().await {
+                        warn!("Failed to revoke permission: {}", err);
+                    }
+                },
+                "Forget screen sharing permission"
+            }
+        }
+    }
+}
+
+/// Lets the user switch between monitors, if more than one is shared.
+#[component]
+fn MonitorSelect() -> Element {
+    let mut monitors = use_resource(monitors);
+    let Some(Ok(list)) = monitors.value().read_unchecked().cloned() else {
+        return rsx! {};
+    };
+    if list.len() < 2 {
+        return rsx! {};
+    }
+
+    rsx! {
+        label {
+            "Monitor "
+            select {
+                onchange: move |event| async move {
+                    if let Ok(id) = event.value().parse() {
+                        if let Err(err) = select_monitor(id).await {
+                            warn!("Failed to select monitor {}: {}", id, err);
+                        }
+                        monitors.restart();
+                    }
+                },
+                for (index , monitor) in list.into_iter().enumerate() {
+                    option {
+                        value: "{monitor.id}",
+                        selected: monitor.selected,
+                        {monitor_name(index, &monitor)}
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn monitor_name(index: usize, monitor: &Monitor) -> String {
+    let mut name = format!("{}", index + 1);
+    if let Some((width, height)) = monitor.size {
+        name += &format!(" ({width}×{height}");
+        if let Some((x, y)) = monitor.position {
+            name += &format!(" at {x}, {y}");
+        }
+        name += ")";
+    }
+    name
+}
@@ -0,0 +1,46 @@
+use dioxus::prelude::*;
+
+use super::SessionStatus;
+use crate::backend::remote::request_permission;
+
+/// Tells the user what the remote desktop session is doing, unless it is running normally.
+#[component]
+pub fn Status(status: Signal<Option<SessionStatus>>) -> Element {
+    let message = match status() {
+        None => "Connecting to the media PC...".to_owned(),
+        Some(SessionStatus::Idle) => "The remote desktop session is stopped".to_owned(),
+        Some(SessionStatus::PermissionRequired) => {
+            return rsx! {
+                div { id: "status",
+                    "The media PC needs permission to share its screen. "
+                    button {
+                        onclick: move |_| async move {
+                            if let Err(err) = request_permission().await {
+                                warn!("Failed to ask for permission: {}", err);
+                            }
+                        },
+                        "Ask for permission"
+                    }
+                }
+            };
+        }
+        Some(SessionStatus::WaitingForPermission) => {
+            "Allow screen sharing in the dialog on the media PC".to_owned()
+        }
+        Some(SessionStatus::Starting) => "Starting the remote desktop session...".to_owned(),
+        Some(SessionStatus::Running) => return rsx! {},
+        Some(SessionStatus::Reconnecting {
+            error,
+            retry_in_secs,
+        }) => {
+            format!("The remote desktop session failed: {error}. Retrying in {retry_in_secs}s...")
+        }
+        Some(SessionStatus::Unsupported) => {
+            "The media PC does not support remote control".to_owned()
+        }
+    };
+
+    rsx! {
+        div { id: "status", "{message}" }
+    }
+}
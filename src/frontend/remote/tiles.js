@@ -0,0 +1,50 @@
+// Expects `url` to be defined, see `START_TILES_JS` in screen.rs.
+// The message format is described in backend/remote/linux/tiles.rs.
+window.empcScreenSocket?.close();
+
+const protocol = location.protocol === "https:" ? "wss:" : "ws:";
+const socket = new WebSocket(`${protocol}//${location.host}${url}`);
+socket.binaryType = "arraybuffer";
+window.empcScreenSocket = socket;
+
+async function drawTiles(buffer) {
+    const canvas = document.getElementById("screen-canvas");
+    if (!canvas) {
+        return;
+    }
+    const context = canvas.getContext("2d");
+
+    const view = new DataView(buffer);
+    const width = view.getUint16(0, true);
+    const height = view.getUint16(2, true);
+    const count = view.getUint16(4, true);
+
+    const tiles = [];
+    let offset = 6;
+    for (let i = 0; i < count; i++) {
+        const x = view.getUint16(offset, true);
+        const y = view.getUint16(offset + 2, true);
+        const length = view.getUint32(offset + 8, true);
+        offset += 12;
+
+        const jpeg = new Blob([new Uint8Array(buffer, offset, length)], { type: "image/jpeg" });
+        tiles.push(createImageBitmap(jpeg).then((bitmap) => ({ x, y, bitmap })));
+        offset += length;
+    }
+
+    // Resizing clears the canvas, but the server sends every tile when the size changes
+    if (canvas.width !== width || canvas.height !== height) {
+        canvas.width = width;
+        canvas.height = height;
+    }
+    for (const { x, y, bitmap } of await Promise.all(tiles)) {
+        context.drawImage(bitmap, x, y);
+        bitmap.close();
+    }
+}
+
+// Draw the messages in order, so older tiles never cover newer ones
+let drawing = Promise.resolve();
+socket.onmessage = (event) => {
+    drawing = drawing.then(() => drawTiles(event.data)).catch((err) => console.warn(err));
+};
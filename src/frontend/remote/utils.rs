@@ -2,9 +2,9 @@ use std::ops::{Deref, DerefMut};
 
 use dioxus_fullstack::{PostcardEncoding, UseWebsocket};
 
-use super::{Interaction, RelativePosition};
+use super::{Interaction, RemoteEvent};
 
-type UseWebsocketInstance = UseWebsocket<Interaction, RelativePosition, PostcardEncoding>;
+type UseWebsocketInstance = UseWebsocket<Interaction, RemoteEvent, PostcardEncoding>;
 
 #[derive(Clone, Copy)]
 /// A [`UseWebsocket`] that implements [`PartialEq`].
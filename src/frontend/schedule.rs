@@ -0,0 +1,233 @@
+use std::fmt;
+
+use dioxus::{
+    fullstack::{WebSocketOptions, use_websocket},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    backend::{auth::current_role, schedule::schedule},
+    frontend::{devices::Role, shutdown::PowerAction},
+};
+
+const TIMER_MINUTES: [u32; 5] = [15, 30, 45, 60, 90];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ScheduledAction {
+    /// Pause every media player
+    StopPlayback,
+    Power(PowerAction),
+}
+
+impl ScheduledAction {
+    const ALL: [ScheduledAction; 4] = [
+        ScheduledAction::StopPlayback,
+        ScheduledAction::Power(PowerAction::ScreenOff),
+        ScheduledAction::Power(PowerAction::Suspend),
+        ScheduledAction::Power(PowerAction::PowerOff),
+    ];
+
+    /// Playback may be stopped by anybody, but only admins may power off.
+    pub fn role(self) -> Role {
+        match self {
+            ScheduledAction::StopPlayback => Role::Guest,
+            ScheduledAction::Power(_) => Role::Admin,
+        }
+    }
+}
+
+impl fmt::Display for ScheduledAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScheduledAction::StopPlayback => f.write_str("Stop playback"),
+            ScheduledAction::Power(action) => action.fmt(f),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Trigger {
+    /// Once, at the given number of seconds since the unix epoch
+    At(u64),
+    /// When the player stops playing the track it was playing when this was scheduled
+    EndOfTrack {
+        player: String,
+        title: Option<String>,
+    },
+    /// Every day at the given local time
+    Daily {
+        hour: u8,
+        minute: u8,
+        /// The next time, in seconds since the unix epoch
+        next: u64,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Schedule {
+    pub id: u64,
+    pub action: ScheduledAction,
+    pub trigger: Trigger,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScheduleEntry {
+    pub schedule: Schedule,
+    /// Seconds until the action runs, if it is known
+    pub secs_left: Option<u64>,
+}
+
+/// When a new schedule should run, as chosen on a phone.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum When {
+    InMinutes(u32),
+    /// At the end of what is playing now
+    EndOfTrack,
+    Daily {
+        hour: u8,
+        minute: u8,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ScheduleCommand {
+    Add { action: ScheduledAction, when: When },
+    Cancel(u64),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ScheduleEvent {
+    /// Sent every second, so countdowns can be shown
+    Entries(Vec<ScheduleEntry>),
+    Error(String),
+}
+
+/// Shows the scheduled actions with their countdowns, and schedules new ones.
+#[component]
+pub fn SleepTimer() -> Element {
+    let socket = use_websocket(|| schedule(WebSocketOptions::new()));
+    let mut entries = use_signal(Vec::<ScheduleEntry>::new);
+    let mut error = use_signal(|| Option::<String>::None);
+    let mut action = use_signal(|| ScheduledAction::StopPlayback);
+    let mut daily_time = use_signal(|| "02:00".to_owned());
+    let role = use_resource(current_role);
+    let role = role.value().read_unchecked().clone().and_then(Result::ok);
+
+    use_future(move || async move {
+        loop {
+            match socket.recv().await {
+                Ok(ScheduleEvent::Entries(new_entries)) => *entries.write() = new_entries,
+                Ok(ScheduleEvent::Error(message)) => error.set(Some(message)),
+                Err(err) => {
+                    warn!("socket.recv() returned an error: {err}");
+                    return;
+                }
+            }
+        }
+    });
+
+    let send = move |command: ScheduleCommand| async move {
+        error.set(None);
+        if let Err(err) = socket.send(command).await {
+            warn!("Failed to send schedule command to socket: {}", err);
+        }
+    };
+    let add = move |when: When| {
+        send(ScheduleCommand::Add {
+            action: action(),
+            when,
+        })
+    };
+
+    rsx! {
+        div { id: "sleep-timer",
+            for entry in entries() {
+                div { class: "schedule", key: "{entry.schedule.id}",
+                    span { "{entry.schedule.action} {describe(&entry)}" }
+                    button {
+                        class: "small",
+                        onclick: move |_| send(ScheduleCommand::Cancel(entry.schedule.id)),
+                        "Cancel"
+                    }
+                }
+            }
+            details {
+                summary { "Sleep timer" }
+                select {
+                    onchange: move |event| {
+                        if let Ok(index) = event.value().parse::<usize>() {
+                            action.set(ScheduledAction::ALL[index]);
+                        }
+                    },
+                    for (index , option_action) in ScheduledAction::ALL.into_iter().enumerate() {
+                        if role.is_some_and(|role| role >= option_action.role()) {
+                            option {
+                                value: "{index}",
+                                selected: action() == option_action,
+                                "{option_action}"
+                            }
+                        }
+                    }
+                }
+                div { class: "timer-buttons",
+                    for minutes in TIMER_MINUTES {
+                        button { onclick: move |_| add(When::InMinutes(minutes)), "{minutes} min" }
+                    }
+                    button { onclick: move |_| add(When::EndOfTrack), "End of episode" }
+                }
+                div { class: "timer-daily",
+                    "Every day at "
+                    input {
+                        r#type: "time",
+                        value: daily_time,
+                        oninput: move |event| daily_time.set(event.value()),
+                    }
+                    button {
+                        onclick: move |_| async move {
+                            match parse_time(&daily_time()) {
+                                Some((hour, minute)) => add(When::Daily { hour, minute }).await,
+                                None => error.set(Some(format!("{} is not a time", daily_time()))),
+                            }
+                        },
+                        "Add"
+                    }
+                }
+            }
+            if let Some(error) = error() {
+                div { class: "error", "{error}" }
+            }
+        }
+    }
+}
+
+fn describe(entry: &ScheduleEntry) -> String {
+    let left = entry.secs_left.map(|secs| match secs {
+        0..3600 => format!("{}:{:02}", secs / 60, secs % 60),
+        _ => format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60),
+    });
+    match (&entry.schedule.trigger, left) {
+        (
+            Trigger::EndOfTrack {
+                title: Some(title), ..
+            },
+            _,
+        ) => format!("after {title}"),
+        (Trigger::EndOfTrack { title: None, .. }, _) => "at the end of the track".to_owned(),
+        (Trigger::Daily { hour, minute, .. }, Some(left)) => {
+            format!("every day at {hour:02}:{minute:02}, next in {left}")
+        }
+        (Trigger::Daily { hour, minute, .. }, None) => {
+            format!("every day at {hour:02}:{minute:02}")
+        }
+        (Trigger::At(_), Some(left)) => format!("in {left}"),
+        (Trigger::At(_), None) => "soon".to_owned(),
+    }
+}
+
+/// Parse the `HH:MM` value of a time input.
+fn parse_time(value: &str) -> Option<(u8, u8)> {
+    let (hour, minute) = value.split_once(':')?;
+    let (hour, minute) = (hour.parse().ok()?, minute.get(..2)?.parse().ok()?);
+    (hour < 24 && minute < 60).then_some((hour, minute))
+}
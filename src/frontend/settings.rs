@@ -0,0 +1,58 @@
+use dioxus::prelude::*;
+
+use crate::backend::config::{config_file, reload_config, save_config};
+
+/// Edits the configuration file, which is used as soon as it is saved.
+#[component]
+pub fn Settings() -> Element {
+    let mut text = use_signal(String::new);
+    let mut message = use_signal(|| Option::<Result<String, String>>::None);
+
+    let load = move || async move {
+        match config_file().await {
+            Ok(file) => text.set(file),
+            Err(err) => message.set(Some(Err(format!("Failed to read settings: {err}")))),
+        }
+    };
+    use_future(move || async move { load().await });
+
+    let save = move |_| async move {
+        match save_config(text()).await {
+            Ok(()) => message.set(Some(Ok("Saved, and using the new settings".to_owned()))),
+            Err(err) => message.set(Some(Err(err.to_string()))),
+        }
+    };
+    let reload = move |_| async move {
+        match reload_config().await {
+            Ok(()) => {
+                load().await;
+                message.set(Some(Ok("Reloaded the settings from disk".to_owned())));
+            }
+            Err(err) => message.set(Some(Err(err.to_string()))),
+        }
+    };
+
+    rsx! {
+        div { id: "config",
+            div { class: "name", "Settings" }
+            textarea {
+                spellcheck: false,
+                value: text,
+                oninput: move |event| text.set(event.value()),
+            }
+            div {
+                button { onclick: save, "Save" }
+                button { onclick: reload, "Reload from disk" }
+            }
+            match message() {
+                Some(Ok(message)) => rsx! {
+                    div { class: "message", "{message}" }
+                },
+                Some(Err(error)) => rsx! {
+                    div { class: "message error", "{error}" }
+                },
+                None => rsx! {},
+            }
+        }
+    }
+}
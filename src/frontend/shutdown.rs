@@ -1,28 +1,147 @@
-use dioxus::prelude::*;
+use std::fmt;
 
+use dioxus::{
+    fullstack::{WebSocketOptions, use_websocket},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::backend::shutdown::{
+    cancel_power_action, power_redirect, power_state, start_power_action,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PowerAction {
+    PowerOff,
+    Reboot,
+    Suspend,
+    Hibernate,
+    /// Blank the screen, without stopping anything
+    ScreenOff,
+    /// Stop EMPC, leaving the media PC running
+    Quit,
+}
+
+impl PowerAction {
+    pub const ALL: [PowerAction; 6] = [
+        PowerAction::PowerOff,
+        PowerAction::Reboot,
+        PowerAction::Suspend,
+        PowerAction::Hibernate,
+        PowerAction::ScreenOff,
+        PowerAction::Quit,
+    ];
+
+    /// Whether EMPC stops answering after this, so the browser should go to the redirect page.
+    pub fn leaves(self) -> bool {
+        self != PowerAction::ScreenOff
+    }
+}
+
+impl fmt::Display for PowerAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PowerAction::PowerOff => "Power off",
+            PowerAction::Reboot => "Reboot",
+            PowerAction::Suspend => "Suspend",
+            PowerAction::Hibernate => "Hibernate",
+            PowerAction::ScreenOff => "Turn off the screen",
+            PowerAction::Quit => "Quit EMPC",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub enum PowerState {
+    #[default]
+    Idle,
+    /// Counting down, and can still be cancelled
+    Pending {
+        action: PowerAction,
+        secs_left: u32,
+    },
+    Running(PowerAction),
+    Failed(String),
+}
+
+/// Powers off, reboots or suspends the media PC, after confirming and counting down.
 #[component]
 pub fn Shutdown() -> Element {
+    let redirect = use_server_future(|| async { power_redirect().await.ok().flatten() })?;
+    let redirect = redirect().flatten();
+
+    let socket = use_websocket(|| power_state(WebSocketOptions::new()));
+    let mut state = use_signal(PowerState::default);
+    let mut confirming = use_signal(|| Option::<PowerAction>::None);
+    let mut error = use_signal(|| Option::<String>::None);
+
+    use_future(move || async move {
+        loop {
+            match socket.recv().await {
+                Ok(new_state) => *state.write() = new_state,
+                Err(err) => {
+                    warn!("socket.recv() returned an error: {err}");
+                    return;
+                }
+            }
+        }
+    });
+
+    let start = move |action: PowerAction| async move {
+        confirming.set(None);
+        match start_power_action(action, true).await {
+            Ok(()) => error.set(None),
+            Err(err) => error.set(Some(format!("Failed to start {action}: {err}"))),
+        }
+    };
+    let cancel = move |_| async move {
+        if let Err(err) = cancel_power_action().await {
+            error.set(Some(format!("Failed to cancel: {err}")));
+        }
+    };
+
     rsx! {
-        meta { http_equiv: "refresh", content: "2;url=http://empc.emilie.moe/" }
         div { id: "parts",
-            form { class: "part",
-                div { class: "name", "URL or Magnet Link:" }
-                input { disabled: true, r#type: "url" }
-                button { disabled: true, "Play" }
-            }
-            form { class: "part",
-                div { class: "name", "File:" }
-                input { disabled: true, r#type: "file" }
-                button { disabled: true, "Play" }
-            }
-            form { class: "part",
-                button { disabled: true, class: "link", "Remote" }
+            match state() {
+                PowerState::Pending { action, secs_left } => rsx! {
+                    div { class: "part",
+                        div { class: "name", "{action} in {secs_left} s" }
+                        button { class: "link", onclick: cancel, "Cancel" }
+                    }
+                },
+                PowerState::Running(action) => rsx! {
+                    if let Some(redirect) = redirect.filter(|_| action.leaves()) {
+                        meta { http_equiv: "refresh", content: "2;url={redirect}" }
+                    }
+                    div { class: "part",
+                        button { disabled: true, class: "link", "{action}..." }
+                    }
+                },
+                PowerState::Idle | PowerState::Failed(_) => rsx! {
+                    for action in PowerAction::ALL {
+                        if confirming() == Some(action) {
+                            div { class: "part confirm",
+                                div { class: "name", "{action}?" }
+                                button { onclick: move |_| start(action), "Yes" }
+                                button { onclick: move |_| confirming.set(None), "No" }
+                            }
+                        } else {
+                            div { class: "part",
+                                button {
+                                    class: "link",
+                                    onclick: move |_| confirming.set(Some(action)),
+                                    "{action}"
+                                }
+                            }
+                        }
+                    }
+                },
             }
-            form { class: "part",
-                button { disabled: true, class: "link", "Local Media" }
+            if let PowerState::Failed(err) = state() {
+                div { class: "part error", "{err}" }
             }
-            form { class: "part",
-                button { disabled: true, class: "link", "Shutting down..." }
+            if let Some(err) = error() {
+                div { class: "part error", "{err}" }
             }
         }
     }
@@ -3,16 +3,38 @@ mod frontend;
 
 fn main() {
     #[cfg(feature = "server")]
-    print_addr();
+    {
+        if let Err(err) = backend::config::load() {
+            eprintln!("{err:#}");
+            std::process::exit(1);
+        }
+        set_addr();
+        backend::discovery::start();
+    }
 
     frontend::run();
 }
 
+/// Serve at the configured address, unless `IP` or `PORT` are set, like `dx serve` does.
 #[cfg(feature = "server")]
-fn print_addr() {
+fn set_addr() {
     use std::env;
 
-    let ip = env::var("IP").unwrap_or("127.0.0.1".into());
-    let port = env::var("PORT").unwrap_or("8080".into());
+    let bind = backend::config::get().bind;
+    // SAFETY: no other threads have been started yet
+    unsafe {
+        if env::var_os("IP").is_none() {
+            env::set_var("IP", bind.ip().to_string());
+        }
+        if env::var_os("PORT").is_none() {
+            env::set_var("PORT", bind.port().to_string());
+        }
+    }
+
+    let ip = env::var("IP").unwrap_or_default();
+    let port = env::var("PORT").unwrap_or_default();
     eprintln!("Serving at: {}:{}", ip, port);
+    if let Some(path) = backend::auth::pairing_screen_path() {
+        eprintln!("Pairing screen for the TV: {}", path);
+    }
 }